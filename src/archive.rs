@@ -0,0 +1,1854 @@
+use crate::binary;
+use crate::checksum::{compute_checksum, compute_workspace_hash, to_hex, ChecksumAlgorithm};
+use crate::comments;
+use crate::config::ExtraFile;
+use crate::confirm;
+use crate::logging::Emoji;
+use crate::progress::ProgressFile;
+use crate::solution_template;
+use crate::unused_use::UnusedImports;
+use anyhow::Context;
+use ignore::DirEntry;
+use sha2::Digest;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{IsTerminal, Read, Seek, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use zip::write::{FileOptions, SimpleFileOptions};
+use zip::{AesMode, CompressionMethod, ZipWriter};
+
+/// Options that affect which files are stored in the archive and how.
+#[derive(Debug, Default)]
+pub struct ArchiveOptions {
+    pub strip_prefix: Option<PathBuf>,
+    pub add_prefix: Option<PathBuf>,
+    pub flatten: bool,
+    pub exclude_tests: bool,
+    pub extra_files: Vec<ExtraFile>,
+    /// Paths (relative to the workspace root) that should never be included.
+    pub excluded_paths: Vec<PathBuf>,
+    /// Directories of git submodules (relative to the workspace root) whose files should
+    /// not be included.
+    pub excluded_submodules: Vec<PathBuf>,
+    pub macos_metadata: bool,
+    /// Files created by `cargo kelvin` that should be stored in the archive.
+    pub generated_files: Vec<GeneratedFile>,
+    /// Vendored dependencies that should be stored in the archive.
+    pub vendored_files: Option<Vec<(PathBuf, PathBuf)>>,
+    /// Compiled binaries that should be stored in the archive, with their paths in the archive.
+    pub binaries: Vec<(PathBuf, PathBuf)>,
+    /// Only include workspace files modified after this time.
+    pub changed_since: Option<SystemTime>,
+    /// Existing ZIP archive whose entries should be stored in the archive, unless they
+    /// conflict with files from the workspace.
+    pub append_to_archive: Option<PathBuf>,
+    /// Only store Cargo manifests and lockfiles, together with a list of the other files.
+    pub metadata_only: bool,
+    /// Normalize the formatting of `Cargo.toml` files stored in the archive.
+    pub format_manifests: bool,
+    /// Format all TOML files stored in the archive with `taplo`.
+    pub format_toml: bool,
+    /// Remove comments from Rust files stored in the archive.
+    pub strip_comments: bool,
+    /// Warn about Rust and TOML files that are not valid UTF-8.
+    pub verify_utf8: bool,
+    /// Fail if a Rust or TOML file is not valid UTF-8.
+    pub abort_on_non_utf8: bool,
+    /// Encoding from which Rust and TOML files that are not valid UTF-8 are converted.
+    pub fix_utf8: Option<&'static encoding_rs::Encoding>,
+    /// Imports that should be removed from Rust files stored in the archive.
+    pub unused_imports: Option<UnusedImports>,
+    /// Path of the workspace that should be replaced with `<workspace>` in Rust files.
+    pub anonymized_root: Option<String>,
+    /// Remove debug symbols from executables among the extra files.
+    pub strip_debug_symbols: bool,
+    /// Store a hash of all files of the archive in the ZIP comment.
+    pub workspace_hash: bool,
+    /// Password used to encrypt the files in the archive with AES-256.
+    pub encryption_password: Option<String>,
+    /// Compression level of the files, the default level is used if not given.
+    pub compression_level: Option<i64>,
+    /// Compression levels of files with the given extensions, `0` means no compression.
+    pub extension_levels: HashMap<String, i64>,
+    /// Align the data of the files in the archive to this number of bytes.
+    pub zip_alignment: Option<u16>,
+    /// Username that should be replaced in Rust files, together with removing `authors`
+    /// from Cargo manifests.
+    pub redacted_author: Option<String>,
+    /// Seed used to replace paths in the archive by their hashes.
+    pub hash_seed: Option<String>,
+    /// Truncate paths in the archive that are longer than the given number of bytes.
+    pub clip_paths: Option<usize>,
+    /// Fail if a path in the archive is longer than this number of bytes.
+    pub max_path_length: Option<usize>,
+    /// Only include files in the workspace root and its direct subdirectories.
+    pub root_only: bool,
+    /// Only include files from `src`, together with the manifest and the lockfile.
+    pub src_only: bool,
+    /// Include files of all types from the `examples` directory.
+    pub include_examples: bool,
+    /// Do not include the `examples` directory.
+    pub exclude_examples: bool,
+    /// Include templates of `.env` files, see [`DOTENV_TEMPLATES`].
+    pub include_dotenv: bool,
+    /// Include files ignored by `.gitignore` and `.ignore` files. `.kelvinignore` files
+    /// are still respected.
+    pub include_ignored: bool,
+    /// Fail if a file cannot be read or written to the archive, instead of skipping it.
+    pub fail_fast_archive: bool,
+    /// Fail if the workspace cannot be walked, instead of skipping the affected files.
+    pub fail_fast_walk: bool,
+    /// Only include Rust files from this set of canonical paths, if it is set.
+    pub feature_sources: Option<HashSet<PathBuf>>,
+    /// SHA-256 hashes of files (by their archive path) in the previous submit. If set, files
+    /// of the workspace with the same hash are not stored and the archive contains
+    /// a [`DELTA_MANIFEST`] instead.
+    pub delta_base: Option<HashMap<PathBuf, String>>,
+    /// Build scripts and directories of proc-macro crates (relative to the workspace root)
+    /// that are included even with `src_only` or `root_only`.
+    pub proc_macro_paths: Vec<PathBuf>,
+    /// Signatures required by the solution template, which are added as a comment to
+    /// `src/lib.rs`.
+    pub required_signatures: Vec<String>,
+    /// Rust edition that should be used by all packages in the archive.
+    pub required_edition: Option<String>,
+    /// File to which the progress of the compression is reported.
+    pub progress: Option<Rc<ProgressFile>>,
+    /// How many workspace files are read at once.
+    pub max_concurrent_file_reads: usize,
+}
+
+/// Workspace file selected to be stored in the archive.
+struct SelectedFile {
+    /// Index of the file in the walk, used to report progress.
+    index: usize,
+    path: PathBuf,
+    relative_path: PathBuf,
+    archive_path: PathBuf,
+}
+
+/// Prefix of the ZIP comment that contains the workspace hash.
+const WORKSPACE_HASH_PREFIX: &str = "workspace-hash: sha256:";
+
+/// Returns the workspace hash stored in the comment of the archive, if there is one.
+pub fn read_workspace_hash(archive: &[u8]) -> Option<String> {
+    let zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).ok()?;
+    let comment = String::from_utf8_lossy(zip.comment()).into_owned();
+    comment
+        .strip_prefix(WORKSPACE_HASH_PREFIX)
+        .map(|hash| hash.to_string())
+}
+
+/// Name of the file that lists all files of the workspace in a metadata-only archive.
+pub const FILE_LIST: &str = ".kelvin-files.txt";
+
+/// Name of the file that maps original paths to hashed paths when filenames are hashed.
+pub const FILENAME_MANIFEST: &str = "filename_manifest.json";
+
+/// Name of the file that lists the files of the workspace with their hashes in a delta
+/// archive, so that Kelvin can take the unchanged ones from the previous submit.
+pub const DELTA_MANIFEST: &str = "manifest.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeltaManifest {
+    pub files: Vec<DeltaFile>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct DeltaFile {
+    pub path: PathBuf,
+    pub sha256: String,
+    /// The file is not stored in the archive, because it did not change since
+    /// the previous submit.
+    pub unchanged: bool,
+}
+
+/// Reads the delta manifest of an archive, if it has one.
+pub fn read_delta_manifest(archive: &[u8]) -> anyhow::Result<Option<DeltaManifest>> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut file = match zip.by_name(DELTA_MANIFEST) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(error) => return Err(error).context("cannot read delta manifest"),
+    };
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .context("cannot read delta manifest")?;
+    serde_json::from_str(&content)
+        .map(Some)
+        .context("cannot parse delta manifest")
+}
+
+/// How many bytes of the SHA-256 digest are used in hashed filenames.
+const HASHED_NAME_BYTES: usize = 6;
+
+/// Replaces paths in the archive by a hash of the seed and the original path, keeping
+/// the extension of the file.
+struct FilenameHasher<'a> {
+    seed: &'a str,
+    /// Original paths mapped to their hashed names.
+    names: BTreeMap<String, String>,
+}
+
+impl<'a> FilenameHasher<'a> {
+    fn new(seed: &'a str) -> Self {
+        Self {
+            seed,
+            names: BTreeMap::new(),
+        }
+    }
+
+    fn hash(&mut self, path: &Path) -> PathBuf {
+        let original = path.to_string_lossy().replace('\\', "/");
+        let mut hasher = sha2::Sha256::new();
+        hasher.update(self.seed.as_bytes());
+        hasher.update([0]);
+        hasher.update(original.as_bytes());
+        let mut name = to_hex(&hasher.finalize()[..HASHED_NAME_BYTES]);
+        if let Some(extension) = path.extension() {
+            name.push('.');
+            name.push_str(&extension.to_string_lossy());
+        }
+        self.names.insert(original, name.clone());
+        PathBuf::from(name)
+    }
+}
+
+/// Hashes the path if filenames should be hashed.
+fn hashed_path(hasher: &mut Option<FilenameHasher>, path: PathBuf) -> PathBuf {
+    match hasher {
+        Some(hasher) => hasher.hash(&path),
+        None => path,
+    }
+}
+
+/// Checks the length of the path, and truncates it if it is too long and paths should be
+/// clipped.
+fn clipped_path(options: &ArchiveOptions, path: PathBuf) -> anyhow::Result<PathBuf> {
+    let length = path.as_os_str().len();
+    if let Some(max_length) = options.max_path_length.filter(|&max| length > max) {
+        anyhow::bail!(
+            "Path {path:?} in the archive is longer than {max_length} bytes ({length} bytes)"
+        );
+    }
+    match options.clip_paths.filter(|&max| length > max) {
+        Some(max_length) => {
+            let clipped = clip_path(&path, max_length)?;
+            log::warn!("Path {path:?} is too long, storing it as {clipped:?}");
+            Ok(clipped)
+        }
+        None => Ok(path),
+    }
+}
+
+/// Truncates the path to at most `max_length` bytes. The extension is kept and a hash of
+/// the original path is appended to the truncated path, so that clipped paths stay unique.
+fn clip_path(path: &Path, max_length: usize) -> anyhow::Result<PathBuf> {
+    let original = path.to_string_lossy().replace('\\', "/");
+    let hash = to_hex(&sha2::Sha256::digest(original.as_bytes())[..HASHED_NAME_BYTES]);
+    let extension = path
+        .extension()
+        .map(|extension| format!(".{}", extension.to_string_lossy()))
+        .unwrap_or_default();
+    let suffix = format!("-{hash}{extension}");
+    let Some(mut prefix_length) = max_length.checked_sub(suffix.len()) else {
+        anyhow::bail!("Cannot clip path {path:?} to {max_length} bytes");
+    };
+    let stem = &original[..original.len() - extension.len()];
+    prefix_length = prefix_length.min(stem.len());
+    while !stem.is_char_boundary(prefix_length) {
+        prefix_length -= 1;
+    }
+    Ok(PathBuf::from(format!("{}{suffix}", &stem[..prefix_length])))
+}
+
+/// Files that are stored in metadata-only archives.
+fn is_metadata_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name == "Cargo.toml" || name == "Cargo.lock")
+}
+
+#[derive(Debug)]
+pub struct GeneratedFile {
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+}
+
+impl ArchiveOptions {
+    /// Computes the path under which a file should be stored in the archive.
+    pub fn archive_path(&self, relative_path: &Path) -> PathBuf {
+        let path = if self.flatten {
+            relative_path
+                .file_name()
+                .map(PathBuf::from)
+                .unwrap_or_else(|| relative_path.to_path_buf())
+        } else {
+            match &self.strip_prefix {
+                Some(prefix) => relative_path
+                    .strip_prefix(prefix)
+                    .unwrap_or(relative_path)
+                    .to_path_buf(),
+                None => relative_path.to_path_buf(),
+            }
+        };
+        match &self.add_prefix {
+            Some(prefix) => prefix.join(path),
+            None => path,
+        }
+    }
+
+    /// Returns the compression level of a file, based on its extension.
+    fn compression_level_for(&self, path: &Path) -> Option<i64> {
+        path.extension()
+            .and_then(|ext| self.extension_levels.get(ext.to_string_lossy().as_ref()))
+            .copied()
+            .or(self.compression_level)
+    }
+}
+
+/// Which part of the workspace should be submitted.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SubmitScope {
+    /// The whole workspace.
+    Workspace,
+    /// Only the package in the current directory.
+    Package,
+}
+
+/// Finds the manifest whose directory should be submitted.
+///
+/// If the current directory is inside a package that is not at the root of the workspace
+/// and no scope was given, the user is asked whether only that package should be submitted.
+pub fn get_manifest_path(scope: Option<SubmitScope>) -> anyhow::Result<PathBuf> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .exec()
+        .context("getting cargo metadata")?;
+    let workspace_manifest = metadata
+        .workspace_root
+        .clone()
+        .into_std_path_buf()
+        .join("Cargo.toml");
+
+    let current_dir = std::env::current_dir()
+        .and_then(|dir| dir.canonicalize())
+        .context("cannot find current directory")?;
+    let package = metadata
+        .workspace_packages()
+        .into_iter()
+        .filter_map(|package| {
+            let dir = package
+                .manifest_path
+                .parent()?
+                .as_std_path()
+                .canonicalize()
+                .ok()?;
+            current_dir.starts_with(&dir).then_some((package, dir))
+        })
+        .max_by_key(|(_, dir)| dir.components().count());
+
+    let (package, package_dir) = match package {
+        Some((package, dir)) if dir != metadata.workspace_root.as_std_path() => (package, dir),
+        Some((_, dir)) if scope == Some(SubmitScope::Package) => {
+            return Ok(dir.join("Cargo.toml"));
+        }
+        _ if scope == Some(SubmitScope::Package) => {
+            anyhow::bail!(
+                "The current directory is not inside a package, it cannot be submitted with `--scope package`"
+            );
+        }
+        _ => return Ok(workspace_manifest),
+    };
+    let submit_package = match scope {
+        Some(scope) => scope == SubmitScope::Package,
+        None if std::io::stdin().is_terminal() => confirm(&format!(
+            "You are inside package `{}`. Do you want to submit only this package instead of the whole workspace?",
+            package.name
+        ), false)?,
+        None => {
+            log::info!(
+                "Submitting the whole workspace, use `--scope package` to submit only package `{}`",
+                package.name
+            );
+            false
+        }
+    };
+    Ok(if submit_package {
+        log::info!("Submitting only package `{}`", package.name);
+        package_dir.join("Cargo.toml")
+    } else {
+        workspace_manifest
+    })
+}
+
+/// Name of files with additional ignore rules (in the `.gitignore` format) for the archive.
+/// They are respected even with `--include-ignored`.
+pub const KELVIN_IGNORE_FILE: &str = ".kelvinignore";
+
+/// Files that are never stored in the archive, because they usually contain secrets.
+const NEVER_ARCHIVED_FILES: [&str; 1] = [".env"];
+
+/// Templates of `.env` files with placeholder values, included with `--include-dotenv`.
+pub const DOTENV_TEMPLATES: [&str; 3] = [".env.example", ".env.test", ".env.template"];
+
+fn is_never_archived(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| NEVER_ARCHIVED_FILES.iter().any(|file| name == *file))
+}
+
+/// Checks if the entry should be stored in the archive. Files in `any_extension_dir`
+/// are stored regardless of their extension.
+fn is_valid_path(
+    entry: &DirEntry,
+    allow_macos_metadata: bool,
+    any_extension_dir: Option<&Path>,
+    include_dotenv: bool,
+) -> bool {
+    let path = entry.path();
+    if is_never_archived(path) {
+        return false;
+    }
+    if !allow_macos_metadata && is_macos_metadata(path) {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy();
+    if is_invalid_file_name(&name) {
+        log::warn!("Skipping {path:?}, its name cannot be stored in the archive");
+        return false;
+    }
+    if entry.depth() > 0 && name.starts_with('.') {
+        // Hidden files and directories are skipped, except for templates of `.env` files
+        return include_dotenv && path.is_file() && DOTENV_TEMPLATES.contains(&name.as_ref());
+    }
+    if path.is_dir() {
+        return true;
+    }
+    if any_extension_dir.is_some_and(|dir| path.starts_with(dir)) {
+        return path.is_file();
+    }
+    // The legacy toolchain file has no extension
+    path.is_file()
+        && (path.extension().is_some_and(|ext| {
+            ext == "toml" || ext == "lock" || ext == "rs" || ext == "md" || ext == "txt"
+        }) || path
+            .file_name()
+            .is_some_and(|name| name == "rust-toolchain"))
+}
+
+/// Checks if the file name contains a NUL byte or, on Windows, a `:`, which separates the
+/// name of a file from the name of its NTFS alternate data stream.
+fn is_invalid_file_name(name: &str) -> bool {
+    name.contains('\0') || (cfg!(windows) && name.contains(':'))
+}
+
+/// Checks if the path belongs to metadata created by macOS (AppleDouble `._` files,
+/// `.DS_Store` files or `__MACOSX` directories).
+fn is_macos_metadata(path: &Path) -> bool {
+    path.file_name().is_some_and(|name| {
+        let name = name.to_string_lossy();
+        name.starts_with("._") || name == ".DS_Store"
+    }) || path.iter().any(|component| component == "__MACOSX")
+}
+
+/// Checks if the file was modified after the given time.
+/// Cargo manifests and lockfiles are needed by the grader, so they always count as modified.
+fn is_modified_since(entry: &DirEntry, time: SystemTime) -> bool {
+    if entry.file_name() == "Cargo.toml" || entry.file_name() == "Cargo.lock" {
+        return true;
+    }
+    entry
+        .metadata()
+        .ok()
+        .and_then(|metadata| metadata.modified().ok())
+        .is_none_or(|modified| modified > time)
+}
+
+/// Heuristically checks if the file contains tests.
+fn is_test_file(relative_path: &Path, fs_path: &Path) -> bool {
+    if relative_path
+        .parent()
+        .is_some_and(|dir| dir.iter().any(|c| c == "tests" || c == "benches"))
+    {
+        return true;
+    }
+    if relative_path.extension().is_none_or(|ext| ext != "rs") {
+        return false;
+    }
+    let mut prefix = Vec::with_capacity(1000);
+    let Ok(file) = std::fs::File::open(fs_path) else {
+        return false;
+    };
+    if file.take(1000).read_to_end(&mut prefix).is_err() {
+        return false;
+    }
+    String::from_utf8_lossy(&prefix).contains("#[cfg(test)]")
+}
+
+pub fn compress_workspace(
+    manifest_path: PathBuf,
+    options: &ArchiveOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let root_dir = manifest_path.parent().expect("Manifest path has no parent");
+
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let mut file_options = zip_file_options(options.encryption_password.as_deref());
+    if let Some(alignment) = options.zip_alignment {
+        file_options = file_options.with_alignment(alignment);
+    }
+    // Files included in the workspace hash
+    let mut hashed_files: Vec<(PathBuf, Vec<u8>)> = Vec::new();
+    let mut archive_paths: HashMap<PathBuf, PathBuf> = HashMap::new();
+
+    let mut file_count = 0;
+    // Files omitted from a metadata-only archive and their total size
+    let mut omitted_files: Vec<PathBuf> = Vec::new();
+    let mut omitted_size = 0;
+    let mut hasher = options.hash_seed.as_deref().map(FilenameHasher::new);
+    let mut delta_files: Vec<DeltaFile> = Vec::new();
+    let allow_macos_metadata = options.macos_metadata;
+    let examples_dir = options.include_examples.then(|| root_dir.join("examples"));
+    let include_dotenv = options.include_dotenv;
+    let mut walker = if options.src_only {
+        let mut walker = ignore::WalkBuilder::new(root_dir.join("src"));
+        for file in ["Cargo.toml", "Cargo.lock"] {
+            let path = root_dir.join(file);
+            if path.is_file() {
+                walker.add(path);
+            }
+        }
+        walker
+    } else {
+        ignore::WalkBuilder::new(root_dir)
+    };
+    if options.root_only {
+        walker.max_depth(Some(2));
+    }
+    let target_dir = root_dir.join("target");
+    let walk_files = |mut walker: ignore::WalkBuilder, respect_ignores: bool| -> Vec<_> {
+        let examples_dir = examples_dir.clone();
+        let target_dir = target_dir.clone();
+        walker
+            .max_filesize(Some(1024 * 1024))
+            .same_file_system(true)
+            .ignore(respect_ignores)
+            .git_ignore(respect_ignores)
+            .git_exclude(respect_ignores)
+            .git_global(respect_ignores)
+            .add_custom_ignore_filename(KELVIN_IGNORE_FILE)
+            // Hidden files are filtered by `is_valid_path`, to find `.env` files
+            .hidden(false)
+            .filter_entry(move |entry| {
+                // The build directory is skipped anyway, this avoids walking it
+                entry.path() != target_dir
+                    && is_valid_path(
+                        entry,
+                        allow_macos_metadata,
+                        examples_dir.as_deref(),
+                        include_dotenv,
+                    )
+            })
+            .build()
+            // Directories are skipped, so that they are not counted in the progress
+            .filter(|file| file.as_ref().map_or(true, |file| !file.path().is_dir()))
+            .collect()
+    };
+    // Files that are walked without `include_ignored`, to warn about the other ones
+    let not_ignored: Option<HashSet<PathBuf>> = options.include_ignored.then(|| {
+        walk_files(walker.clone(), true)
+            .into_iter()
+            .filter_map(|file| file.ok())
+            .map(|file| file.path().to_path_buf())
+            .collect()
+    });
+    let mut files = walk_files(walker, !options.include_ignored);
+    if options.src_only || options.root_only {
+        // These can be outside of `src` or deeper than the directories of the workspace root
+        let mut seen: HashSet<PathBuf> = files
+            .iter()
+            .filter_map(|file| file.as_ref().ok())
+            .map(|file| file.path().to_path_buf())
+            .collect();
+        for path in &options.proc_macro_paths {
+            let path = root_dir.join(path);
+            if !path.exists() {
+                continue;
+            }
+            files.extend(
+                walk_files(ignore::WalkBuilder::new(path), !options.include_ignored)
+                    .into_iter()
+                    .filter(|file| {
+                        file.as_ref()
+                            .map_or(true, |file| seen.insert(file.path().to_path_buf()))
+                    }),
+            );
+        }
+    }
+    let extra_files = options
+        .extra_files
+        .iter()
+        .map(|extra_file| resolve_extra_file(root_dir, extra_file))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let extra_files: Vec<(PathBuf, PathBuf)> = extra_files
+        .into_iter()
+        .flatten()
+        .chain(options.vendored_files.iter().flatten().cloned())
+        .chain(options.binaries.iter().cloned())
+        .collect();
+    let walked_files = files.len();
+    let total_files = walked_files + extra_files.len() + options.generated_files.len();
+    let report_progress = |done: usize| {
+        if let Some(progress) = &options.progress {
+            progress.set_compressed_files(done, total_files);
+        }
+    };
+    report_progress(0);
+    let mut selected: Vec<SelectedFile> = Vec::new();
+    for (index, file) in files.into_iter().enumerate() {
+        match file {
+            Ok(file) => {
+                if is_never_archived(file.path()) {
+                    log::warn!(
+                        "Found {:?}, which usually contains secrets. It is not included in the archive, but make sure that it is not shared elsewhere",
+                        file.path()
+                    );
+                    continue;
+                }
+                if !is_valid_path(
+                    &file,
+                    allow_macos_metadata,
+                    examples_dir.as_deref(),
+                    include_dotenv,
+                ) {
+                    continue;
+                }
+                if file.path().is_dir() {
+                    continue;
+                }
+                if file.path() == root_dir {
+                    continue;
+                }
+                let Ok(relative_path) = file.path().strip_prefix(root_dir) else {
+                    continue;
+                };
+                if relative_path.starts_with("target") {
+                    continue;
+                }
+                if options.exclude_examples && relative_path.starts_with("examples") {
+                    log::debug!("Excluding example {relative_path:?}");
+                    continue;
+                }
+                // The vendored dependencies replace the `vendor` directory of the workspace
+                if options.vendored_files.is_some() && relative_path.starts_with("vendor") {
+                    continue;
+                }
+                if options
+                    .excluded_paths
+                    .iter()
+                    .any(|path| path == relative_path)
+                {
+                    log::debug!("Excluding file {relative_path:?}");
+                    continue;
+                }
+                if options
+                    .excluded_submodules
+                    .iter()
+                    .any(|path| relative_path.starts_with(path))
+                {
+                    log::debug!("Excluding file {relative_path:?} of a git submodule");
+                    continue;
+                }
+                if let Some(changed_since) = options.changed_since {
+                    if !is_modified_since(&file, changed_since) {
+                        log::debug!("Excluding unchanged file {relative_path:?}");
+                        continue;
+                    }
+                }
+                if options.exclude_tests && is_test_file(relative_path, file.path()) {
+                    log::debug!("Excluding test file {relative_path:?}");
+                    continue;
+                }
+                if let Some(sources) = &options.feature_sources {
+                    if relative_path.extension().is_some_and(|ext| ext == "rs")
+                        && !file
+                            .path()
+                            .canonicalize()
+                            .is_ok_and(|path| sources.contains(&path))
+                    {
+                        log::debug!(
+                            "Excluding file not used with the selected features {relative_path:?}"
+                        );
+                        continue;
+                    }
+                }
+                if not_ignored
+                    .as_ref()
+                    .is_some_and(|files| !files.contains(file.path()))
+                {
+                    log::warn!("Including ignored file {relative_path:?}");
+                }
+                let archive_path = clipped_path(
+                    options,
+                    hashed_path(&mut hasher, options.archive_path(relative_path)),
+                )?;
+                if !claim_archive_path(&mut archive_paths, &archive_path, relative_path) {
+                    continue;
+                }
+                if options.metadata_only && !is_metadata_file(relative_path) {
+                    omitted_size += file.metadata().map(|m| m.len()).unwrap_or(0);
+                    omitted_files.push(archive_path);
+                    continue;
+                }
+                selected.push(SelectedFile {
+                    index,
+                    path: file.path().to_path_buf(),
+                    relative_path: relative_path.to_path_buf(),
+                    archive_path,
+                });
+            }
+            Err(error) if options.fail_fast_walk => {
+                return Err(anyhow::Error::new(error).context("Cannot walk the workspace"));
+            }
+            Err(error) => log::warn!("Cannot include file {error:?}"),
+        }
+    }
+    // Files are read in parallel, but written to the archive in the order of the walk
+    let read_paths: Vec<(PathBuf, PathBuf)> = selected
+        .iter()
+        .map(|file| (file.path.clone(), file.archive_path.clone()))
+        .collect();
+    let contents = read_files_parallel(&read_paths, options.max_concurrent_file_reads);
+    for (file, (archive_path, content)) in selected.into_iter().zip(contents) {
+        report_progress(file.index);
+        let bytes = match content {
+            Ok(bytes) => ensure_utf8(&file.relative_path, bytes, options)?,
+            Err(error) if options.fail_fast_archive => return Err(error),
+            Err(error) => {
+                log::warn!("{error:?}");
+                continue;
+            }
+        };
+        let written = process_workspace_file(&file.relative_path, &file.path, bytes, options)
+            .and_then(|bytes| {
+                if let Some(base) = &options.delta_base {
+                    let sha256 = compute_checksum(ChecksumAlgorithm::Sha256, &bytes);
+                    let unchanged = base.get(&archive_path) == Some(&sha256);
+                    delta_files.push(DeltaFile {
+                        path: archive_path.clone(),
+                        sha256,
+                        unchanged,
+                    });
+                    if unchanged {
+                        return Ok((bytes, false));
+                    }
+                }
+                let level = options.compression_level_for(&file.relative_path);
+                write_bytes_to_zip(&mut zip, file_options, &archive_path, &bytes, level)?;
+                Ok((bytes, true))
+            });
+        match written {
+            Ok((bytes, stored)) => {
+                if stored {
+                    file_count += 1;
+                }
+                if options.workspace_hash {
+                    hashed_files.push((archive_path, bytes));
+                }
+            }
+            Err(error) if options.fail_fast_archive => {
+                return Err(
+                    error.context(format!("Cannot write file {:?} to ZIP archive", file.path))
+                );
+            }
+            Err(error) => log::warn!(
+                "Cannot write file {:?} to ZIP archive: {error:?}",
+                file.path
+            ),
+        }
+    }
+    let generated_start = walked_files + extra_files.len();
+    for (index, (fs_path, archive_path)) in extra_files.into_iter().enumerate() {
+        report_progress(walked_files + index);
+        let archive_path = clipped_path(
+            options,
+            hashed_path(&mut hasher, options.archive_path(&archive_path)),
+        )?;
+        if !claim_archive_path(&mut archive_paths, &archive_path, &fs_path) {
+            continue;
+        }
+        if options.metadata_only {
+            omitted_size += std::fs::metadata(&fs_path).map(|m| m.len()).unwrap_or(0);
+            omitted_files.push(archive_path);
+            continue;
+        }
+        let written = read_extra_file(&fs_path, options).and_then(|bytes| {
+            let level = options.compression_level_for(&fs_path);
+            write_bytes_to_zip(&mut zip, file_options, &archive_path, &bytes, level)?;
+            Ok(bytes)
+        });
+        match written {
+            Ok(bytes) => {
+                file_count += 1;
+                if options.workspace_hash {
+                    hashed_files.push((archive_path, bytes));
+                }
+            }
+            Err(error) if options.fail_fast_archive => {
+                return Err(error.context(format!("Cannot write file {fs_path:?} to ZIP archive")));
+            }
+            Err(error) => log::warn!("Cannot write file {fs_path:?} to ZIP archive: {error:?}"),
+        }
+    }
+    for (index, file) in options.generated_files.iter().enumerate() {
+        report_progress(generated_start + index);
+        let archive_path = clipped_path(
+            options,
+            hashed_path(&mut hasher, options.archive_path(&file.path)),
+        )?;
+        if !claim_archive_path(&mut archive_paths, &archive_path, Path::new("<generated>")) {
+            continue;
+        }
+        let level = options.compression_level_for(&file.path);
+        write_bytes_to_zip(&mut zip, file_options, &archive_path, &file.content, level)?;
+        file_count += 1;
+        if options.workspace_hash {
+            hashed_files.push((archive_path, file.content.clone()));
+        }
+    }
+    report_progress(total_files);
+    if options.delta_base.is_some() {
+        let unchanged = delta_files.iter().filter(|file| file.unchanged).count();
+        let manifest = serde_json::to_string_pretty(&DeltaManifest { files: delta_files })
+            .context("cannot serialize delta manifest")?;
+        write_bytes_to_zip(
+            &mut zip,
+            file_options,
+            Path::new(DELTA_MANIFEST),
+            manifest.as_bytes(),
+            options.compression_level,
+        )?;
+        file_count += 1;
+        log::info!(
+            "{unchanged} unchanged file{} not stored, they are listed in `{DELTA_MANIFEST}`",
+            if unchanged == 1 { " is" } else { "s are" }
+        );
+    }
+    if let Some(hasher) = hasher {
+        let manifest = serde_json::to_string_pretty(&hasher.names)
+            .context("cannot serialize filename manifest")?;
+        write_bytes_to_zip(
+            &mut zip,
+            file_options,
+            Path::new(FILENAME_MANIFEST),
+            manifest.as_bytes(),
+            options.compression_level,
+        )?;
+        file_count += 1;
+        log::info!(
+            "Hashed {} filename{}, the original paths are stored in `{FILENAME_MANIFEST}`",
+            hasher.names.len(),
+            if hasher.names.len() == 1 { "" } else { "s" }
+        );
+    }
+    if options.changed_since.is_some() && file_count < 2 {
+        log::warn!(
+            "Only {file_count} file{} changed recently enough, is the duration passed to `--limit-to-changed-since` correct?",
+            if file_count == 1 { "" } else { "s" }
+        );
+    }
+    if options.metadata_only {
+        let list = omitted_files
+            .iter()
+            .map(|path| format!("{}\n", path.display()))
+            .collect::<String>();
+        write_bytes_to_zip(
+            &mut zip,
+            file_options,
+            Path::new(FILE_LIST),
+            list.as_bytes(),
+            options.compression_level,
+        )?;
+        file_count += 1;
+        log::info!(
+            "Metadata-only archive omits {} file{} with a total size of {omitted_size}B",
+            omitted_files.len(),
+            if omitted_files.len() == 1 { "" } else { "s" }
+        );
+    }
+    if let Some(path) = &options.append_to_archive {
+        if options.metadata_only {
+            log::warn!("Entries of {path:?} are not included in a metadata-only archive");
+        } else {
+            if options.encryption_password.is_some() {
+                log::warn!("Entries of {path:?} are copied without encryption");
+            }
+            file_count += append_archive(&mut zip, path, &archive_paths, options.macos_metadata)?;
+        }
+    }
+    if options.workspace_hash {
+        let hash = to_hex(&compute_workspace_hash(&hashed_files));
+        log::info!("Workspace hash: {hash}");
+        zip.set_comment(format!("{WORKSPACE_HASH_PREFIX}{hash}"));
+    }
+    let data = zip
+        .finish()
+        .context("cannot create ZIP archive")?
+        .into_inner();
+    if options.zip_alignment.is_some() && log::log_enabled!(log::Level::Trace) {
+        log_alignment_padding(&data)?;
+    }
+    log::info!(
+        "{}Compressed {file_count} file{}, total size: {}B",
+        Emoji::Archive.prefix(),
+        if file_count == 1 { "" } else { "s" },
+        data.len()
+    );
+    Ok(data)
+}
+
+/// Remembers that a file from `source` is stored under `archive_path`.
+/// Returns `false` (and warns) if another file was already stored there.
+fn claim_archive_path(
+    archive_paths: &mut HashMap<PathBuf, PathBuf>,
+    archive_path: &Path,
+    source: &Path,
+) -> bool {
+    if let Some(previous) = archive_paths.get(archive_path) {
+        log::warn!(
+            "Files {previous:?} and {source:?} would both be stored as {archive_path:?}, skipping {source:?}"
+        );
+        return false;
+    }
+    archive_paths.insert(archive_path.to_path_buf(), source.to_path_buf());
+    true
+}
+
+/// Finds files matched by an extra file entry and computes the paths under which they should
+/// be stored in the archive.
+fn resolve_extra_file(
+    root_dir: &Path,
+    extra_file: &ExtraFile,
+) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    // The workspace path itself can contain characters with a special meaning in patterns
+    let escaped_root = glob::Pattern::escape(&root_dir.to_string_lossy());
+    let pattern = Path::new(&escaped_root).join(&extra_file.src);
+    let pattern = pattern.to_string_lossy();
+    let is_glob = glob::Pattern::escape(&extra_file.src) != extra_file.src;
+
+    let mut files = Vec::new();
+    for path in glob::glob(&pattern)
+        .with_context(|| anyhow::anyhow!("Invalid extra file pattern `{}`", extra_file.src))?
+    {
+        let path = path.context("cannot read extra file")?;
+        if !path.is_file() {
+            continue;
+        }
+        if is_never_archived(&path) {
+            log::warn!("Skipping extra file {path:?}, `.env` files are never included");
+            continue;
+        }
+        let name = match path.strip_prefix(root_dir) {
+            Ok(relative_path) if !extra_file.strip_workspace_prefix => relative_path.to_path_buf(),
+            _ => PathBuf::from(path.file_name().expect("File has no name")),
+        };
+        let archive_path = match &extra_file.dest {
+            Some(dest) if is_glob => dest.join(name),
+            Some(dest) => dest.clone(),
+            None => name,
+        };
+        files.push((path, archive_path));
+    }
+    if files.is_empty() {
+        log::warn!("Extra file `{}` does not match any file", extra_file.src);
+    }
+    Ok(files)
+}
+
+/// Copies entries of an existing ZIP archive that are not yet stored in the archive.
+/// Returns the number of copied files.
+fn append_archive<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    path: &Path,
+    archive_paths: &HashMap<PathBuf, PathBuf>,
+    allow_macos_metadata: bool,
+) -> anyhow::Result<usize> {
+    let file =
+        std::fs::File::open(path).with_context(|| anyhow::anyhow!("Cannot open {path:?}"))?;
+    let mut existing = zip::ZipArchive::new(std::io::BufReader::new(file))
+        .with_context(|| anyhow::anyhow!("Cannot read ZIP archive {path:?}"))?;
+
+    let mut count = 0;
+    for index in 0..existing.len() {
+        let entry = existing
+            .by_index_raw(index)
+            .with_context(|| anyhow::anyhow!("Cannot read entry {index} of {path:?}"))?;
+        let name = entry.name().to_string();
+        // Archives created on macOS usually contain metadata of the compressed files
+        if !allow_macos_metadata && is_macos_metadata(Path::new(&name)) {
+            log::debug!("Skipping macOS metadata entry `{name}` of {path:?}");
+            continue;
+        }
+        if name.split('/').any(is_invalid_file_name) {
+            log::warn!(
+                "Skipping entry `{name}` of {path:?}, its name cannot be stored in the archive"
+            );
+            continue;
+        }
+        if let Some(source) = archive_paths.get(Path::new(name.trim_end_matches('/'))) {
+            log::debug!("Entry `{name}` of {path:?} is replaced by {source:?}");
+            continue;
+        }
+        let is_file = entry.is_file();
+        zip.raw_copy_file(entry)
+            .with_context(|| anyhow::anyhow!("Cannot copy entry `{name}` of {path:?}"))?;
+        if is_file {
+            count += 1;
+        }
+    }
+    Ok(count)
+}
+
+/// Reads a file of the workspace and applies the transformations requested by the options.
+/// The file on disk is never modified.
+fn process_workspace_file(
+    relative_path: &Path,
+    fs_path: &Path,
+    mut bytes: Vec<u8>,
+    options: &ArchiveOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let is_manifest = relative_path
+        .file_name()
+        .is_some_and(|name| name == "Cargo.toml");
+    let is_rust_file = relative_path.extension().is_some_and(|ext| ext == "rs");
+
+    // The ranges reported by rustc refer to the original content of the file
+    if let (Some(unused_imports), true) = (&options.unused_imports, is_rust_file) {
+        if let Ok(content) = std::str::from_utf8(&bytes) {
+            let (content, changed_lines) = unused_imports.remove(fs_path, content);
+            if !changed_lines.is_empty() {
+                for line in &changed_lines {
+                    log::info!("Removing unused imports from `{line}` in {relative_path:?}");
+                }
+                bytes = content.into_bytes();
+            }
+        }
+    }
+    if let Some(author) = &options.redacted_author {
+        if is_rust_file {
+            if let Ok(content) = std::str::from_utf8(&bytes) {
+                let (content, count) =
+                    comments::replace_word_in_comments_and_strings(content, author, "<REDACTED>");
+                if count > 0 {
+                    log::warn!(
+                        "Replaced {count} occurrence{} of `{author}` with `<REDACTED>` in comments and strings of {relative_path:?}",
+                        if count == 1 { "" } else { "s" }
+                    );
+                    bytes = content.into_bytes();
+                }
+            }
+        } else if is_manifest {
+            match remove_authors(&String::from_utf8_lossy(&bytes)) {
+                Ok(Some(content)) => {
+                    log::warn!("Removed `authors` from {relative_path:?}");
+                    bytes = content.into_bytes();
+                }
+                Ok(None) => {}
+                Err(error) => log::warn!("Cannot remove authors from {relative_path:?}: {error:?}"),
+            }
+        }
+    }
+    if let (Some(root), true) = (&options.anonymized_root, is_rust_file) {
+        if let Ok(content) = std::str::from_utf8(&bytes) {
+            let mut content = content.to_string();
+            let mut count = 0;
+            // Paths in string literals on Windows contain escaped backslashes
+            let escaped_root = root.replace('\\', "\\\\");
+            for pattern in [escaped_root.as_str(), root.as_str()] {
+                count += content.matches(pattern).count();
+                content = content.replace(pattern, "<workspace>");
+            }
+            if count > 0 {
+                log::debug!(
+                    "Replaced {count} occurrence{} of the workspace path in {relative_path:?}",
+                    if count == 1 { "" } else { "s" }
+                );
+                bytes = content.into_bytes();
+            }
+        }
+    }
+    if options.strip_comments && is_rust_file {
+        if let Ok(content) = std::str::from_utf8(&bytes) {
+            bytes = comments::strip_comments(content).into_bytes();
+        }
+    }
+    if !options.required_signatures.is_empty() && relative_path == Path::new("src/lib.rs") {
+        let content = String::from_utf8_lossy(&bytes);
+        bytes = solution_template::inject_signatures(&content, &options.required_signatures)
+            .into_bytes();
+    }
+    if let (Some(edition), true) = (&options.required_edition, is_manifest) {
+        match set_edition(&String::from_utf8_lossy(&bytes), edition) {
+            Ok(Some((content, previous))) => {
+                log::warn!(
+                    "Grader requires edition {edition} but your {} specifies {previous}. Archive will use {edition}.",
+                    relative_path.display()
+                );
+                bytes = content.into_bytes();
+            }
+            Ok(None) => {}
+            Err(error) => log::warn!("Cannot set edition in {relative_path:?}: {error:?}"),
+        }
+    }
+    if options.format_manifests && is_manifest {
+        match format_manifest(&String::from_utf8_lossy(&bytes)) {
+            Ok(formatted) => bytes = formatted.into_bytes(),
+            Err(error) => log::warn!("Cannot format {relative_path:?}: {error:?}"),
+        }
+    }
+    if options.format_toml && relative_path.extension().is_some_and(|ext| ext == "toml") {
+        match format_toml(&String::from_utf8_lossy(&bytes)) {
+            Ok(formatted) => bytes = formatted.into_bytes(),
+            Err(error) => log::warn!("Cannot format {relative_path:?}: {error:?}"),
+        }
+    }
+    Ok(bytes)
+}
+
+/// Removes the `authors` field of the package (and of the workspace package template).
+/// Returns `None` if the manifest does not contain it.
+fn remove_authors(content: &str) -> anyhow::Result<Option<String>> {
+    let mut document: toml_edit::DocumentMut = content.parse().context("invalid TOML")?;
+    let mut removed = false;
+    if let Some(package) = document
+        .get_mut("package")
+        .and_then(|item| item.as_table_like_mut())
+    {
+        removed |= package.remove("authors").is_some();
+    }
+    if let Some(package) = document
+        .get_mut("workspace")
+        .and_then(|item| item.get_mut("package"))
+        .and_then(|item| item.as_table_like_mut())
+    {
+        removed |= package.remove("authors").is_some();
+    }
+    Ok(removed.then(|| document.to_string()))
+}
+
+/// Sets the edition of the package (and of the workspace package template) to `edition`.
+/// Returns `None` if the manifest already uses it, otherwise the new manifest and
+/// the previous edition.
+fn set_edition(content: &str, edition: &str) -> anyhow::Result<Option<(String, String)>> {
+    let mut document: toml_edit::DocumentMut = content.parse().context("invalid TOML")?;
+    let mut previous = None;
+    if let Some(package) = document
+        .get_mut("package")
+        .and_then(|item| item.as_table_like_mut())
+    {
+        match package.get("edition") {
+            // `edition.workspace = true` is resolved below, in the workspace manifest
+            Some(item) if item.is_table_like() => {}
+            Some(item) if item.as_str() == Some(edition) => {}
+            Some(item) => {
+                previous = Some(item.as_str().unwrap_or_default().to_string());
+                package.insert("edition", toml_edit::value(edition));
+            }
+            // Packages without an edition use the 2015 edition
+            None => {
+                previous = Some("2015".to_string());
+                package.insert("edition", toml_edit::value(edition));
+            }
+        }
+    }
+    if let Some(package) = document
+        .get_mut("workspace")
+        .and_then(|item| item.get_mut("package"))
+        .and_then(|item| item.as_table_like_mut())
+    {
+        if let Some(current) = package.get("edition").and_then(|item| item.as_str()) {
+            if current != edition {
+                previous = Some(current.to_string());
+                package.insert("edition", toml_edit::value(edition));
+            }
+        }
+    }
+    Ok(previous.map(|previous| (document.to_string(), previous)))
+}
+
+/// Normalizes spacing in a Cargo manifest and sorts the keys of its tables.
+fn format_manifest(content: &str) -> anyhow::Result<String> {
+    let mut document: toml_edit::DocumentMut = content.parse().context("invalid TOML")?;
+    format_table(document.as_table_mut());
+    Ok(document.to_string())
+}
+
+/// Formats a TOML file with the default options of `taplo`.
+fn format_toml(content: &str) -> anyhow::Result<String> {
+    let parsed = taplo::parser::parse(content);
+    if let Some(error) = parsed.errors.first() {
+        anyhow::bail!("invalid TOML: {error}");
+    }
+    Ok(taplo::formatter::format_syntax(
+        parsed.into_syntax(),
+        taplo::formatter::Options::default(),
+    ))
+}
+
+fn format_table(table: &mut toml_edit::Table) {
+    table.fmt();
+    table.sort_values();
+    for (_, item) in table.iter_mut() {
+        match item {
+            toml_edit::Item::Table(table) => format_table(table),
+            toml_edit::Item::ArrayOfTables(tables) => tables.iter_mut().for_each(format_table),
+            toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+                table.fmt();
+                table.sort_values();
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Options of files stored in the archive, encrypted if a password is given.
+fn zip_file_options(password: Option<&str>) -> FileOptions<'_, ()> {
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    match password {
+        Some(password) => options.with_aes_encryption(AesMode::Aes256, password),
+        None => options,
+    }
+}
+
+/// ID of the extra field that the `zip` crate uses to align the data of entries.
+const ALIGNMENT_EXTRA_FIELD: u16 = 0xa11e;
+
+/// Logs how many bytes of padding were added before the data of each entry of the archive.
+fn log_alignment_padding(data: &[u8]) -> anyhow::Result<()> {
+    let mut archive =
+        zip::ZipArchive::new(std::io::Cursor::new(data)).context("cannot read ZIP archive")?;
+    for index in 0..archive.len() {
+        let file = archive
+            .by_index_raw(index)
+            .context("cannot read ZIP archive entry")?;
+        let mut extra = file.extra_data().unwrap_or_default();
+        let mut padding = 0;
+        while let [id_low, id_high, len_low, len_high, rest @ ..] = extra {
+            let len = usize::from(u16::from_le_bytes([*len_low, *len_high])).min(rest.len());
+            if u16::from_le_bytes([*id_low, *id_high]) == ALIGNMENT_EXTRA_FIELD {
+                padding += 4 + len;
+            }
+            extra = &rest[len..];
+        }
+        log::trace!(
+            "Added {padding}B of padding before the data of {} (at offset {})",
+            file.name(),
+            file.data_start()
+        );
+    }
+    Ok(())
+}
+
+/// Reads `(file path, archive path)` pairs with at most `concurrency` threads, which is
+/// faster on network filesystems. The contents are returned with the archive paths,
+/// in the order of `paths`.
+fn read_files_parallel(
+    paths: &[(PathBuf, PathBuf)],
+    concurrency: usize,
+) -> Vec<(PathBuf, anyhow::Result<Vec<u8>>)> {
+    let read = |path: &Path| {
+        std::fs::read(path).with_context(|| anyhow::anyhow!("Cannot read file at {path:?}"))
+    };
+    let concurrency = concurrency.clamp(1, paths.len().max(1));
+    let mut contents: Vec<Option<anyhow::Result<Vec<u8>>>> = if concurrency == 1 {
+        paths.iter().map(|(path, _)| Some(read(path))).collect()
+    } else {
+        let next = AtomicUsize::new(0);
+        let contents = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+        std::thread::scope(|scope| {
+            for _ in 0..concurrency {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((path, _)) = paths.get(index) else {
+                        break;
+                    };
+                    let content = read(path);
+                    contents.lock().expect("reader thread panicked")[index] = Some(content);
+                });
+            }
+        });
+        contents.into_inner().expect("reader thread panicked")
+    };
+    paths
+        .iter()
+        .zip(contents.iter_mut())
+        .map(|((_, archive_path), content)| {
+            let content = content.take().expect("file was not read");
+            (archive_path.clone(), content)
+        })
+        .collect()
+}
+
+/// Checks that Rust and TOML files are valid UTF-8, and converts them to UTF-8 from
+/// the encoding given by `--fix-utf8`.
+fn ensure_utf8(
+    relative_path: &Path,
+    bytes: Vec<u8>,
+    options: &ArchiveOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let is_source = relative_path
+        .extension()
+        .is_some_and(|ext| ext == "rs" || ext == "toml");
+    if !is_source || std::str::from_utf8(&bytes).is_ok() {
+        return Ok(bytes);
+    }
+    if let Some(encoding) = options.fix_utf8 {
+        let (content, _, had_errors) = encoding.decode(&bytes);
+        if had_errors {
+            log::warn!(
+                "File {relative_path:?} is not valid {}, invalid characters were replaced",
+                encoding.name()
+            );
+        }
+        log::warn!(
+            "Converted {relative_path:?} from {} to UTF-8",
+            encoding.name()
+        );
+        return Ok(content.into_owned().into_bytes());
+    }
+    if options.abort_on_non_utf8 {
+        anyhow::bail!("File {relative_path:?} is not valid UTF-8");
+    }
+    if options.verify_utf8 {
+        log::warn!("File {relative_path:?} is not valid UTF-8");
+    }
+    Ok(bytes)
+}
+
+/// Reads a file that is stored in the archive in addition to the workspace files.
+/// Executables are checked for debug symbols, which are removed if requested.
+fn read_extra_file(fs_path: &Path, options: &ArchiveOptions) -> anyhow::Result<Vec<u8>> {
+    let bytes = std::fs::read(fs_path)
+        .with_context(|| anyhow::anyhow!("Cannot read file at {fs_path:?}"))?;
+    if !binary::is_executable(&bytes) || !binary::has_debug_symbols(&bytes) {
+        return Ok(bytes);
+    }
+    if !options.strip_debug_symbols {
+        log::warn!(
+            "Executable {fs_path:?} contains debug symbols, which make the archive larger. Run `strip` on it or use `--strip-debug-symbols`."
+        );
+        return Ok(bytes);
+    }
+    match binary::strip_debug_symbols(&bytes) {
+        Ok(stripped) => {
+            log::info!(
+                "Stripped debug symbols from {fs_path:?} ({}B -> {}B)",
+                bytes.len(),
+                stripped.len()
+            );
+            Ok(stripped)
+        }
+        Err(error) => {
+            log::warn!("Cannot strip debug symbols from {fs_path:?}: {error:?}");
+            Ok(bytes)
+        }
+    }
+}
+
+/// Writes a file into the archive, with the given compression level (`0` stores the file
+/// without compression).
+fn write_bytes_to_zip<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    options: FileOptions<()>,
+    relative_path: &Path,
+    bytes: &[u8],
+    compression_level: Option<i64>,
+) -> anyhow::Result<()> {
+    let options = match compression_level {
+        Some(0) => options
+            .compression_method(CompressionMethod::Stored)
+            .compression_level(None),
+        Some(level) => options.compression_level(Some(level)),
+        None => options,
+    };
+    zip.start_file_from_path(relative_path, options)
+        .with_context(|| anyhow::anyhow!("Cannot store {relative_path:?} into ZIP archive"))?;
+    zip.write_all(bytes)
+        .context("cannot write bytes into ZIP archive")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clip_long_path() {
+        let path = Path::new("src/very_long_module_name/another_long_name.rs");
+        let clipped = clip_path(path, 30).unwrap();
+        let clipped = clipped.to_string_lossy();
+        assert_eq!(clipped.len(), 30);
+        assert!(clipped.starts_with("src/very_long_"));
+        assert!(clipped.ends_with(".rs"));
+        assert_ne!(
+            clip_path(Path::new("src/very_long_module_name/other.rs"), 30).unwrap(),
+            clip_path(path, 30).unwrap()
+        );
+        assert!(clip_path(path, 10).is_err());
+    }
+
+    #[test]
+    fn read_files_in_order() {
+        let dir = create_workspace("kelvin-read", &["a.rs", "b.rs", "c.rs", "d.rs"]);
+        let paths: Vec<(PathBuf, PathBuf)> = ["a.rs", "missing.rs", "b.rs", "c.rs", "d.rs"]
+            .iter()
+            .map(|name| (dir.path().join(name), PathBuf::from(name)))
+            .collect();
+        for concurrency in [1, 3, 16] {
+            let contents = read_files_parallel(&paths, concurrency);
+            let names: Vec<_> = contents.iter().map(|(path, _)| path.clone()).collect();
+            assert_eq!(
+                names,
+                paths
+                    .iter()
+                    .map(|(_, path)| path.clone())
+                    .collect::<Vec<_>>()
+            );
+            assert!(contents[1].1.is_err());
+            assert_eq!(contents[4].1.as_ref().unwrap(), b"d.rs");
+        }
+    }
+
+    fn create_workspace(prefix: &str, files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::Builder::new().prefix(prefix).tempdir().unwrap();
+        for file in ["Cargo.toml"].iter().chain(files) {
+            let path = dir.path().join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, file.as_bytes()).unwrap();
+        }
+        dir
+    }
+
+    fn archive_names(archive: &[u8]) -> Vec<String> {
+        let zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        let mut names: Vec<String> = zip.file_names().map(|name| name.to_string()).collect();
+        names.sort();
+        names
+    }
+
+    fn extra_file(src: &str, dest: Option<&str>, strip_workspace_prefix: bool) -> ExtraFile {
+        ExtraFile {
+            src: src.to_string(),
+            dest: dest.map(PathBuf::from),
+            strip_workspace_prefix,
+        }
+    }
+
+    fn compress_with_extra_files(dir: &Path, extra_files: Vec<ExtraFile>) -> Vec<String> {
+        let options = ArchiveOptions {
+            extra_files,
+            ..Default::default()
+        };
+        archive_names(&compress_workspace(dir.join("Cargo.toml"), &options).unwrap())
+    }
+
+    /// Archive with the layout created by the macOS Archive Utility.
+    const MACOS_ARCHIVE: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/macos-archive.zip"
+    );
+
+    #[test]
+    fn macos_metadata_paths() {
+        for path in [
+            "__MACOSX/",
+            "__MACOSX/report/._notes.txt",
+            "report/._main.rs",
+            "report/.DS_Store",
+        ] {
+            assert!(is_macos_metadata(Path::new(path)), "{path}");
+        }
+        for path in ["report/notes.txt", "src/_main.rs", "MACOSX/main.rs"] {
+            assert!(!is_macos_metadata(Path::new(path)), "{path}");
+        }
+    }
+
+    #[test]
+    fn invalid_file_names() {
+        assert!(is_invalid_file_name("main\0.rs"));
+        assert!(!is_invalid_file_name("main.rs"));
+        assert_eq!(
+            is_invalid_file_name("main.rs:Zone.Identifier"),
+            cfg!(windows)
+        );
+    }
+
+    #[test]
+    fn appended_archive_skips_invalid_names() {
+        let appended = tempfile::NamedTempFile::new().unwrap();
+        let mut zip = ZipWriter::new(appended.reopen().unwrap());
+        for name in ["data/input.txt", "data/input\0.txt", "data\0/output.txt"] {
+            zip.start_file(name, SimpleFileOptions::default()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        let dir = create_workspace("kelvin-test", &["src/main.rs"]);
+        let options = ArchiveOptions {
+            append_to_archive: Some(appended.path().to_path_buf()),
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.toml", "data/input.txt", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn appended_macos_archive_skips_metadata() {
+        let dir = create_workspace("kelvin-test", &["src/main.rs"]);
+        let options = ArchiveOptions {
+            append_to_archive: Some(PathBuf::from(MACOS_ARCHIVE)),
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.toml", "report/", "report/notes.txt", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn appended_macos_archive_keeps_metadata_if_allowed() {
+        let dir = create_workspace("kelvin-test", &["src/main.rs"]);
+        let options = ArchiveOptions {
+            append_to_archive: Some(PathBuf::from(MACOS_ARCHIVE)),
+            macos_metadata: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            [
+                "Cargo.toml",
+                "__MACOSX/",
+                "__MACOSX/report/",
+                "__MACOSX/report/._notes.txt",
+                "report/",
+                "report/.DS_Store",
+                "report/notes.txt",
+                "src/main.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn include_or_exclude_examples() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &["src/main.rs", "examples/demo.rs", "examples/data/input.csv"],
+        );
+        let manifest = dir.path().join("Cargo.toml");
+        let archive = compress_workspace(manifest.clone(), &ArchiveOptions::default()).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.toml", "examples/demo.rs", "src/main.rs"]
+        );
+        let options = ArchiveOptions {
+            include_examples: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(manifest.clone(), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            [
+                "Cargo.toml",
+                "examples/data/input.csv",
+                "examples/demo.rs",
+                "src/main.rs"
+            ]
+        );
+        let options = ArchiveOptions {
+            exclude_examples: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(manifest, &options).unwrap();
+        assert_eq!(archive_names(&archive), ["Cargo.toml", "src/main.rs"]);
+    }
+
+    #[test]
+    fn include_ignored_files() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &[
+                "src/main.rs",
+                "src/parser.rs",
+                "src/notes.txt",
+                "src/local.rs",
+            ],
+        );
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join(".gitignore"), "parser.rs\nlocal.rs\n").unwrap();
+        std::fs::write(dir.path().join(".kelvinignore"), "notes.txt\nlocal.rs\n").unwrap();
+        let manifest = dir.path().join("Cargo.toml");
+        let archive = compress_workspace(manifest.clone(), &ArchiveOptions::default()).unwrap();
+        assert_eq!(archive_names(&archive), ["Cargo.toml", "src/main.rs"]);
+        let options = ArchiveOptions {
+            include_ignored: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(manifest, &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.toml", "src/main.rs", "src/parser.rs"]
+        );
+    }
+
+    #[test]
+    fn align_entry_data() {
+        let dir = create_workspace("kelvin-test", &["src/main.rs", "src/lib.rs", "README.md"]);
+        let options = ArchiveOptions {
+            zip_alignment: Some(64),
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        for index in 0..zip.len() {
+            let file = zip.by_index(index).unwrap();
+            assert_eq!(file.data_start() % 64, 0, "{} is not aligned", file.name());
+        }
+    }
+
+    #[test]
+    fn include_dotenv_templates() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &["src/main.rs", ".env", ".env.example", ".hidden/lib.rs"],
+        );
+        let manifest = dir.path().join("Cargo.toml");
+        let archive = compress_workspace(manifest.clone(), &ArchiveOptions::default()).unwrap();
+        assert_eq!(archive_names(&archive), ["Cargo.toml", "src/main.rs"]);
+        let options = ArchiveOptions {
+            include_dotenv: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(manifest, &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            [".env.example", "Cargo.toml", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn compression_level_per_extension() {
+        let dir = create_workspace("kelvin-test", &["src/main.rs"]);
+        let options = ArchiveOptions {
+            extension_levels: HashMap::from([("rs".to_string(), 0)]),
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        let mut zip = zip::ZipArchive::new(std::io::Cursor::new(archive)).unwrap();
+        assert_eq!(
+            zip.by_name("src/main.rs").unwrap().compression(),
+            CompressionMethod::Stored
+        );
+        assert_eq!(
+            zip.by_name("Cargo.toml").unwrap().compression(),
+            CompressionMethod::Deflated
+        );
+    }
+
+    #[test]
+    fn workspace_skips_macos_metadata() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &["src/main.rs", "src/._main.rs", "__MACOSX/src/._lib.rs"],
+        );
+        let archive =
+            compress_workspace(dir.path().join("Cargo.toml"), &ArchiveOptions::default()).unwrap();
+        assert_eq!(archive_names(&archive), ["Cargo.toml", "src/main.rs"]);
+    }
+
+    #[test]
+    fn extra_file_keeps_relative_path() {
+        let dir = create_workspace("kelvin-test", &["docs/report.pdf"]);
+        let names =
+            compress_with_extra_files(dir.path(), vec![extra_file("docs/report.pdf", None, false)]);
+        assert_eq!(names, ["Cargo.toml", "docs/report.pdf"]);
+    }
+
+    #[test]
+    fn extra_file_with_dest() {
+        let dir = create_workspace("kelvin-test", &["docs/report.pdf"]);
+        let names = compress_with_extra_files(
+            dir.path(),
+            vec![extra_file(
+                "docs/report.pdf",
+                Some("report-final.pdf"),
+                false,
+            )],
+        );
+        assert_eq!(names, ["Cargo.toml", "report-final.pdf"]);
+    }
+
+    #[test]
+    fn extra_file_glob_with_dest() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &["reports/a.pdf", "reports/b.pdf", "reports/c.png"],
+        );
+        let names = compress_with_extra_files(
+            dir.path(),
+            vec![extra_file("reports/*.pdf", Some("out"), false)],
+        );
+        assert_eq!(
+            names,
+            ["Cargo.toml", "out/reports/a.pdf", "out/reports/b.pdf"]
+        );
+    }
+
+    #[test]
+    fn extra_file_strip_workspace_prefix() {
+        let dir = create_workspace("kelvin-test", &["reports/2024/a.pdf"]);
+        let names = compress_with_extra_files(
+            dir.path(),
+            vec![
+                extra_file("reports/*/a.pdf", Some("out"), true),
+                extra_file("reports/2024/a.pdf", None, true),
+            ],
+        );
+        // Both entries map to different paths, the second one only under its file name
+        assert_eq!(names, ["Cargo.toml", "a.pdf", "out/a.pdf"]);
+    }
+
+    #[test]
+    fn extra_file_in_workspace_with_pattern_characters() {
+        let dir = create_workspace("kelvin-test-[1]*?", &["docs/report.pdf", "reports/a.pdf"]);
+        let names = compress_with_extra_files(
+            dir.path(),
+            vec![
+                extra_file("docs/report.pdf", None, false),
+                extra_file("reports/*.pdf", None, false),
+            ],
+        );
+        assert_eq!(names, ["Cargo.toml", "docs/report.pdf", "reports/a.pdf"]);
+    }
+
+    #[test]
+    fn add_prefix_moves_all_files() {
+        let dir = create_workspace(
+            "kelvin-test",
+            &[
+                "src/main.rs",
+                "docs/report.pdf",
+                "vendored/rand/.cargo-checksum.json",
+            ],
+        );
+        let options = ArchiveOptions {
+            add_prefix: Some(PathBuf::from("project")),
+            extra_files: vec![extra_file("docs/report.pdf", None, false)],
+            vendored_files: Some(vec![(
+                dir.path().join("vendored/rand/.cargo-checksum.json"),
+                PathBuf::from("vendor/rand/.cargo-checksum.json"),
+            )]),
+            generated_files: vec![GeneratedFile {
+                path: PathBuf::from(".cargo/config.toml"),
+                content: b"[source]".to_vec(),
+            }],
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            [
+                "project/.cargo/config.toml",
+                "project/Cargo.toml",
+                "project/docs/report.pdf",
+                "project/src/main.rs",
+                "project/vendor/rand/.cargo-checksum.json"
+            ]
+        );
+    }
+
+    const NESTED_WORKSPACE: [&str; 6] = [
+        "Cargo.lock",
+        "README.md",
+        "src/main.rs",
+        "src/parser/mod.rs",
+        "src/parser/lexer/tokens.rs",
+        "crates/util/src/lib.rs",
+    ];
+
+    #[test]
+    fn root_only_excludes_nested_files() {
+        let dir = create_workspace("kelvin-test", &NESTED_WORKSPACE);
+        let options = ArchiveOptions {
+            root_only: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.lock", "Cargo.toml", "README.md", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn src_only_excludes_files_outside_src() {
+        let dir = create_workspace("kelvin-test", &NESTED_WORKSPACE);
+        let options = ArchiveOptions {
+            src_only: true,
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            [
+                "Cargo.lock",
+                "Cargo.toml",
+                "src/main.rs",
+                "src/parser/lexer/tokens.rs",
+                "src/parser/mod.rs"
+            ]
+        );
+    }
+
+    #[test]
+    fn root_only_stacks_with_excluded_paths() {
+        let dir = create_workspace("kelvin-test", &NESTED_WORKSPACE);
+        let options = ArchiveOptions {
+            root_only: true,
+            excluded_paths: vec![PathBuf::from("Cargo.lock")],
+            ..Default::default()
+        };
+        let archive = compress_workspace(dir.path().join("Cargo.toml"), &options).unwrap();
+        assert_eq!(
+            archive_names(&archive),
+            ["Cargo.toml", "README.md", "src/main.rs"]
+        );
+    }
+
+    #[test]
+    fn set_edition_of_package_and_workspace() {
+        let (content, previous) =
+            set_edition("[package]\nname = \"a\"\nedition = \"2018\"\n", "2021")
+                .unwrap()
+                .unwrap();
+        assert_eq!(content, "[package]\nname = \"a\"\nedition = \"2021\"\n");
+        assert_eq!(previous, "2018");
+
+        let (content, previous) = set_edition("[package]\nname = \"a\"\n", "2021")
+            .unwrap()
+            .unwrap();
+        assert!(content.contains("edition = \"2021\""));
+        assert_eq!(previous, "2015");
+
+        let inherited =
+            "[package]\nedition.workspace = true\n\n[workspace.package]\nedition = \"2021\"\n";
+        assert!(set_edition(inherited, "2021").unwrap().is_none());
+        let (content, _) = set_edition(inherited, "2024").unwrap().unwrap();
+        assert!(content.contains("edition.workspace = true"));
+        assert!(content.contains("[workspace.package]\nedition = \"2024\""));
+    }
+}
@@ -0,0 +1,116 @@
+use anyhow::Context;
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+use std::io::Read;
+
+/// Change of a single file between two archives.
+#[derive(Debug)]
+enum FileChange {
+    Added { lines: usize },
+    Removed { lines: usize },
+    Modified { added: usize, removed: usize },
+}
+
+/// Files that differ between two ZIP archives.
+#[derive(Debug)]
+pub struct ArchiveDiff {
+    changes: BTreeMap<String, FileChange>,
+}
+
+impl ArchiveDiff {
+    pub fn log(&self) {
+        if self.changes.is_empty() {
+            log::info!("No files were changed");
+            return;
+        }
+        for line in self.lines() {
+            log::info!("  {line}");
+        }
+    }
+
+    /// Returns the number of added, removed and modified files.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        self.changes.values().fold(
+            (0, 0, 0),
+            |(added, removed, modified), change| match change {
+                FileChange::Added { .. } => (added + 1, removed, modified),
+                FileChange::Removed { .. } => (added, removed + 1, modified),
+                FileChange::Modified { .. } => (added, removed, modified + 1),
+            },
+        )
+    }
+
+    /// Describes each changed file on a separate line.
+    pub fn lines(&self) -> impl Iterator<Item = String> + '_ {
+        self.changes.iter().map(|(path, change)| match change {
+            FileChange::Added { lines } => format!("added     {path} (+{lines})"),
+            FileChange::Removed { lines } => format!("removed   {path} (-{lines})"),
+            FileChange::Modified { added, removed } => {
+                format!("modified  {path} (+{added} -{removed})")
+            }
+        })
+    }
+}
+
+/// Compares the files stored in two ZIP archives.
+pub fn diff_archives(old: &[u8], new: &[u8]) -> anyhow::Result<ArchiveDiff> {
+    let old = read_files(old).context("cannot read the old archive")?;
+    let mut new = read_files(new).context("cannot read the new archive")?;
+
+    let mut changes = BTreeMap::new();
+    for (path, old_content) in old {
+        let change = match new.remove(&path) {
+            Some(new_content) if new_content == old_content => continue,
+            Some(new_content) => {
+                let old_text = String::from_utf8_lossy(&old_content);
+                let new_text = String::from_utf8_lossy(&new_content);
+                let diff = TextDiff::from_lines(old_text.as_ref(), new_text.as_ref());
+                let (mut added, mut removed) = (0, 0);
+                for change in diff.iter_all_changes() {
+                    match change.tag() {
+                        ChangeTag::Insert => added += 1,
+                        ChangeTag::Delete => removed += 1,
+                        ChangeTag::Equal => {}
+                    }
+                }
+                FileChange::Modified { added, removed }
+            }
+            None => FileChange::Removed {
+                lines: count_lines(&old_content),
+            },
+        };
+        changes.insert(path, change);
+    }
+    for (path, content) in new {
+        changes.insert(
+            path,
+            FileChange::Added {
+                lines: count_lines(&content),
+            },
+        );
+    }
+    Ok(ArchiveDiff { changes })
+}
+
+fn count_lines(content: &[u8]) -> usize {
+    String::from_utf8_lossy(content).lines().count()
+}
+
+fn read_files(archive: &[u8]) -> anyhow::Result<BTreeMap<String, Vec<u8>>> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut files = BTreeMap::new();
+    for index in 0..zip.len() {
+        let mut file = zip
+            .by_index(index)
+            .context("cannot read file from ZIP archive")?;
+        if !file.is_file() {
+            continue;
+        }
+        let mut content = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut content)
+            .with_context(|| anyhow::anyhow!("cannot read {} from ZIP archive", file.name()))?;
+        files.insert(file.name().to_string(), content);
+    }
+    Ok(files)
+}
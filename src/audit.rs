@@ -0,0 +1,207 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the file with the output of `cargo audit` stored in the archive.
+pub const SECURITY_AUDIT_FILE: &str = "security_audit.json";
+
+/// Vulnerabilities with at least this CVSS base score are considered critical.
+const CRITICAL_SCORE: f64 = 9.0;
+
+pub struct SecurityAudit {
+    /// JSON output of `cargo audit`.
+    pub content: String,
+    /// Vulnerabilities found in the dependencies.
+    pub vulnerabilities: Vec<Vulnerability>,
+}
+
+impl SecurityAudit {
+    pub fn critical(&self) -> impl Iterator<Item = &Vulnerability> {
+        self.vulnerabilities
+            .iter()
+            .filter(|vulnerability| vulnerability.score.is_some_and(|s| s >= CRITICAL_SCORE))
+    }
+}
+
+pub struct Vulnerability {
+    pub id: String,
+    pub package: String,
+    pub version: String,
+    pub title: String,
+    /// CVSS base score, if the advisory has a CVSS v3 vector.
+    pub score: Option<f64>,
+}
+
+#[derive(serde::Deserialize)]
+struct AuditReport {
+    vulnerabilities: AuditVulnerabilities,
+}
+
+#[derive(serde::Deserialize)]
+struct AuditVulnerabilities {
+    list: Vec<AuditVulnerability>,
+}
+
+#[derive(serde::Deserialize)]
+struct AuditVulnerability {
+    advisory: Advisory,
+    package: AuditPackage,
+}
+
+#[derive(serde::Deserialize)]
+struct Advisory {
+    id: String,
+    title: String,
+    cvss: Option<String>,
+}
+
+#[derive(serde::Deserialize)]
+struct AuditPackage {
+    name: String,
+    version: String,
+}
+
+/// Runs `cargo audit --json` in the given directory and parses the found vulnerabilities.
+pub fn run_audit(root: &Path) -> anyhow::Result<SecurityAudit> {
+    log::info!("Running `cargo audit`");
+    let output = Command::new("cargo")
+        .args(["audit", "--json"])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo audit`")?;
+
+    // `cargo audit` fails when it finds a vulnerability, so its output is checked instead
+    let content = String::from_utf8_lossy(&output.stdout).into_owned();
+    let report: AuditReport = match serde_json::from_str(&content) {
+        Ok(report) => report,
+        Err(error) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            anyhow::bail!(
+                "`cargo audit` has failed, you can install it with `cargo install cargo-audit`\n{}\n{error}",
+                stderr.trim()
+            );
+        }
+    };
+    let vulnerabilities = report
+        .vulnerabilities
+        .list
+        .into_iter()
+        .map(|vulnerability| Vulnerability {
+            score: vulnerability.advisory.cvss.as_deref().and_then(cvss_score),
+            id: vulnerability.advisory.id,
+            package: vulnerability.package.name,
+            version: vulnerability.package.version,
+            title: vulnerability.advisory.title,
+        })
+        .collect();
+    Ok(SecurityAudit {
+        content,
+        vulnerabilities,
+    })
+}
+
+/// Computes the base score of a CVSS v3 vector, e.g.
+/// `CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H`.
+fn cvss_score(vector: &str) -> Option<f64> {
+    let mut metrics = vector.split('/');
+    if !metrics.next()?.starts_with("CVSS:3") {
+        return None;
+    }
+    let metrics: Vec<(&str, &str)> = metrics
+        .filter_map(|metric| metric.split_once(':'))
+        .collect();
+    let metric = |name: &str| {
+        metrics
+            .iter()
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| *value)
+    };
+    let changed = match metric("S")? {
+        "U" => false,
+        "C" => true,
+        _ => return None,
+    };
+    let attack_vector = match metric("AV")? {
+        "N" => 0.85,
+        "A" => 0.62,
+        "L" => 0.55,
+        "P" => 0.2,
+        _ => return None,
+    };
+    let attack_complexity = match metric("AC")? {
+        "L" => 0.77,
+        "H" => 0.44,
+        _ => return None,
+    };
+    let privileges = match (metric("PR")?, changed) {
+        ("N", _) => 0.85,
+        ("L", false) => 0.62,
+        ("L", true) => 0.68,
+        ("H", false) => 0.27,
+        ("H", true) => 0.5,
+        _ => return None,
+    };
+    let user_interaction = match metric("UI")? {
+        "N" => 0.85,
+        "R" => 0.62,
+        _ => return None,
+    };
+    let impact = |name: &str| match metric(name)? {
+        "H" => Some(0.56),
+        "L" => Some(0.22),
+        "N" => Some(0.0),
+        _ => None,
+    };
+    let base = 1.0 - (1.0 - impact("C")?) * (1.0 - impact("I")?) * (1.0 - impact("A")?);
+    let impact = if changed {
+        7.52 * (base - 0.029) - 3.25 * f64::powi(base - 0.02, 15)
+    } else {
+        6.42 * base
+    };
+    if impact <= 0.0 {
+        return Some(0.0);
+    }
+    let exploitability = 8.22 * attack_vector * attack_complexity * privileges * user_interaction;
+    let score = if changed {
+        1.08 * (impact + exploitability)
+    } else {
+        impact + exploitability
+    };
+    Some(round_up(score.min(10.0)))
+}
+
+/// Rounds up to one decimal place, as defined by the CVSS v3.1 specification.
+fn round_up(value: f64) -> f64 {
+    let value = (value * 100_000.0).round() as u64;
+    if value.is_multiple_of(10_000) {
+        value as f64 / 100_000.0
+    } else {
+        (value / 10_000 + 1) as f64 / 10.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cvss_score;
+
+    #[test]
+    fn cvss_base_score() {
+        assert_eq!(
+            cvss_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:H/I:H/A:H"),
+            Some(9.8)
+        );
+        assert_eq!(
+            cvss_score("CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:C/C:H/I:H/A:H"),
+            Some(10.0)
+        );
+        assert_eq!(
+            cvss_score("CVSS:3.0/AV:N/AC:H/PR:N/UI:N/S:U/C:N/I:N/A:H"),
+            Some(5.9)
+        );
+        assert_eq!(
+            cvss_score("CVSS:3.1/AV:L/AC:L/PR:N/UI:R/S:U/C:N/I:N/A:N"),
+            Some(0.0)
+        );
+        assert_eq!(cvss_score("CVSS:2.0/AV:N"), None);
+    }
+}
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+/// How many times is the archive compressed when benchmarking.
+const COMPRESSION_RUNS: usize = 3;
+
+#[derive(serde::Serialize, Debug)]
+pub struct BenchmarkResult {
+    pub compression_min_ms: f64,
+    pub compression_mean_ms: f64,
+    pub compression_max_ms: f64,
+    /// Peak resident memory of the process, only available on Linux.
+    pub peak_memory_kib: Option<u64>,
+    pub archive_size: usize,
+    pub upload_ms: Option<f64>,
+    pub bandwidth_bytes_per_sec: Option<f64>,
+}
+
+impl BenchmarkResult {
+    pub fn record_upload(&mut self, duration: Duration) {
+        self.upload_ms = Some(duration.as_secs_f64() * 1000.0);
+        if !duration.is_zero() {
+            self.bandwidth_bytes_per_sec = Some(self.archive_size as f64 / duration.as_secs_f64());
+        }
+    }
+
+    pub fn log(&self) {
+        log::info!(
+            "Compression time (min/mean/max): {:.1}ms/{:.1}ms/{:.1}ms",
+            self.compression_min_ms,
+            self.compression_mean_ms,
+            self.compression_max_ms
+        );
+        if let Some(memory) = self.peak_memory_kib {
+            log::info!("Peak memory usage: {memory} KiB");
+        }
+        if let (Some(upload), Some(bandwidth)) = (self.upload_ms, self.bandwidth_bytes_per_sec) {
+            log::info!(
+                "Upload of {}B took {upload:.1}ms ({:.1} KiB/s)",
+                self.archive_size,
+                bandwidth / 1024.0
+            );
+        }
+    }
+}
+
+/// Runs the compression several times and measures how long it takes.
+/// Returns the archive created by the last run.
+pub fn benchmark_compression<F: FnMut() -> anyhow::Result<Vec<u8>>>(
+    mut compress: F,
+) -> anyhow::Result<(Vec<u8>, BenchmarkResult)> {
+    let mut durations = Vec::with_capacity(COMPRESSION_RUNS);
+    let mut archive = Vec::new();
+    for _ in 0..COMPRESSION_RUNS {
+        let start = Instant::now();
+        archive = compress()?;
+        durations.push(start.elapsed());
+    }
+
+    let to_ms = |duration: &Duration| duration.as_secs_f64() * 1000.0;
+    let min = durations.iter().min().map(to_ms).unwrap_or_default();
+    let max = durations.iter().max().map(to_ms).unwrap_or_default();
+    let mean = durations.iter().map(to_ms).sum::<f64>() / durations.len() as f64;
+    let result = BenchmarkResult {
+        compression_min_ms: min,
+        compression_mean_ms: mean,
+        compression_max_ms: max,
+        peak_memory_kib: peak_memory_kib(),
+        archive_size: archive.len(),
+        upload_ms: None,
+        bandwidth_bytes_per_sec: None,
+    };
+    Ok((archive, result))
+}
+
+#[cfg(target_os = "linux")]
+fn peak_memory_kib() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("VmHWM:"))
+        .and_then(|value| value.trim().trim_end_matches("kB").trim().parse().ok())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn peak_memory_kib() -> Option<u64> {
+    None
+}
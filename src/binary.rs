@@ -0,0 +1,102 @@
+use anyhow::Context;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Checks if the file starts with the magic bytes of an ELF, Mach-O or PE executable.
+pub fn is_executable(bytes: &[u8]) -> bool {
+    const MAGICS: [&[u8]; 7] = [
+        b"\x7fELF",
+        b"\xfe\xed\xfa\xce",
+        b"\xfe\xed\xfa\xcf",
+        b"\xce\xfa\xed\xfe",
+        b"\xcf\xfa\xed\xfe",
+        b"\xca\xfe\xba\xbe",
+        b"MZ",
+    ];
+    MAGICS.iter().any(|magic| bytes.starts_with(magic))
+}
+
+/// Heuristically checks if an executable contains debug information or a symbol table,
+/// by looking for the names of the sections that store them.
+pub fn has_debug_symbols(bytes: &[u8]) -> bool {
+    const SECTIONS: [&[u8]; 4] = [b".debug_info", b".symtab", b"__DWARF", b"__debug_info"];
+    SECTIONS.iter().any(|section| {
+        bytes
+            .windows(section.len())
+            .any(|window| window == *section)
+    })
+}
+
+/// Strips a copy of the executable with `strip` (or `llvm-strip`) and returns the stripped
+/// copy.
+pub fn strip_debug_symbols(bytes: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let mut file = tempfile::NamedTempFile::new().context("cannot create temporary file")?;
+    file.write_all(bytes)
+        .context("cannot write executable to temporary file")?;
+    let path = file.path();
+
+    for program in ["strip", "llvm-strip"] {
+        match Command::new(program).arg(path).output() {
+            Ok(output) if output.status.success() => {
+                return std::fs::read(path).context("cannot read stripped executable");
+            }
+            Ok(output) => log::debug!(
+                "`{program}` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+            Err(error) => log::debug!("Cannot run `{program}`: {error:?}"),
+        }
+    }
+    anyhow::bail!("neither `strip` nor `llvm-strip` could strip the executable")
+}
+
+/// Builds the binaries of the packages in `root` with the given Cargo profile. Returns
+/// the paths of the built executables, along with their paths in the archive
+/// (`bin/<name>`).
+pub fn build_binaries(root: &Path, profile: &str) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+    log::info!("Building binaries with profile `{profile}`");
+    let output = Command::new("cargo")
+        .args(["build", "--bins", "--profile", profile])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo build`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo build` failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(root)
+        .no_deps()
+        .exec()
+        .context("getting cargo metadata")?;
+    // The `dev` and `test` profiles store their output in `target/debug`
+    let profile_dir = match profile {
+        "dev" | "test" => "debug",
+        "bench" => "release",
+        profile => profile,
+    };
+    let dir = metadata.target_directory.as_std_path().join(profile_dir);
+    let mut binaries = Vec::new();
+    for package in metadata.workspace_packages() {
+        if !package.manifest_path.as_std_path().starts_with(root) {
+            continue;
+        }
+        for target in &package.targets {
+            if !target.is_bin() {
+                continue;
+            }
+            let name = format!("{}{}", target.name, std::env::consts::EXE_SUFFIX);
+            let path = dir.join(&name);
+            if path.is_file() {
+                binaries.push((path, Path::new("bin").join(name)));
+            } else {
+                log::warn!("Cannot find binary `{}` at {path:?}", target.name);
+            }
+        }
+    }
+    Ok(binaries)
+}
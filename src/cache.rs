@@ -0,0 +1,237 @@
+use crate::archive::DeltaManifest;
+use crate::cache_dir;
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long are cached course data considered to be fresh.
+const COURSE_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// How long are the hashes of submitted files used by delta submits, by default.
+pub const CONTENT_HASHES_MAX_AGE: &str = "30days";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct CacheEntry {
+    /// UNIX timestamp (in seconds) of the time when the value was fetched.
+    fetched_at: u64,
+    value: serde_json::Value,
+}
+
+fn course_cache_path() -> anyhow::Result<PathBuf> {
+    Ok(cache_dir()?.join("courses.json"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn load_entries() -> HashMap<String, CacheEntry> {
+    let Ok(path) = course_cache_path() else {
+        return HashMap::new();
+    };
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn store_entries(entries: &HashMap<String, CacheEntry>) -> anyhow::Result<()> {
+    let path = course_cache_path()?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| anyhow::anyhow!("Cannot create directory {parent:?}"))?;
+    }
+    let content = serde_json::to_string(entries).context("cannot serialize course cache")?;
+    write_atomically(&path, content.as_bytes())
+        .with_context(|| anyhow::anyhow!("Cannot write course cache to {path:?}"))
+}
+
+/// Writes the file through a temporary file in the same directory, so that a cache file
+/// is never left half-written, e.g. when two submits run at the same time.
+fn write_atomically(path: &Path, content: &[u8]) -> anyhow::Result<()> {
+    let dir = path
+        .parent()
+        .context("cache file has no parent directory")?;
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| anyhow::anyhow!("Cannot create temporary file in {dir:?}"))?;
+    file.write_all(content)
+        .context("cannot write to temporary file")?;
+    file.persist(path)
+        .with_context(|| anyhow::anyhow!("Cannot move temporary file to {path:?}"))?;
+    Ok(())
+}
+
+/// Returns course data stored under `key` in `~/.cache/kelvin/courses.json` if they were
+/// fetched recently, otherwise fetches them again and stores them in the cache.
+/// Problems with the cache are not fatal, the data are then simply fetched from Kelvin.
+pub fn cached_course_data<T: Serialize + DeserializeOwned>(
+    key: &str,
+    fetch: impl FnOnce() -> anyhow::Result<T>,
+) -> anyhow::Result<T> {
+    let mut entries = load_entries();
+    if let Some(entry) = entries.get(key) {
+        if now().saturating_sub(entry.fetched_at) < COURSE_CACHE_TTL.as_secs() {
+            if let Ok(value) = serde_json::from_value(entry.value.clone()) {
+                log::debug!("Using cached `{key}`");
+                return Ok(value);
+            }
+        }
+    }
+
+    let value = fetch()?;
+    let now = now();
+    entries.retain(|_, entry| now.saturating_sub(entry.fetched_at) < COURSE_CACHE_TTL.as_secs());
+    match serde_json::to_value(&value) {
+        Ok(json) => {
+            entries.insert(
+                key.to_string(),
+                CacheEntry {
+                    fetched_at: now,
+                    value: json,
+                },
+            );
+            if let Err(error) = store_entries(&entries) {
+                log::debug!("Cannot store course cache: {error:?}");
+            }
+        }
+        Err(error) => log::debug!("Cannot cache `{key}`: {error:?}"),
+    }
+    Ok(value)
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct ContentHashes {
+    /// UNIX timestamp (in seconds) of the time when the files were submitted.
+    stored_at: u64,
+    /// SHA-256 hashes of the submitted files, by their archive path.
+    hashes: HashMap<PathBuf, String>,
+}
+
+/// Returns the directory with the hashes of submitted files, which is
+/// `~/.cache/kelvin/content-hashes` unless `custom_dir` is given.
+pub fn content_hashes_dir(custom_dir: Option<&Path>) -> anyhow::Result<PathBuf> {
+    match custom_dir {
+        Some(dir) => Ok(dir.to_path_buf()),
+        None => Ok(cache_dir()?.join("content-hashes")),
+    }
+}
+
+fn content_hashes_path(dir: &Path, assignment_id: u64) -> PathBuf {
+    dir.join(format!("{assignment_id}.json"))
+}
+
+/// Loads the hashes of the files submitted into the assignment the last time, stored in
+/// `<dir>/<assignment_id>.json`. Returns an empty map if there are none or if they are
+/// older than `max_age`.
+pub fn load_content_hashes(
+    dir: &Path,
+    assignment_id: u64,
+    max_age: Duration,
+) -> HashMap<PathBuf, String> {
+    let path = content_hashes_path(dir, assignment_id);
+    let Some(cached) = std::fs::read_to_string(&path)
+        .ok()
+        .and_then(|content| serde_json::from_str::<ContentHashes>(&content).ok())
+    else {
+        log::debug!("No usable hashes of submitted files in {path:?}");
+        return HashMap::new();
+    };
+    if now().saturating_sub(cached.stored_at) > max_age.as_secs() {
+        log::info!(
+            "The hashes of the previously submitted files are older than {}, all files are submitted",
+            humantime::format_duration(max_age)
+        );
+        return HashMap::new();
+    }
+    cached.hashes
+}
+
+/// Stores the hashes of the files of a delta archive that was submitted into the assignment.
+pub fn store_content_hashes(
+    dir: &Path,
+    assignment_id: u64,
+    manifest: &DeltaManifest,
+) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir)
+        .with_context(|| anyhow::anyhow!("Cannot create directory {dir:?}"))?;
+    let cached = ContentHashes {
+        stored_at: now(),
+        hashes: manifest
+            .files
+            .iter()
+            .map(|file| (file.path.clone(), file.sha256.clone()))
+            .collect(),
+    };
+    let content = serde_json::to_string_pretty(&cached).context("cannot serialize hashes")?;
+    let path = content_hashes_path(dir, assignment_id);
+    write_atomically(&path, content.as_bytes())
+        .with_context(|| anyhow::anyhow!("Cannot write content hashes to {path:?}"))
+}
+
+/// Deletes the stored hashes of the files submitted into the given assignment, or into all
+/// assignments. Returns the number of deleted files.
+pub fn clear_content_hashes(dir: &Path, assignment_id: Option<u64>) -> anyhow::Result<usize> {
+    let paths = match assignment_id {
+        Some(assignment_id) => vec![content_hashes_path(dir, assignment_id)],
+        None => match std::fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+                .collect(),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => vec![],
+            Err(error) => {
+                return Err(error).with_context(|| anyhow::anyhow!("Cannot read {dir:?}"));
+            }
+        },
+    };
+    let mut deleted = 0;
+    for path in paths {
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error).with_context(|| anyhow::anyhow!("Cannot delete {path:?}"));
+            }
+        }
+    }
+    Ok(deleted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{clear_content_hashes, load_content_hashes, store_content_hashes};
+    use crate::archive::{DeltaFile, DeltaManifest};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    #[test]
+    fn store_and_clear_content_hashes() {
+        let dir = tempfile::tempdir().unwrap();
+        let manifest = DeltaManifest {
+            files: vec![DeltaFile {
+                path: PathBuf::from("src/main.rs"),
+                sha256: "abcd".to_string(),
+                unchanged: false,
+            }],
+        };
+        let max_age = Duration::from_secs(60);
+        store_content_hashes(dir.path(), 1, &manifest).unwrap();
+        store_content_hashes(dir.path(), 2, &manifest).unwrap();
+        let hashes = load_content_hashes(dir.path(), 1, max_age);
+        assert_eq!(hashes[&PathBuf::from("src/main.rs")], "abcd");
+
+        assert_eq!(clear_content_hashes(dir.path(), Some(1)).unwrap(), 1);
+        assert!(load_content_hashes(dir.path(), 1, max_age).is_empty());
+        assert_eq!(load_content_hashes(dir.path(), 2, max_age).len(), 1);
+        assert_eq!(clear_content_hashes(dir.path(), None).unwrap(), 1);
+        assert!(load_content_hashes(dir.path(), 2, max_age).is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the file with the output of `cargo tree` stored in the archive.
+pub const CARGO_TREE_FILE: &str = "cargo_tree.txt";
+
+/// Name of the file with the dependency tree in JSON stored in the archive.
+pub const CARGO_TREE_JSON_FILE: &str = "cargo_tree.json";
+
+/// Package in the dependency tree.
+#[derive(serde::Serialize, Debug, PartialEq)]
+struct TreeNode {
+    name: String,
+    version: String,
+    /// Additional information printed by `cargo tree`, e.g. the path of a local package.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    details: Option<String>,
+    /// The dependencies of the package were already listed elsewhere in the tree.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    deduplicated: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    dependencies: Vec<TreeNode>,
+}
+
+/// Returns the dependency tree of the workspace (`cargo tree --workspace`), or `None` if
+/// `cargo tree` fails. Dev-dependencies are included only if `dev` is set.
+pub fn cargo_tree(root: &Path, dev: bool) -> Option<String> {
+    run_cargo_tree(root, dev, &[])
+}
+
+/// Returns the dependency tree of the workspace as JSON, or `None` if `cargo tree` fails.
+/// `cargo tree` has no JSON output, so its output with depth prefixes is converted.
+pub fn cargo_tree_json(root: &Path, dev: bool) -> Option<String> {
+    let output = run_cargo_tree(root, dev, &["--prefix", "depth"])?;
+    let tree = parse_depth_tree(&output);
+    Some(serde_json::to_string_pretty(&tree).expect("cannot serialize dependency tree"))
+}
+
+fn run_cargo_tree(root: &Path, dev: bool, args: &[&str]) -> Option<String> {
+    let edges = if dev {
+        "normal,build,dev"
+    } else {
+        "normal,build"
+    };
+    let output = Command::new("cargo")
+        .args(["tree", "--workspace", "--edges", edges])
+        .args(args)
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo tree`")
+        .and_then(|output| {
+            if !output.status.success() {
+                anyhow::bail!(
+                    "`cargo tree` has failed:\n{}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                );
+            }
+            Ok(output)
+        });
+    match output {
+        Ok(output) => Some(String::from_utf8_lossy(&output.stdout).into_owned()),
+        Err(error) => {
+            log::warn!("Skipping the dependency tree: {error:?}");
+            None
+        }
+    }
+}
+
+/// Parses the output of `cargo tree --prefix depth`, where each line starts with the depth
+/// of the package in the tree.
+fn parse_depth_tree(output: &str) -> Vec<TreeNode> {
+    let mut roots = Vec::new();
+    // Ancestors of the current package
+    let mut stack: Vec<TreeNode> = Vec::new();
+    for line in output.lines() {
+        let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+        let Ok(depth) = line[..digits].parse::<usize>() else {
+            continue;
+        };
+        let Some(node) = parse_node(&line[digits..]) else {
+            continue;
+        };
+        while stack.len() > depth {
+            attach(&mut stack, &mut roots);
+        }
+        stack.push(node);
+    }
+    while !stack.is_empty() {
+        attach(&mut stack, &mut roots);
+    }
+    roots
+}
+
+fn attach(stack: &mut Vec<TreeNode>, roots: &mut Vec<TreeNode>) {
+    let node = stack.pop().expect("empty dependency stack");
+    match stack.last_mut() {
+        Some(parent) => parent.dependencies.push(node),
+        None => roots.push(node),
+    }
+}
+
+fn parse_node(package: &str) -> Option<TreeNode> {
+    let (package, deduplicated) = match package.strip_suffix(" (*)") {
+        Some(package) => (package, true),
+        None => (package, false),
+    };
+    let mut parts = package.splitn(3, ' ');
+    let name = parts.next()?.to_string();
+    let version = parts.next()?.trim_start_matches('v').to_string();
+    let details = parts.next().map(|details| {
+        details
+            .trim_start_matches('(')
+            .trim_end_matches(')')
+            .to_string()
+    });
+    Some(TreeNode {
+        name,
+        version,
+        details,
+        deduplicated,
+        dependencies: vec![],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_tree() {
+        let tree = parse_depth_tree(
+            "0app v0.1.0 (/tmp/app)\n1rand v0.8.5\n2libc v0.2.0\n1serde v1.0.0\n2libc v0.2.0 (*)\n",
+        );
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].name, "app");
+        assert_eq!(tree[0].details.as_deref(), Some("/tmp/app"));
+        let dependencies: Vec<_> = tree[0].dependencies.iter().map(|d| &d.name).collect();
+        assert_eq!(dependencies, ["rand", "serde"]);
+        let libc = &tree[0].dependencies[1].dependencies[0];
+        assert_eq!(libc.version, "0.2.0");
+        assert!(libc.deduplicated);
+    }
+}
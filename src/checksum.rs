@@ -0,0 +1,48 @@
+use sha2::Digest;
+use std::path::PathBuf;
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ChecksumAlgorithm {
+    Sha256,
+    Md5,
+    Sha1,
+}
+
+impl ChecksumAlgorithm {
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha1",
+        }
+    }
+}
+
+/// Computes the checksum of the data, formatted as a lowercase hex string.
+pub fn compute_checksum(algo: ChecksumAlgorithm, data: &[u8]) -> String {
+    match algo {
+        ChecksumAlgorithm::Sha256 => to_hex(&sha2::Sha256::digest(data)),
+        ChecksumAlgorithm::Md5 => to_hex(&md5::Md5::digest(data)),
+        ChecksumAlgorithm::Sha1 => to_hex(&sha1::Sha1::digest(data)),
+    }
+}
+
+/// Computes a SHA-256 hash of the given files that does not depend on their order.
+/// The files are sorted by path and each path and content is prefixed by its length.
+pub fn compute_workspace_hash(files: &[(PathBuf, Vec<u8>)]) -> [u8; 32] {
+    let mut files: Vec<_> = files.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut hasher = sha2::Sha256::new();
+    for (path, content) in files {
+        let path = path.to_string_lossy().replace('\\', "/");
+        hasher.update((path.len() as u64).to_le_bytes());
+        hasher.update(path.as_bytes());
+        hasher.update((content.len() as u64).to_le_bytes());
+        hasher.update(content);
+    }
+    hasher.finalize().into()
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
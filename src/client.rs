@@ -0,0 +1,1264 @@
+use crate::checksum::{compute_checksum, to_hex, ChecksumAlgorithm};
+use crate::rate_limit::parse_retry_after;
+use crate::request_log::{LoggedRequest, RequestLog};
+use crate::throttle::ThrottledReader;
+use crate::tls;
+use anyhow::Context;
+use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE, RETRY_AFTER};
+use reqwest::redirect::Policy;
+use reqwest::StatusCode;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use url::Url;
+use uuid::Uuid;
+
+#[derive(serde::Deserialize, Debug)]
+pub struct SubmitData {
+    pub id: u64,
+    pub url: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct TaskData {
+    /// Assignment ID of the task, which can be used with `submit`.
+    #[serde(default)]
+    pub id: Option<u64>,
+    pub name: String,
+    #[serde(default)]
+    pub deadline: Option<String>,
+}
+
+/// A course in which the user is enrolled.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct CourseData {
+    pub id: u64,
+    pub name: String,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub tasks: Vec<TaskData>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct Response {
+    pub submit: SubmitData,
+    pub task: TaskData,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct ScoreItem {
+    pub name: String,
+    pub points: f64,
+}
+
+/// Detailed information about an assignment.
+#[derive(serde::Deserialize, Debug)]
+pub struct TaskInfo {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub deadline: Option<String>,
+    #[serde(default)]
+    pub score_breakdown: Vec<ScoreItem>,
+    #[serde(default)]
+    pub allowed_crates: Option<Vec<String>>,
+    #[serde(default)]
+    pub file_restrictions: Vec<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Rust edition required by the grader.
+    #[serde(default)]
+    pub edition: Option<String>,
+    /// Rust toolchain (e.g. `1.80.0`) required by the grader.
+    #[serde(default)]
+    pub required_toolchain: Option<String>,
+    /// Whether the archive can be uploaded in multiple multipart parts.
+    #[serde(default)]
+    pub supports_chunked_upload: bool,
+    /// Whether Kelvin can reconstruct a submit from an archive with only the changed files
+    /// and the previous submit.
+    #[serde(default)]
+    pub supports_delta_submit: bool,
+}
+
+/// Short information about a single submit of an assignment.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct SubmitSummary {
+    pub id: u64,
+    /// Time when the submit was created, in RFC 3339 format.
+    pub created_at: String,
+    /// Evaluation state of the submit.
+    #[serde(default)]
+    pub status: Option<String>,
+    /// Percentage (0-100) of points awarded to the submit, once it has been graded.
+    #[serde(default)]
+    pub score: Option<f64>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct SubmitStatus {
+    /// Evaluation state of the submit, e.g. `queued`, `running` or `finished`.
+    pub status: String,
+    /// Percentage (0-100) of points awarded to the submit, once it has been graded.
+    pub score: Option<f64>,
+}
+
+impl SubmitStatus {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self.status.as_str(), "queued" | "pending" | "running")
+    }
+}
+
+/// Result of building a submit on the Kelvin server.
+#[derive(serde::Deserialize, Debug)]
+pub struct BuildCheck {
+    pub success: bool,
+    /// Output of the compiler.
+    #[serde(default)]
+    pub output: String,
+}
+
+/// Result of validating a submit on the server without storing it.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct DryRunResult {
+    pub compiled: bool,
+    /// Output of the compiler.
+    #[serde(default)]
+    pub output: String,
+    /// Preliminary results of the tests.
+    #[serde(default)]
+    pub tests: Vec<DryRunTest>,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct DryRunTest {
+    pub name: String,
+    pub passed: bool,
+}
+
+/// A newly generated API token.
+#[derive(serde::Deserialize, Debug)]
+pub struct TokenRotation {
+    pub token: String,
+    /// For how many seconds the previous token remains valid.
+    #[serde(default)]
+    pub grace_period: Option<u64>,
+}
+
+/// The user that owns the API token.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct UserInfo {
+    pub username: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub courses: Vec<String>,
+    /// Number of submits that can still be uploaded, per assignment ID.
+    #[serde(default)]
+    pub remaining_submits: HashMap<u64, u32>,
+}
+
+/// Settings of the HTTP request used to upload a submit.
+pub struct UploadOptions {
+    /// Additional headers sent with the request.
+    pub headers: HeaderMap,
+    /// Content type of the uploaded archive.
+    pub content_type: String,
+    /// Name of the multipart form field containing the archive.
+    pub field_name: String,
+    /// Log where the request and the response should be stored.
+    pub request_log: Option<RequestLog>,
+    /// Maximum upload speed, in bytes per second.
+    pub bandwidth_limit: Option<u64>,
+    /// Split the archive into multipart parts of this size (in bytes), named
+    /// `<field>_chunk_<index>`, together with a `<field>_total_chunks` field.
+    pub chunk_size: Option<usize>,
+    /// Maximum duration of the whole upload request, including the response.
+    pub timeout: Option<Duration>,
+    /// Maximum time to wait for the response after the archive was uploaded.
+    pub response_timeout: Option<Duration>,
+    /// Boundary of the multipart form. If set, the form is encoded manually instead of by
+    /// `reqwest`, which always generates a random boundary.
+    pub multipart_boundary: Option<String>,
+}
+
+/// Tracks when the whole archive was read by the HTTP client, for `--response-timeout`.
+struct UploadTracker {
+    remaining: AtomicU64,
+    finished_at: OnceLock<Instant>,
+}
+
+/// Reader of the uploaded archive that reports to an [`UploadTracker`].
+struct TrackedReader<R: Read> {
+    inner: R,
+    tracker: Arc<UploadTracker>,
+}
+
+impl<R: Read> Read for TrackedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        if read > 0
+            && self
+                .tracker
+                .remaining
+                .fetch_sub(read as u64, Ordering::Relaxed)
+                == read as u64
+        {
+            let _ = self.tracker.finished_at.set(Instant::now());
+        }
+        Ok(read)
+    }
+}
+
+/// Header with the version of `cargo kelvin`, sent with every request.
+pub const VERSION_HEADER: &str = "X-Cargo-Kelvin-Version";
+
+/// Header sent by Kelvin when this version of `cargo kelvin` is no longer supported.
+const UPGRADE_REQUIRED_HEADER: &str = "X-Upgrade-Required";
+
+/// Logs the negotiated HTTP version and warns (once) if Kelvin asks for a newer version of
+/// `cargo kelvin`.
+fn inspect_response(response: reqwest::blocking::Response) -> reqwest::blocking::Response {
+    log::debug!(
+        "Negotiated protocol {:?} with {}",
+        response.version(),
+        response.url()
+    );
+    static WARNED: AtomicBool = AtomicBool::new(false);
+    if let Some(value) = response.headers().get(UPGRADE_REQUIRED_HEADER) {
+        if !WARNED.swap(true, Ordering::Relaxed) {
+            let value = String::from_utf8_lossy(value.as_bytes());
+            log::warn!(
+                "Kelvin requires a newer version of cargo-kelvin ({value}), update it with `cargo install cargo-kelvin`"
+            );
+        }
+    }
+    response
+}
+
+/// How many redirects are followed with `--accept-response-redirects`.
+const MAX_REDIRECTS: usize = 5;
+
+/// Follows a limited number of redirects, but stops with an error when the server redirects
+/// to a login page, which is what SSO deployments do when the token is not accepted.
+fn sso_redirect_policy() -> Policy {
+    Policy::custom(|attempt| {
+        if is_login_page(attempt.url()) {
+            attempt.error("Server redirected to login page — your token may be expired.")
+        } else if attempt.previous().len() >= MAX_REDIRECTS {
+            attempt.error(format!("Server redirected more than {MAX_REDIRECTS} times"))
+        } else {
+            attempt.follow()
+        }
+    })
+}
+
+fn is_login_page(url: &Url) -> bool {
+    let path = url.path().to_lowercase();
+    path.contains("/login") || path.contains("/sso")
+}
+
+/// HTTP version used for the requests to Kelvin.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum HttpVersion {
+    #[default]
+    #[value(name = "1.1")]
+    Http1,
+    /// HTTP/2 without negotiation (prior knowledge).
+    #[value(name = "2")]
+    Http2,
+}
+
+/// Tool that uses `cargo kelvin` to create submits, if not set otherwise.
+pub const DEFAULT_AGENT: &str = "cli";
+
+/// Headers with the IDs of the trace and of the parent span in the B3 format of Zipkin.
+const B3_TRACE_ID_HEADER: &str = "X-B3-TraceId";
+const B3_SPAN_ID_HEADER: &str = "X-B3-SpanId";
+/// Header with the IDs of the trace and of the parent span in the W3C Trace Context format.
+const TRACEPARENT_HEADER: &str = "traceparent";
+
+/// Settings of the underlying HTTP client.
+#[derive(Clone, Default)]
+struct HttpSettings {
+    accept_redirects: bool,
+    /// `None` lets the client negotiate the version with the server.
+    version: Option<HttpVersion>,
+    /// Added to the `User-Agent` header, `None` uses [`DEFAULT_AGENT`].
+    agent: Option<String>,
+    /// SHA-256 fingerprint of the only accepted server certificate.
+    certificate_fingerprint: Option<[u8; 32]>,
+    /// ID of the distributed trace to which all requests belong.
+    trace_id: Option<Uuid>,
+}
+
+impl HttpSettings {
+    fn agent(&self) -> &str {
+        self.agent.as_deref().unwrap_or(DEFAULT_AGENT)
+    }
+}
+
+fn http_client(settings: &HttpSettings) -> reqwest::blocking::Client {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        VERSION_HEADER,
+        HeaderValue::from_static(env!("CARGO_PKG_VERSION")),
+    );
+    if let Some(trace_id) = settings.trace_id {
+        let trace_id = trace_id.simple().to_string();
+        // All requests are children of a single span of `cargo kelvin`
+        let span_id = to_hex(&Uuid::new_v4().as_bytes()[..8]);
+        for (name, value) in [
+            (B3_TRACE_ID_HEADER, trace_id.clone()),
+            (B3_SPAN_ID_HEADER, span_id.clone()),
+            (TRACEPARENT_HEADER, format!("00-{trace_id}-{span_id}-01")),
+        ] {
+            headers.insert(
+                name,
+                HeaderValue::from_str(&value).expect("Invalid trace header"),
+            );
+        }
+    }
+    let redirect = if settings.accept_redirects {
+        sso_redirect_policy()
+    } else {
+        Policy::default()
+    };
+    let builder = reqwest::blocking::Client::builder()
+        .user_agent(format!(
+            "cargo-kelvin/{} (+{})",
+            env!("CARGO_PKG_VERSION"),
+            settings.agent()
+        ))
+        .default_headers(headers)
+        .redirect(redirect);
+    let builder = match settings.version {
+        None => builder,
+        Some(HttpVersion::Http1) => builder.http1_only(),
+        Some(HttpVersion::Http2) => builder.http2_prior_knowledge(),
+    };
+    let builder = match settings.certificate_fingerprint {
+        Some(fingerprint) => builder.use_preconfigured_tls(tls::pinned_certificate_config(
+            fingerprint,
+            settings.version,
+        )),
+        None => builder,
+    };
+    builder.build().expect("Cannot create HTTP client")
+}
+
+/// How many times to try uploading a submit when the server is rate limiting us.
+const MAX_SUBMIT_ATTEMPTS: u32 = 3;
+
+/// Oldest version of the Kelvin API supported by this version of `cargo kelvin`.
+pub const MIN_API_VERSION: u32 = 1;
+/// Newest version of the Kelvin API supported by this version of `cargo kelvin`.
+pub const MAX_API_VERSION: u32 = 1;
+
+#[derive(serde::Deserialize, Debug)]
+struct HealthStatus {
+    api_version: Option<u32>,
+}
+
+/// Thin wrapper over the Kelvin REST API.
+pub struct KelvinClient {
+    client: reqwest::blocking::Client,
+    settings: HttpSettings,
+    kelvin_url: String,
+    token: String,
+    /// Version of the API used in the URLs of the endpoints, `None` uses the unversioned
+    /// endpoints.
+    api_version: Option<u32>,
+}
+
+impl KelvinClient {
+    pub fn new(kelvin_url: Url, token: String) -> Self {
+        Self {
+            client: http_client(&HttpSettings::default()),
+            settings: HttpSettings::default(),
+            kelvin_url: kelvin_url.as_str().trim_end_matches('/').to_string(),
+            token,
+            api_version: None,
+        }
+    }
+
+    /// Uses the endpoints of the given API version (`/api/v<N>/`) instead of the unversioned
+    /// ones (`/api/`), which serve the latest API version of the server.
+    pub fn api_version(mut self, version: u32) -> Self {
+        self.api_version = Some(version);
+        self
+    }
+
+    fn api_url(&self, path: impl std::fmt::Display) -> String {
+        match self.api_version {
+            Some(version) => format!("{}/api/v{version}/{path}", self.kelvin_url),
+            None => format!("{}/api/{path}", self.kelvin_url),
+        }
+    }
+
+    /// Warns if the API version advertised by the health endpoint of the server is not
+    /// supported by this version of `cargo kelvin`. Servers without the endpoint are
+    /// not checked.
+    pub fn check_api_version(&self) {
+        let version = match self.get_server_api_version() {
+            Ok(Some(version)) => version,
+            Ok(None) => return,
+            Err(error) => {
+                log::debug!("Cannot check the API version of Kelvin: {error:?}");
+                return;
+            }
+        };
+        if version < MIN_API_VERSION {
+            log::warn!(
+                "Kelvin uses API version {version}, but cargo-kelvin supports only versions {MIN_API_VERSION} to {MAX_API_VERSION}. Please report the mismatch to the administrators of Kelvin."
+            );
+        } else if version > MAX_API_VERSION {
+            log::warn!(
+                "Kelvin uses API version {version}, but cargo-kelvin supports only versions {MIN_API_VERSION} to {MAX_API_VERSION}. Update it with `cargo install cargo-kelvin`."
+            );
+        }
+    }
+
+    /// Fetches the API version advertised by the server, or `None` if the server does not
+    /// advertise it.
+    fn get_server_api_version(&self) -> anyhow::Result<Option<u32>> {
+        // The health endpoint is not versioned, so that it is available to all clients
+        let res = self
+            .client
+            .get(format!("{}/api/health", self.kelvin_url))
+            .send()
+            .map(inspect_response)
+            .context("fetching health status of Kelvin")?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if res.status() != StatusCode::OK {
+            anyhow::bail!("Cannot fetch health status. Status error: {}", res.status());
+        }
+        let health: HealthStatus = res.json().context("deserializing health status")?;
+        Ok(health.api_version)
+    }
+
+    /// Follows only a limited number of redirects and fails with a clear error when the
+    /// server redirects to a login page.
+    pub fn accept_response_redirects(mut self) -> Self {
+        self.settings.accept_redirects = true;
+        self.client = http_client(&self.settings);
+        self
+    }
+
+    /// Identifies the tool that creates the submits (e.g. an editor extension or a CI system)
+    /// in the `User-Agent` header.
+    pub fn agent(mut self, agent: String) -> Self {
+        self.settings.agent = Some(agent);
+        self.client = http_client(&self.settings);
+        self
+    }
+
+    /// Accepts only the server certificate with the given SHA-256 fingerprint, even if it is
+    /// not signed by a trusted authority (e.g. a self-signed certificate).
+    pub fn pin_certificate(mut self, fingerprint: [u8; 32]) -> Self {
+        self.settings.certificate_fingerprint = Some(fingerprint);
+        self.client = http_client(&self.settings);
+        self
+    }
+
+    /// Sends the ID of a distributed trace with all requests, so that they can be correlated
+    /// with the traces of the server.
+    pub fn trace_id(mut self, trace_id: Uuid) -> Self {
+        self.settings.trace_id = Some(trace_id);
+        self.client = http_client(&self.settings);
+        self
+    }
+
+    pub fn agent_name(&self) -> &str {
+        self.settings.agent()
+    }
+
+    /// Uses only the given HTTP version instead of negotiating it with the server.
+    pub fn http_version(mut self, version: HttpVersion) -> Self {
+        self.settings.version = Some(version);
+        self.client = http_client(&self.settings);
+        self
+    }
+
+    /// Uploads a ZIP archive as a new submit of the given assignment.
+    /// Returns `None` if the server did not accept the submit.
+    ///
+    /// If the server responds with 429 (Too Many Requests), the upload is retried after the
+    /// delay requested by the server.
+    pub fn submit(
+        &self,
+        assignment_id: u64,
+        archive: &[u8],
+        options: &UploadOptions,
+    ) -> anyhow::Result<Option<Response>> {
+        let (status, body) = self.upload(assignment_id, &[], archive, options)?;
+        parse_submit_response(status, &body)
+    }
+
+    /// Lets the server build and test the archive without storing the submit.
+    /// Returns `None` if the server does not support it.
+    pub fn dry_run_submit(
+        &self,
+        assignment_id: u64,
+        archive: &[u8],
+        options: &UploadOptions,
+    ) -> anyhow::Result<Option<DryRunResult>> {
+        let (status, body) = self.upload(assignment_id, &[("dry_run", "1")], archive, options)?;
+        if status == StatusCode::BAD_REQUEST {
+            return Ok(None);
+        }
+        if status != StatusCode::OK {
+            log::debug!("Response content: {}", String::from_utf8_lossy(&body));
+            anyhow::bail!("The server could not validate the submit. Status error: {status}");
+        }
+        serde_json::from_slice(&body)
+            .context("deserializing dry run response")
+            .map(Some)
+    }
+
+    /// Uploads the first phase of a two-phase submit, which contains only Cargo manifests
+    /// and lockfiles. Returns a token that has to be passed to [`KelvinClient::phase2_submit`],
+    /// or `None` if the server does not support two-phase submits.
+    pub fn phase1_submit(
+        &self,
+        assignment_id: u64,
+        manifest_archive: &[u8],
+        options: &UploadOptions,
+    ) -> anyhow::Result<Option<String>> {
+        #[derive(serde::Deserialize)]
+        struct PhaseResponse {
+            phase_token: String,
+        }
+
+        let (status, body) =
+            self.upload(assignment_id, &[("phase", "1")], manifest_archive, options)?;
+        if status == StatusCode::BAD_REQUEST {
+            return Ok(None);
+        }
+        if status != StatusCode::OK {
+            log::debug!("Response content: {}", String::from_utf8_lossy(&body));
+            anyhow::bail!(
+                "The dependencies of the submit were not approved. Status error: {status}"
+            );
+        }
+        let response: PhaseResponse =
+            serde_json::from_slice(&body).context("deserializing phase 1 response")?;
+        Ok(Some(response.phase_token))
+    }
+
+    /// Uploads the full archive as the second phase of a two-phase submit.
+    /// Returns `None` if the server did not accept the submit.
+    pub fn phase2_submit(
+        &self,
+        assignment_id: u64,
+        archive: &[u8],
+        phase_token: &str,
+        options: &UploadOptions,
+    ) -> anyhow::Result<Option<Response>> {
+        let (status, body) = self.upload(
+            assignment_id,
+            &[("phase", "2"), ("token", phase_token)],
+            archive,
+            options,
+        )?;
+        parse_submit_response(status, &body)
+    }
+
+    /// Sends a ZIP archive to the submit endpoint and returns the status and body of the
+    /// response. Requests that were rate limited are retried.
+    fn upload(
+        &self,
+        assignment_id: u64,
+        query: &[(&str, &str)],
+        archive: &[u8],
+        options: &UploadOptions,
+    ) -> anyhow::Result<(StatusCode, Vec<u8>)> {
+        let mut attempt = 1;
+        loop {
+            let tracker = options.response_timeout.map(|_| {
+                Arc::new(UploadTracker {
+                    remaining: AtomicU64::new(archive.len() as u64),
+                    finished_at: OnceLock::new(),
+                })
+            });
+            let fields = form_fields(archive, options);
+            let mut request = self
+                .client
+                .post(self.api_url(format_args!("submits/{assignment_id}")))
+                .query(query)
+                .header("Authorization", format!("Bearer {}", self.token))
+                .headers(options.headers.clone());
+            request = match &options.multipart_boundary {
+                Some(boundary) => {
+                    let body = encode_multipart(&fields, boundary, options)?;
+                    if let Some(tracker) = &tracker {
+                        // The multipart headers are uploaded together with the archive
+                        tracker
+                            .remaining
+                            .store(body.len() as u64, Ordering::Relaxed);
+                    }
+                    request
+                        .header(
+                            CONTENT_TYPE,
+                            format!("multipart/form-data; boundary={boundary}"),
+                        )
+                        .body(upload_body(body, options, tracker.as_ref()))
+                }
+                None => request.multipart(multipart_form(fields, options, tracker.as_ref())?),
+            };
+            if let Some(timeout) = options.timeout {
+                request = request.timeout(timeout);
+            }
+            let request = request.build().context("creating submit request")?;
+            let logged_request = options
+                .request_log
+                .as_ref()
+                .map(|_| LoggedRequest::new(&request));
+            let res = match (&tracker, options.response_timeout) {
+                (Some(tracker), Some(timeout)) => {
+                    self.execute_with_response_timeout(request, tracker, timeout)?
+                }
+                _ => self.client.execute(request),
+            }
+            .map(inspect_response)
+            .context("sending submit to Kelvin")?;
+            let version = res.version();
+            let status = res.status();
+            let headers = res.headers().clone();
+            let body = res.bytes().context("getting content of HTTP response")?;
+            if let (Some(log), Some(request)) = (&options.request_log, &logged_request) {
+                if let Err(error) = log.record(request, version, status, &headers, &body) {
+                    log::warn!("Cannot log HTTP request: {error:?}");
+                }
+            }
+
+            if status == StatusCode::TOO_MANY_REQUESTS && attempt < MAX_SUBMIT_ATTEMPTS {
+                let delay = parse_retry_after(headers.get(RETRY_AFTER));
+                log::warn!("Rate limited, waiting {}s before retry.", delay.as_secs());
+                std::thread::sleep(delay);
+                attempt += 1;
+                continue;
+            }
+            return Ok((status, body.to_vec()));
+        }
+    }
+
+    /// Sends the request on a separate thread and fails if the response does not arrive
+    /// within `timeout` after the whole archive was uploaded.
+    fn execute_with_response_timeout(
+        &self,
+        request: reqwest::blocking::Request,
+        tracker: &UploadTracker,
+        timeout: Duration,
+    ) -> anyhow::Result<reqwest::Result<reqwest::blocking::Response>> {
+        let (sender, receiver) = mpsc::channel();
+        let client = self.client.clone();
+        // The thread is left behind if the response does not arrive in time
+        std::thread::spawn(move || {
+            let _ = sender.send(client.execute(request));
+        });
+        loop {
+            match receiver.recv_timeout(Duration::from_millis(50)) {
+                Ok(result) => return Ok(result),
+                Err(RecvTimeoutError::Timeout) => {
+                    if tracker
+                        .finished_at
+                        .get()
+                        .is_some_and(|finished| finished.elapsed() > timeout)
+                    {
+                        anyhow::bail!(
+                            "Kelvin did not respond within {}ms after the submit was uploaded",
+                            timeout.as_millis()
+                        );
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    anyhow::bail!("The upload of the submit was interrupted")
+                }
+            }
+        }
+    }
+
+    pub fn kelvin_url(&self) -> &str {
+        &self.kelvin_url
+    }
+
+    /// Short hash of the API token, which identifies the user without revealing the token.
+    pub fn token_fingerprint(&self) -> String {
+        let digest = compute_checksum(ChecksumAlgorithm::Sha256, self.token.as_bytes());
+        digest[..16].to_string()
+    }
+
+    /// Fetches information about the user that owns the API token.
+    pub fn get_user_info(&self) -> anyhow::Result<UserInfo> {
+        let res = self
+            .client
+            .get(self.api_url("me"))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching user information from Kelvin")?;
+        if matches!(
+            res.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            anyhow::bail!(
+                "The API token is not valid. You can generate a new one at `{}/api_token`.",
+                self.kelvin_url
+            );
+        }
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch user information. Status error: {}",
+                res.status()
+            );
+        }
+        res.json().context("deserializing user information")
+    }
+
+    /// Asks Kelvin to generate a new API token. The current token remains valid for
+    /// the requested grace period, or for a period chosen by the server.
+    pub fn rotate_token(&self, grace_period: Option<Duration>) -> anyhow::Result<TokenRotation> {
+        let mut request = self
+            .client
+            .post(self.api_url("token/rotate"))
+            .header("Authorization", format!("Bearer {}", self.token));
+        if let Some(grace_period) = grace_period {
+            request = request.query(&[("grace_period", grace_period.as_secs())]);
+        }
+        let res = request
+            .send()
+            .map(inspect_response)
+            .context("rotating API token")?;
+        if matches!(
+            res.status(),
+            StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN
+        ) {
+            anyhow::bail!(
+                "The API token is not valid. You can generate a new one at `{}/api_token`.",
+                self.kelvin_url
+            );
+        }
+        if res.status() != StatusCode::OK {
+            anyhow::bail!("Cannot rotate API token. Status error: {}", res.status());
+        }
+        res.json().context("deserializing new API token")
+    }
+
+    /// Fetches all courses in which the user is enrolled.
+    pub fn get_courses(&self) -> anyhow::Result<Vec<CourseData>> {
+        let res = self
+            .client
+            .get(self.api_url("courses"))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching courses from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!("Cannot fetch courses. Status error: {}", res.status());
+        }
+        res.json().context("deserializing courses")
+    }
+
+    /// Fetches a course together with all its tasks.
+    pub fn get_course_tasks(&self, course_id: u64) -> anyhow::Result<CourseData> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("courses/{course_id}/tasks")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching course tasks from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch tasks of course {course_id}. Status error: {}",
+                res.status()
+            );
+        }
+        res.json().context("deserializing course tasks")
+    }
+
+    /// Fetches detailed information about an assignment.
+    pub fn get_task_info(&self, assignment_id: u64) -> anyhow::Result<TaskInfo> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("tasks/{assignment_id}")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching task information from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch information about task {assignment_id}. Status error: {}",
+                res.status()
+            );
+        }
+        res.json().context("deserializing task information")
+    }
+
+    /// Fetches the solution template of the assignment with the signatures that submits
+    /// must define. Returns `None` if the assignment has no template.
+    pub fn get_solution_template(&self, assignment_id: u64) -> anyhow::Result<Option<String>> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("assignments/{assignment_id}/template")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching solution template from Kelvin")?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch solution template of assignment {assignment_id}. Status error: {}",
+                res.status()
+            );
+        }
+        res.text().context("reading solution template").map(Some)
+    }
+
+    /// Fetches names of crates that can be used as dependencies in the given assignment.
+    pub fn get_allowed_crates(&self, assignment_id: u64) -> anyhow::Result<Vec<String>> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("assignments/{assignment_id}/allowed-crates")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching allowed crates from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch allowed crates of assignment {assignment_id}. Status error: {}",
+                res.status()
+            );
+        }
+        res.json().context("deserializing allowed crates")
+    }
+
+    /// Fetches all submits of the authenticated user for the given assignment,
+    /// ordered from the oldest one.
+    pub fn get_submits(&self, assignment_id: u64) -> anyhow::Result<Vec<SubmitSummary>> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("assignments/{assignment_id}/submits")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching submits from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot fetch submits of assignment {assignment_id}. Status error: {}",
+                res.status()
+            );
+        }
+        let mut submits: Vec<SubmitSummary> = res.json().context("deserializing submits")?;
+        submits.sort_by_key(|submit| submit.id);
+        Ok(submits)
+    }
+
+    /// Downloads the ZIP archive of an existing submit.
+    pub fn get_submit_archive(&self, submit_id: u64) -> anyhow::Result<Vec<u8>> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("submits/{submit_id}/archive")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("downloading submit archive from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot download archive of submit #{submit_id}. Status error: {}",
+                res.status()
+            );
+        }
+        Ok(res
+            .bytes()
+            .context("downloading submit archive from Kelvin")?
+            .to_vec())
+    }
+
+    /// Asks the server to build the submit with its toolchain and waits for the result.
+    /// Returns `None` if the server does not support build checks.
+    pub fn check_build(&self, submit_id: u64) -> anyhow::Result<Option<BuildCheck>> {
+        let res = self
+            .client
+            .post(self.api_url(format_args!("submits/{submit_id}/check-build")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("requesting build check from Kelvin")?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if res.status() != StatusCode::OK {
+            anyhow::bail!(
+                "Cannot check build of submit. Status error: {}",
+                res.status()
+            );
+        }
+        res.json().context("deserializing build check").map(Some)
+    }
+
+    /// Fetches the current evaluation state of a submit.
+    pub fn get_submit_status(&self, submit_id: u64) -> anyhow::Result<SubmitStatus> {
+        let res = self
+            .client
+            .get(self.api_url(format_args!("submits/{submit_id}/status")))
+            .header("Authorization", format!("Bearer {}", self.token))
+            .send()
+            .map(inspect_response)
+            .context("fetching submit status from Kelvin")?;
+        if res.status() != StatusCode::OK {
+            anyhow::bail!("Cannot fetch submit status. Status error: {}", res.status());
+        }
+        res.json().context("deserializing submit status")
+    }
+}
+
+/// Field of the multipart form of an upload.
+enum FormField<'a> {
+    Text {
+        name: String,
+        value: String,
+    },
+    /// (A chunk of) the archive.
+    Archive {
+        name: String,
+        data: &'a [u8],
+    },
+}
+
+/// Name of the uploaded archive in the multipart form.
+const ARCHIVE_FILE_NAME: &str = "submit.zip";
+
+fn form_fields<'a>(archive: &'a [u8], options: &UploadOptions) -> Vec<FormField<'a>> {
+    let field = &options.field_name;
+    match options.chunk_size {
+        Some(chunk_size) => {
+            let chunks = split_bytes(archive, chunk_size);
+            let mut fields = vec![FormField::Text {
+                name: format!("{field}_total_chunks"),
+                value: chunks.len().to_string(),
+            }];
+            fields.extend(
+                chunks
+                    .into_iter()
+                    .enumerate()
+                    .map(|(index, data)| FormField::Archive {
+                        name: format!("{field}_chunk_{index}"),
+                        data,
+                    }),
+            );
+            fields
+        }
+        None => vec![FormField::Archive {
+            name: field.clone(),
+            data: archive,
+        }],
+    }
+}
+
+fn multipart_form(
+    fields: Vec<FormField>,
+    options: &UploadOptions,
+    tracker: Option<&Arc<UploadTracker>>,
+) -> anyhow::Result<reqwest::blocking::multipart::Form> {
+    let mut form = reqwest::blocking::multipart::Form::new();
+    for field in fields {
+        form = match field {
+            FormField::Text { name, value } => form.text(name, value),
+            FormField::Archive { name, data } => {
+                form.part(name, archive_part(data, options, tracker)?)
+            }
+        };
+    }
+    Ok(form)
+}
+
+/// Creates a multipart part with (a chunk of) the archive.
+fn archive_part(
+    data: &[u8],
+    options: &UploadOptions,
+    tracker: Option<&Arc<UploadTracker>>,
+) -> anyhow::Result<reqwest::blocking::multipart::Part> {
+    let part = if options.bandwidth_limit.is_none() && tracker.is_none() {
+        reqwest::blocking::multipart::Part::bytes(data.to_vec())
+    } else {
+        reqwest::blocking::multipart::Part::reader_with_length(
+            upload_reader(data.to_vec(), options, tracker),
+            data.len() as u64,
+        )
+    };
+    part.file_name(ARCHIVE_FILE_NAME)
+        .mime_str(&options.content_type)
+        .with_context(|| anyhow::anyhow!("Invalid content type `{}`", options.content_type))
+}
+
+/// Creates the body of a request, which is throttled and tracked if needed.
+fn upload_body(
+    data: Vec<u8>,
+    options: &UploadOptions,
+    tracker: Option<&Arc<UploadTracker>>,
+) -> reqwest::blocking::Body {
+    if options.bandwidth_limit.is_none() && tracker.is_none() {
+        reqwest::blocking::Body::from(data)
+    } else {
+        let length = data.len() as u64;
+        reqwest::blocking::Body::sized(upload_reader(data, options, tracker), length)
+    }
+}
+
+/// Wraps the uploaded data into a reader that limits the upload speed and reports
+/// to the tracker.
+fn upload_reader(
+    data: Vec<u8>,
+    options: &UploadOptions,
+    tracker: Option<&Arc<UploadTracker>>,
+) -> Box<dyn Read + Send> {
+    let mut reader: Box<dyn Read + Send> = Box::new(std::io::Cursor::new(data));
+    if let Some(limit) = options.bandwidth_limit {
+        reader = Box::new(ThrottledReader::new(reader, limit));
+    }
+    if let Some(tracker) = tracker {
+        reader = Box::new(TrackedReader {
+            inner: reader,
+            tracker: tracker.clone(),
+        });
+    }
+    reader
+}
+
+/// Encodes the fields as a `multipart/form-data` body (RFC 7578) with the given boundary.
+fn encode_multipart(
+    fields: &[FormField],
+    boundary: &str,
+    options: &UploadOptions,
+) -> anyhow::Result<Vec<u8>> {
+    let delimiter = format!("--{boundary}");
+    let mut body = Vec::new();
+    for field in fields {
+        body.extend_from_slice(format!("{delimiter}\r\n").as_bytes());
+        match field {
+            FormField::Text { name, value } => {
+                body.extend_from_slice(
+                    format!("Content-Disposition: form-data; name=\"{name}\"\r\n\r\n{value}")
+                        .as_bytes(),
+                );
+            }
+            FormField::Archive { name, data } => {
+                if data
+                    .windows(delimiter.len())
+                    .any(|window| window == delimiter.as_bytes())
+                {
+                    anyhow::bail!(
+                        "The archive contains the multipart boundary `{boundary}`, use a different one"
+                    );
+                }
+                body.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; filename=\"{ARCHIVE_FILE_NAME}\"\r\nContent-Type: {}\r\n\r\n",
+                        options.content_type
+                    )
+                    .as_bytes(),
+                );
+                body.extend_from_slice(data);
+            }
+        }
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("{delimiter}--\r\n").as_bytes());
+    Ok(body)
+}
+
+/// Splits the data into chunks of `chunk_size` bytes, the last one can be shorter.
+/// Empty data is a single empty chunk.
+fn split_bytes(data: &[u8], chunk_size: usize) -> Vec<&[u8]> {
+    if data.is_empty() {
+        return vec![data];
+    }
+    data.chunks(chunk_size.max(1)).collect()
+}
+
+fn parse_submit_response(status: StatusCode, body: &[u8]) -> anyhow::Result<Option<Response>> {
+    if status != StatusCode::OK {
+        log::error!("The submit was not successful. Status error: {status}");
+        log::debug!("Response content: {}", String::from_utf8_lossy(body));
+        return Ok(None);
+    }
+    let response: Response = serde_json::from_slice(body).context("deserializing response")?;
+    Ok(Some(response))
+}
+
+/// Checks that the passed Kelvin URL points to the root of a Kelvin instance.
+pub fn validate_kelvin_url(url: &str) -> anyhow::Result<Url> {
+    let parsed = match Url::parse(url) {
+        Ok(parsed) => parsed,
+        // `url` refuses `http(s)` URLs without a host
+        Err(url::ParseError::EmptyHost) => {
+            anyhow::bail!("Kelvin URL `{url}` does not contain a host")
+        }
+        Err(error) => {
+            return Err(error)
+                .with_context(|| anyhow::anyhow!("Kelvin URL `{url}` is not a valid URL"))
+        }
+    };
+    if !matches!(parsed.scheme(), "http" | "https") {
+        anyhow::bail!(
+            "Kelvin URL `{url}` has unsupported scheme `{}`, use `https://` instead",
+            parsed.scheme()
+        );
+    }
+    if parsed.host_str().is_none_or(|host| host.is_empty()) {
+        anyhow::bail!("Kelvin URL `{url}` does not contain a host");
+    }
+    if parsed.path() != "/" {
+        anyhow::bail!(
+            "Kelvin URL `{url}` must not contain a path (`{}`), pass only the address of the server, e.g. `https://kelvin.cs.vsb.cz`",
+            parsed.path()
+        );
+    }
+    Ok(parsed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upload_options(chunk_size: Option<usize>) -> UploadOptions {
+        UploadOptions {
+            headers: HeaderMap::new(),
+            content_type: "application/zip".to_string(),
+            field_name: "submit".to_string(),
+            request_log: None,
+            bandwidth_limit: None,
+            chunk_size,
+            timeout: None,
+            response_timeout: None,
+            multipart_boundary: None,
+        }
+    }
+
+    /// Describes the fields as `name=value` for text fields and `name:<length>` for the archive.
+    fn describe_fields(fields: &[FormField]) -> Vec<String> {
+        fields
+            .iter()
+            .map(|field| match field {
+                FormField::Text { name, value } => format!("{name}={value}"),
+                FormField::Archive { name, data } => format!("{name}:{}", data.len()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn split_archive_into_chunks() {
+        let archive = [0; 10];
+        assert_eq!(
+            describe_fields(&form_fields(&archive, &upload_options(None))),
+            ["submit:10"]
+        );
+        assert_eq!(
+            describe_fields(&form_fields(&archive, &upload_options(Some(4)))),
+            [
+                "submit_total_chunks=3",
+                "submit_chunk_0:4",
+                "submit_chunk_1:4",
+                "submit_chunk_2:2"
+            ]
+        );
+        // Exact multiple of the chunk size
+        assert_eq!(
+            describe_fields(&form_fields(&archive, &upload_options(Some(5)))),
+            [
+                "submit_total_chunks=2",
+                "submit_chunk_0:5",
+                "submit_chunk_1:5"
+            ]
+        );
+        assert_eq!(
+            describe_fields(&form_fields(&archive, &upload_options(Some(100)))),
+            ["submit_total_chunks=1", "submit_chunk_0:10"]
+        );
+        // The server still receives the (empty) archive
+        assert_eq!(
+            describe_fields(&form_fields(&[], &upload_options(Some(4)))),
+            ["submit_total_chunks=1", "submit_chunk_0:0"]
+        );
+    }
+
+    #[test]
+    fn encode_multipart_form() {
+        let fields = [
+            FormField::Text {
+                name: "submit_total_chunks".to_string(),
+                value: "1".to_string(),
+            },
+            FormField::Archive {
+                name: "submit".to_string(),
+                data: b"PK\x03\x04",
+            },
+        ];
+        let body = encode_multipart(&fields, "kelvin", &upload_options(None)).unwrap();
+        assert_eq!(
+            body,
+            b"--kelvin\r\n\
+              Content-Disposition: form-data; name=\"submit_total_chunks\"\r\n\
+              \r\n\
+              1\r\n\
+              --kelvin\r\n\
+              Content-Disposition: form-data; name=\"submit\"; filename=\"submit.zip\"\r\n\
+              Content-Type: application/zip\r\n\
+              \r\n\
+              PK\x03\x04\r\n\
+              --kelvin--\r\n"
+        );
+    }
+
+    #[test]
+    fn encode_multipart_boundary_collision() {
+        let fields = [FormField::Archive {
+            name: "submit".to_string(),
+            data: b"data--kelvin-data",
+        }];
+        let error = encode_multipart(&fields, "kelvin", &upload_options(None)).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("contains the multipart boundary `kelvin`"));
+        // The boundary is only a problem when it follows `--`
+        let fields = [FormField::Archive {
+            name: "submit".to_string(),
+            data: b"data-kelvin-data",
+        }];
+        assert!(encode_multipart(&fields, "kelvin", &upload_options(None)).is_ok());
+    }
+
+    #[test]
+    fn validate_kelvin_urls() {
+        let error = |url: &str| validate_kelvin_url(url).unwrap_err().to_string();
+
+        assert!(error("ftp://kelvin.cs.vsb.cz").contains("unsupported scheme `ftp`"));
+        assert!(error("https://").contains("does not contain a host"));
+        assert!(error("kelvin.cs.vsb.cz").contains("is not a valid URL"));
+        assert!(error("https://kelvin.cs.vsb.cz/api").contains("must not contain a path (`/api`)"));
+
+        assert_eq!(
+            validate_kelvin_url("http://localhost:8000")
+                .unwrap()
+                .as_str(),
+            "http://localhost:8000/"
+        );
+        assert_eq!(
+            validate_kelvin_url("https://kelvin.cs.vsb.cz/")
+                .unwrap()
+                .host_str(),
+            Some("kelvin.cs.vsb.cz")
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use crate::cache::{clear_content_hashes, content_hashes_dir};
+use std::path::PathBuf;
+
+#[derive(clap::Parser, Debug)]
+pub struct CacheArgs {
+    #[clap(subcommand)]
+    command: CacheCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum CacheCommand {
+    /// Delete the hashes of submitted files stored by `submit --compress-only-changed`.
+    Clear(CacheClearArgs),
+}
+
+#[derive(clap::Parser, Debug)]
+struct CacheClearArgs {
+    /// Only delete the hashes of files submitted into this assignment.
+    #[clap(long)]
+    assignment: Option<u64>,
+
+    /// Directory with the hashes, if it was changed with `--content-hash-cache-dir`.
+    #[clap(long, value_name = "PATH")]
+    content_hash_cache_dir: Option<PathBuf>,
+}
+
+pub fn run_cache(args: CacheArgs) -> anyhow::Result<()> {
+    match args.command {
+        CacheCommand::Clear(args) => clear_cache(args),
+    }
+}
+
+fn clear_cache(args: CacheClearArgs) -> anyhow::Result<()> {
+    let dir = content_hashes_dir(args.content_hash_cache_dir.as_deref())?;
+    let deleted = clear_content_hashes(&dir, args.assignment)?;
+    match args.assignment {
+        Some(assignment) if deleted == 0 => {
+            log::info!("No hashes of files submitted into assignment {assignment} are stored")
+        }
+        Some(assignment) => {
+            log::info!("Deleted the hashes of files submitted into assignment {assignment}")
+        }
+        None => log::info!(
+            "Deleted the hashes of files submitted into {deleted} assignment{}",
+            if deleted == 1 { "" } else { "s" }
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,69 @@
+use crate::client::KelvinClient;
+use crate::ConnectionArgs;
+use anyhow::Context;
+use cargo_metadata::DependencyKind;
+use std::collections::HashSet;
+
+#[derive(clap::Parser, Debug)]
+pub struct CheckAllowedCratesArgs {
+    /// Assignment ID whose crate allowlist should be checked.
+    assignment_id: u64,
+
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Also check dev-dependencies.
+    #[clap(long, default_value_t = false)]
+    include_dev_deps: bool,
+}
+
+pub fn run_check_allowed_crates(
+    args: CheckAllowedCratesArgs,
+    client: &KelvinClient,
+) -> anyhow::Result<()> {
+    check_allowed_crates(client, args.assignment_id, args.include_dev_deps)
+}
+
+/// Checks that the workspace only directly depends on crates allowed by the assignment.
+pub fn check_allowed_crates(
+    client: &KelvinClient,
+    assignment_id: u64,
+    include_dev_deps: bool,
+) -> anyhow::Result<()> {
+    let allowed: HashSet<String> = client
+        .get_allowed_crates(assignment_id)?
+        .into_iter()
+        .collect();
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .exec()
+        .context("getting cargo metadata")?;
+
+    let mut disallowed = Vec::new();
+    for package in metadata.workspace_packages() {
+        for dependency in &package.dependencies {
+            if dependency.kind == DependencyKind::Development && !include_dev_deps {
+                continue;
+            }
+            // Local crates are submitted together with the rest of the workspace
+            if dependency.path.is_some() {
+                continue;
+            }
+            if !allowed.contains(&dependency.name) {
+                disallowed.push((package.name.clone(), dependency.name.clone()));
+            }
+        }
+    }
+
+    if disallowed.is_empty() {
+        log::info!("All dependencies are allowed by the assignment");
+        return Ok(());
+    }
+    for (package, dependency) in &disallowed {
+        log::error!("Package `{package}` depends on crate `{dependency}`, which is not allowed");
+    }
+    anyhow::bail!(
+        "Found {} disallowed dependenc{}",
+        disallowed.len(),
+        if disallowed.len() == 1 { "y" } else { "ies" }
+    );
+}
@@ -0,0 +1,82 @@
+use crate::{confirm, home_dir, Args};
+use anyhow::Context;
+use clap::CommandFactory;
+use clap_complete::Shell;
+use std::path::PathBuf;
+
+#[derive(clap::Parser, Debug)]
+pub struct CompletionInstallArgs {
+    /// Only print where the completion script would be written and its content.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+}
+
+pub fn run_completion_install(args: CompletionInstallArgs) -> anyhow::Result<()> {
+    let shell = detect_shell()?;
+    let path = completion_path(shell)?;
+
+    let mut script = Vec::new();
+    clap_complete::generate(shell, &mut Args::command(), "cargo", &mut script);
+
+    if args.dry_run {
+        log::info!(
+            "Completion script for {shell} would be written to {}",
+            path.display()
+        );
+        println!("{}", String::from_utf8_lossy(&script));
+        return Ok(());
+    }
+
+    if path.is_file()
+        && !confirm(
+            &format!(
+                "File {} already exists. Do you want to overwrite it?",
+                path.display()
+            ),
+            false,
+        )?
+    {
+        log::info!("Completion script was not installed");
+        return Ok(());
+    }
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| anyhow::anyhow!("Cannot create directory {parent:?}"))?;
+    }
+    std::fs::write(&path, script)
+        .with_context(|| anyhow::anyhow!("Cannot write completion script to {path:?}"))?;
+
+    log::info!(
+        "Completion script for {shell} was written to {}",
+        path.display()
+    );
+    log::info!(
+        "Run `source {}` to enable it in the current session",
+        path.display()
+    );
+    Ok(())
+}
+
+fn detect_shell() -> anyhow::Result<Shell> {
+    let shell = std::env::var("SHELL").context("cannot detect your shell, `$SHELL` is not set")?;
+    let name = shell.rsplit('/').next().unwrap_or_default();
+    match name {
+        "bash" => Ok(Shell::Bash),
+        "zsh" => Ok(Shell::Zsh),
+        "fish" => Ok(Shell::Fish),
+        _ => anyhow::bail!("Shell `{shell}` is not supported, use bash, zsh or fish"),
+    }
+}
+
+fn completion_path(shell: Shell) -> anyhow::Result<PathBuf> {
+    let home = home_dir()?;
+    Ok(match shell {
+        Shell::Zsh => home.join(".zsh").join("completions").join("_cargo-kelvin"),
+        Shell::Fish => home
+            .join(".config")
+            .join("fish")
+            .join("completions")
+            .join("cargo-kelvin.fish"),
+        _ => home.join(".bash_completion.d").join("cargo-kelvin"),
+    })
+}
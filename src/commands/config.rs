@@ -0,0 +1,311 @@
+use crate::client::validate_kelvin_url;
+use crate::config::{
+    resolve_kelvin_url, workspace_root, write_config_file, ConfigSource, GlobalConfig,
+    KelvinConfig, PROJECT_CONFIG_FILE,
+};
+use crate::logging::ColorScheme;
+use anyhow::Context;
+use clap::ValueEnum;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Parser, Debug)]
+pub struct ConfigArgs {
+    #[clap(subcommand)]
+    command: ConfigCommand,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConfigCommand {
+    /// Show the configuration used by `cargo kelvin` and where each value comes from.
+    Show(ConfigShowArgs),
+    /// Set a value in the project config (`.kelvin.toml`) or in the global config.
+    Set(ConfigSetArgs),
+    /// Print a value from the project config or the global config.
+    Get(ConfigGetArgs),
+}
+
+#[derive(clap::Parser, Debug)]
+struct ConfigSetArgs {
+    /// Key of the value, keys of nested tables are separated by dots (e.g. `a.b.c`).
+    key: String,
+
+    /// The value. It is parsed as a TOML value (e.g. `42`, `true` or `["a", "b"]`),
+    /// otherwise it is stored as a string.
+    value: String,
+
+    /// Change the global config (`~/.config/kelvin/config.toml`) instead of `.kelvin.toml`.
+    #[clap(long, default_value_t = false)]
+    global: bool,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ConfigGetArgs {
+    /// Key of the value, keys of nested tables are separated by dots (e.g. `a.b.c`).
+    key: String,
+
+    /// Only read the global config. By default, the project config takes precedence over it.
+    #[clap(long, default_value_t = false)]
+    global: bool,
+}
+
+#[derive(clap::Parser, Debug)]
+struct ConfigShowArgs {
+    /// API token, as it would be passed to other commands.
+    #[clap(long)]
+    token: Option<String>,
+
+    /// Kelvin URL, as it would be passed to other commands.
+    #[clap(long)]
+    kelvin_url: Option<String>,
+
+    /// Show the API token instead of redacting it.
+    #[clap(long, default_value_t = false)]
+    show_secrets: bool,
+}
+
+/// Values of the global options, which are parsed before the command is executed.
+pub struct GlobalOptions<'a> {
+    pub color_scheme: Option<ColorScheme>,
+    pub config: &'a GlobalConfig,
+}
+
+pub fn run_config(args: ConfigArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    match args.command {
+        ConfigCommand::Show(args) => show_config(args, global),
+        ConfigCommand::Set(args) => set_config(args),
+        ConfigCommand::Get(args) => get_config(args, global),
+    }
+}
+
+/// Keys used by `cargo kelvin` in the project config.
+const PROJECT_KEYS: [&str; 4] = [
+    "kelvin-url",
+    "submit.extra-files",
+    "submit.require-no-unsafe",
+    "submit.required-edition",
+];
+
+/// Keys used by `cargo kelvin` in the global config.
+const GLOBAL_KEYS: [&str; 3] = ["color-scheme", "kelvin-url", "token"];
+
+/// Splits a dotted key into the keys of nested tables. Fields of the configuration use
+/// kebab-case, so underscores are replaced by dashes.
+fn parse_key(key: &str) -> anyhow::Result<Vec<String>> {
+    let keys: Vec<String> = key.split('.').map(|key| key.replace('_', "-")).collect();
+    if keys.iter().any(|key| key.is_empty()) {
+        anyhow::bail!("Invalid config key `{key}`");
+    }
+    Ok(keys)
+}
+
+fn project_config_path() -> anyhow::Result<PathBuf> {
+    let root = workspace_root().context(
+        "Not inside a Cargo workspace, use `--global` to access the global config instead",
+    )?;
+    Ok(root.join(PROJECT_CONFIG_FILE))
+}
+
+fn read_document(path: &Path) -> anyhow::Result<toml_edit::DocumentMut> {
+    if !path.is_file() {
+        return Ok(toml_edit::DocumentMut::new());
+    }
+    std::fs::read_to_string(path)
+        .with_context(|| anyhow::anyhow!("Cannot read config from {path:?}"))?
+        .parse()
+        .with_context(|| anyhow::anyhow!("Cannot parse config at {path:?}"))
+}
+
+fn set_config(args: ConfigSetArgs) -> anyhow::Result<()> {
+    let keys = parse_key(&args.key)?;
+    let key = keys.join(".");
+    let path = match args.global {
+        true => GlobalConfig::path()?,
+        false => project_config_path()?,
+    };
+    let known_keys: &[&str] = match args.global {
+        true => &GLOBAL_KEYS,
+        false => &PROJECT_KEYS,
+    };
+    if !known_keys.contains(&key.as_str()) {
+        log::warn!("`{key}` is not used by `cargo kelvin`, storing it anyway");
+    }
+    if key == "kelvin-url" {
+        validate_kelvin_url(&args.value)?;
+    }
+
+    let mut document = read_document(&path)?;
+    let (last, tables) = keys.split_last().expect("Key is not empty");
+    let mut table = document.as_table_mut() as &mut dyn toml_edit::TableLike;
+    for (index, name) in tables.iter().enumerate() {
+        table = table
+            .entry(name)
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                toml_edit::Item::Table(table)
+            })
+            .as_table_like_mut()
+            .with_context(|| anyhow::anyhow!("`{}` is not a table", keys[..=index].join(".")))?;
+    }
+    let value = args
+        .value
+        .parse::<toml_edit::Value>()
+        .unwrap_or_else(|_| args.value.as_str().into());
+    table.insert(last, toml_edit::value(value));
+
+    // Check that the config can still be loaded
+    let content = document.to_string();
+    let valid = match args.global {
+        true => toml::from_str::<GlobalConfig>(&content).map(|_| ()),
+        false => toml::from_str::<KelvinConfig>(&content).map(|_| ()),
+    };
+    valid.with_context(|| anyhow::anyhow!("Invalid value `{}` of `{key}`", args.value))?;
+
+    write_config_file(&path, &content, args.global)?;
+    log::info!("Set `{key}` in {}", path.display());
+    Ok(())
+}
+
+fn get_config(args: ConfigGetArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    let keys = parse_key(&args.key)?;
+    let key = keys.join(".");
+    // The Kelvin URL can also come from the environment
+    if key == "kelvin-url" && !args.global {
+        let (kelvin_url, source) = resolve_kelvin_url(None, global.config)?;
+        log::debug!("`kelvin-url` comes from {source}");
+        println!("{kelvin_url}");
+        return Ok(());
+    }
+
+    let mut paths = vec![GlobalConfig::path()?];
+    if !args.global {
+        if let Ok(path) = project_config_path() {
+            paths.insert(0, path);
+        }
+    }
+    for path in paths {
+        let document = read_document(&path)?;
+        let mut item = Some(document.as_item());
+        for name in &keys {
+            item = item.and_then(|item| item.get(name));
+        }
+        match item {
+            Some(toml_edit::Item::Value(toml_edit::Value::String(value))) => {
+                println!("{}", value.value());
+            }
+            Some(toml_edit::Item::Value(value)) => println!("{}", value.to_string().trim()),
+            Some(item) if !item.is_none() => println!("{}", item.to_string().trim()),
+            _ => continue,
+        }
+        log::debug!("`{key}` comes from {}", path.display());
+        return Ok(());
+    }
+    anyhow::bail!("`{key}` is not set")
+}
+
+fn show_config(args: ConfigShowArgs, global: GlobalOptions) -> anyhow::Result<()> {
+    let mut rows: Vec<(&str, String, String)> = Vec::new();
+
+    let redact = |token: String| {
+        if args.show_secrets {
+            token
+        } else {
+            "<redacted>".to_string()
+        }
+    };
+    let global_path = GlobalConfig::path()?;
+    let token = match (args.token, std::env::var("KELVIN_API_TOKEN")) {
+        (Some(token), _) => (redact(token), ConfigSource::Cli.to_string()),
+        (None, Ok(token)) => (
+            redact(token),
+            ConfigSource::Env("KELVIN_API_TOKEN").to_string(),
+        ),
+        (None, Err(_)) => match &global.config.token {
+            Some(token) => (
+                redact(token.clone()),
+                ConfigSource::GlobalConfig(global_path.clone()).to_string(),
+            ),
+            None => ("<not set>".to_string(), "-".to_string()),
+        },
+    };
+    rows.push(("token", token.0, token.1));
+
+    let (kelvin_url, source) = resolve_kelvin_url(args.kelvin_url.as_deref(), global.config)?;
+    rows.push(("kelvin-url", kelvin_url, source.to_string()));
+
+    let color_scheme = match (global.color_scheme, global.config.color_scheme) {
+        (Some(scheme), _) => (scheme, ConfigSource::Cli),
+        (None, Some(scheme)) => (scheme, ConfigSource::GlobalConfig(global_path)),
+        (None, None) => (ColorScheme::default(), ConfigSource::Default),
+    };
+    rows.push((
+        "color-scheme",
+        color_scheme
+            .0
+            .to_possible_value()
+            .map(|value| value.get_name().to_string())
+            .unwrap_or_default(),
+        color_scheme.1.to_string(),
+    ));
+
+    match workspace_root() {
+        Some(root) => {
+            let path = root.join(PROJECT_CONFIG_FILE);
+            let config = KelvinConfig::load(&root)?;
+            let source = if path.is_file() {
+                ConfigSource::ProjectConfig(path)
+            } else {
+                ConfigSource::Default
+            };
+            let extra_files = config
+                .submit
+                .extra_files
+                .iter()
+                .map(|file| file.src.as_str())
+                .collect::<Vec<_>>();
+            rows.push((
+                "submit.extra-files",
+                format!("[{}]", extra_files.join(", ")),
+                source.to_string(),
+            ));
+            rows.push((
+                "submit.require-no-unsafe",
+                config.submit.require_no_unsafe.to_string(),
+                source.to_string(),
+            ));
+            rows.push((
+                "submit.required-edition",
+                config
+                    .submit
+                    .required_edition
+                    .clone()
+                    .unwrap_or_else(|| "<not set>".to_string()),
+                source.to_string(),
+            ));
+        }
+        None => {
+            rows.push((
+                "submit.extra-files",
+                "[]".to_string(),
+                "default (not inside a Cargo workspace)".to_string(),
+            ));
+            rows.push((
+                "submit.require-no-unsafe",
+                "false".to_string(),
+                "default (not inside a Cargo workspace)".to_string(),
+            ));
+            rows.push((
+                "submit.required-edition",
+                "<not set>".to_string(),
+                "default (not inside a Cargo workspace)".to_string(),
+            ));
+        }
+    }
+
+    let key_width = rows.iter().map(|row| row.0.len()).max().unwrap_or(0);
+    let value_width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+    for (key, value, source) in rows {
+        println!("{key:<key_width$}  {value:<value_width$}  {source}");
+    }
+    Ok(())
+}
@@ -0,0 +1,60 @@
+use crate::cache::cached_course_data;
+use crate::client::{CourseData, KelvinClient};
+use crate::{ConnectionArgs, OutputFormat};
+use anyhow::Context;
+
+#[derive(clap::Parser, Debug)]
+pub struct CoursesArgs {
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Format of the printed courses.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+}
+
+pub fn run_courses(args: CoursesArgs, client: &KelvinClient) -> anyhow::Result<()> {
+    let courses = get_courses(client)?;
+    match args.format {
+        OutputFormat::Human => {
+            for course in &courses {
+                println!("{:>6}  {}", course.id, course.name);
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&courses).context("serializing courses")?
+        ),
+    }
+    Ok(())
+}
+
+/// Fetches the courses of the user, using cached data if they are recent enough.
+pub fn get_courses(client: &KelvinClient) -> anyhow::Result<Vec<CourseData>> {
+    cached_course_data(&cache_key(client, "courses"), || client.get_courses())
+}
+
+/// Fetches a course with its tasks, using cached data if they are recent enough.
+pub fn get_course_tasks(client: &KelvinClient, course_id: u64) -> anyhow::Result<CourseData> {
+    cached_course_data(
+        &cache_key(client, &format!("courses/{course_id}/tasks")),
+        || client.get_course_tasks(course_id),
+    )
+}
+
+/// Cached data differ between users, so the key also contains a fingerprint of the token.
+fn cache_key(client: &KelvinClient, path: &str) -> String {
+    format!(
+        "{}/{path}#{}",
+        client.kelvin_url(),
+        client.token_fingerprint()
+    )
+}
+
+/// Address of the page of the course in Kelvin.
+pub fn course_url(client: &KelvinClient, course: &CourseData) -> String {
+    course
+        .url
+        .clone()
+        .unwrap_or_else(|| format!("{}/course/{}", client.kelvin_url(), course.id))
+}
@@ -0,0 +1,102 @@
+use crate::client::KelvinClient;
+use crate::ConnectionArgs;
+use anyhow::Context;
+use textplots::{Chart, Plot, Shape};
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum TrendFormat {
+    Chart,
+    Table,
+    Json,
+}
+
+#[derive(clap::Parser, Debug)]
+pub struct GradeTrendArgs {
+    /// Assignment ID whose submits should be shown.
+    assignment_id: u64,
+
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// How should the scores be displayed.
+    #[clap(long, value_enum, default_value_t = TrendFormat::Chart)]
+    format: TrendFormat,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct TrendStats {
+    submits: usize,
+    first_score: Option<f64>,
+    best_score: Option<f64>,
+    last_score: Option<f64>,
+}
+
+pub fn run_grade_trend(args: GradeTrendArgs, client: &KelvinClient) -> anyhow::Result<()> {
+    let submits = client.get_submits(args.assignment_id)?;
+
+    let scores: Vec<f64> = submits.iter().filter_map(|submit| submit.score).collect();
+    let stats = TrendStats {
+        submits: submits.len(),
+        first_score: scores.first().copied(),
+        best_score: scores.iter().copied().reduce(f64::max),
+        last_score: scores.last().copied(),
+    };
+
+    match args.format {
+        TrendFormat::Json => {
+            let output = serde_json::json!({
+                "submits": submits,
+                "stats": stats,
+            });
+            println!(
+                "{}",
+                serde_json::to_string(&output).context("serializing grade trend")?
+            );
+            return Ok(());
+        }
+        TrendFormat::Chart => {
+            if scores.is_empty() {
+                println!("No graded submits yet");
+            } else {
+                let points: Vec<(f32, f32)> = scores
+                    .iter()
+                    .enumerate()
+                    .map(|(index, score)| ((index + 1) as f32, *score as f32))
+                    .collect();
+                Chart::new_with_y_range(100, 40, 1.0, points.len().max(2) as f32, 0.0, 100.0)
+                    .lineplot(&Shape::Lines(&points))
+                    .display();
+            }
+        }
+        TrendFormat::Table => {
+            println!(
+                "{:>3}  {:>8}  {:<25}  {:>6}",
+                "#", "Submit", "Created at", "Score"
+            );
+            for (index, submit) in submits.iter().enumerate() {
+                let score = submit
+                    .score
+                    .map(|score| format!("{score}%"))
+                    .unwrap_or_else(|| "-".to_string());
+                println!(
+                    "{:>3}  {:>8}  {:<25}  {score:>6}",
+                    index + 1,
+                    submit.id,
+                    submit.created_at
+                );
+            }
+        }
+    }
+
+    let format_score = |score: Option<f64>| {
+        score
+            .map(|score| format!("{score}%"))
+            .unwrap_or_else(|| "-".to_string())
+    };
+    println!();
+    println!("Submits: {}", stats.submits);
+    println!("First score: {}", format_score(stats.first_score));
+    println!("Best score: {}", format_score(stats.best_score));
+    println!("Last score: {}", format_score(stats.last_score));
+    Ok(())
+}
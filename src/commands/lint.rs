@@ -0,0 +1,116 @@
+use crate::archive::{get_manifest_path, SubmitScope};
+use crate::client::KelvinClient;
+use crate::commands::check_allowed_crates::check_allowed_crates;
+use crate::{todo_check, ConnectionArgs};
+use anyhow::Context;
+use std::path::Path;
+use std::process::{Command, ExitCode};
+use std::time::SystemTime;
+
+#[derive(clap::Parser, Debug)]
+pub struct LintArgs {
+    /// Assignment ID whose requirements should be checked.
+    assignment_id: u64,
+
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Fix problems that can be fixed automatically, e.g. formatting.
+    #[clap(long, default_value_t = false)]
+    fix: bool,
+}
+
+/// Performs all checks that can be done before submitting and prints their results.
+/// Fails with exit code 1 if any of the checks does not pass.
+pub fn run_lint(args: LintArgs, client: &KelvinClient) -> anyhow::Result<ExitCode> {
+    let manifest = get_manifest_path(Some(SubmitScope::Workspace))?;
+    let root = manifest.parent().expect("Manifest path has no parent");
+
+    let checks: Vec<(&str, anyhow::Result<()>)> = vec![
+        ("API token", client.get_user_info().map(|_| ())),
+        ("Deadline", check_deadline(client, args.assignment_id)),
+        ("Cargo.lock", check_lockfile(root)),
+        ("Unfinished code", check_todo(root)),
+        (
+            "Allowed crates",
+            check_allowed_crates(client, args.assignment_id, false),
+        ),
+        ("cargo check", run_cargo(root, &["check", "--quiet"])),
+        ("Formatting", check_formatting(root, args.fix)),
+    ];
+
+    let mut failed = 0;
+    for (name, result) in &checks {
+        match result {
+            Ok(()) => println!("[PASS] {name}"),
+            Err(error) => {
+                println!("[FAIL] {name}: {error:#}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        println!("{failed} of {} checks failed", checks.len());
+        Ok(ExitCode::FAILURE)
+    } else {
+        println!("All checks passed");
+        Ok(ExitCode::SUCCESS)
+    }
+}
+
+fn check_deadline(client: &KelvinClient, assignment_id: u64) -> anyhow::Result<()> {
+    let task = client.get_task_info(assignment_id)?;
+    let Some(deadline) = task.deadline else {
+        return Ok(());
+    };
+    // Kelvin may omit seconds from the deadline
+    let Ok(time) = humantime::parse_rfc3339_weak(&deadline)
+        .or_else(|_| humantime::parse_rfc3339_weak(&format!("{deadline}:00")))
+    else {
+        log::warn!("Cannot parse deadline `{deadline}`, it was not checked");
+        return Ok(());
+    };
+    if time < SystemTime::now() {
+        anyhow::bail!("The deadline ({deadline}) has already passed");
+    }
+    Ok(())
+}
+
+fn check_lockfile(root: &Path) -> anyhow::Result<()> {
+    if !root.join("Cargo.lock").is_file() {
+        anyhow::bail!("`Cargo.lock` was not found, generate it with `cargo generate-lockfile`");
+    }
+    Ok(())
+}
+
+fn check_todo(root: &Path) -> anyhow::Result<()> {
+    let count = todo_check::find_forbidden_patterns(root, true, &[])?;
+    if count > 0 {
+        anyhow::bail!(
+            "Found {count} line{} with unfinished code",
+            if count == 1 { "" } else { "s" }
+        );
+    }
+    Ok(())
+}
+
+fn check_formatting(root: &Path, fix: bool) -> anyhow::Result<()> {
+    if fix {
+        run_cargo(root, &["fmt", "--all"])
+    } else {
+        run_cargo(root, &["fmt", "--all", "--check"])
+            .context("the code is not formatted, run `cargo kelvin lint --fix` to format it")
+    }
+}
+
+fn run_cargo(root: &Path, args: &[&str]) -> anyhow::Result<()> {
+    let status = Command::new("cargo")
+        .args(args)
+        .current_dir(root)
+        .status()
+        .with_context(|| anyhow::anyhow!("Cannot run `cargo {}`", args.join(" ")))?;
+    if !status.success() {
+        anyhow::bail!("`cargo {}` failed", args.join(" "));
+    }
+    Ok(())
+}
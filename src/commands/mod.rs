@@ -0,0 +1,12 @@
+pub mod cache;
+pub mod check_allowed_crates;
+pub mod completion_install;
+pub mod config;
+pub mod courses;
+pub mod grade_trend;
+pub mod lint;
+pub mod submit;
+pub mod task_info;
+pub mod task_list;
+pub mod token;
+pub mod whoami;
@@ -0,0 +1,2799 @@
+use crate::archive::{
+    compress_workspace, get_manifest_path, read_delta_manifest, read_workspace_hash,
+    ArchiveOptions, GeneratedFile, SubmitScope, DELTA_MANIFEST, FILENAME_MANIFEST, FILE_LIST,
+};
+use crate::audit::SECURITY_AUDIT_FILE;
+use crate::benchmark::BenchmarkResult;
+use crate::cache::CONTENT_HASHES_MAX_AGE;
+use crate::cargo_tree::{CARGO_TREE_FILE, CARGO_TREE_JSON_FILE};
+use crate::checksum::{to_hex, ChecksumAlgorithm};
+use crate::client::{
+    HttpVersion, KelvinClient, Response, SubmitStatus, UploadOptions, DEFAULT_AGENT,
+    MAX_API_VERSION, MIN_API_VERSION, VERSION_HEADER,
+};
+use crate::commands::check_allowed_crates::check_allowed_crates;
+use crate::config::{ExtraFile, KelvinConfig, RUST_EDITIONS};
+use crate::coverage::COVERAGE_REPORT_FILE;
+use crate::environment::{BUILD_METADATA_FILE, ENV_FILE};
+use crate::flamegraph::{ProfiledRun, FLAMEGRAPH_FILE};
+use crate::git_history::{GIT_DIFF_FILE, GIT_LOG_FILE};
+use crate::history::HISTORY_FILE;
+use crate::lines::LineCounts;
+use crate::lock_provenance::{CARGO_METADATA_FILE, CARGO_VERIFY_FILE};
+use crate::logging::{Emoji, LogFormat};
+use crate::progress::{ProgressFile, Stage};
+use crate::rate_limit::RateLimiter;
+use crate::readme::{SubmissionInfo, SUBMISSION_README_FILE};
+use crate::request_log::RequestLog;
+use crate::sccache::SCCACHE_STATS_FILE;
+use crate::snapshot::WorkspaceSnapshot;
+use crate::stats::WORKSPACE_STATS_FILE;
+use crate::test_report::DEFAULT_TEST_REPORT;
+use crate::test_vectors::TEST_VECTORS_DIR;
+use crate::vendor::VendoredDependencies;
+use crate::{
+    archive_diff, audit, benchmark, binary, cache, cache_dir, cargo_tree, checksum, confirm,
+    coverage, environment, features, flamegraph, git_history, history, lines, lock_provenance,
+    makefile, notification, read_password, readme, sccache, secrets, signing, solution_template,
+    stats, submodules, test_report, test_vectors, todo_check, toolchain, unused_use, update,
+    upload_stats, vendor, ConnectionArgs, OutputFormat,
+};
+use anyhow::Context;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION,
+};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::rc::Rc;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+#[derive(clap::Parser, Debug)]
+pub struct SubmitArgs {
+    /// Assignment ID into which your code should be submitted.
+    /// You can find it in the URL of the task, i.e. `https://kelvin.cs.vsb.cz/task/<assignment-id>/<your-login>`.
+    #[clap(required_unless_present = "split_by_crate")]
+    assignment_id: Option<u64>,
+
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Do not open the browser after uploading the submit.
+    #[clap(long, default_value_t = false)]
+    no_open: bool,
+
+    /// Wait until the submit is graded and fail with exit code 2 if its score (0-100)
+    /// is below the given value. Fails with exit code 1 if the submit was rejected or
+    /// Kelvin did not report its score.
+    #[clap(long, value_parser = clap::value_parser!(u8).range(0..=100))]
+    expect_score: Option<u8>,
+
+    /// How often (in seconds) to check whether the submit was already graded.
+    #[clap(long, default_value_t = 5)]
+    score_poll_interval: u64,
+
+    /// How long (in seconds) to wait for the submit to be graded.
+    #[clap(long, default_value_t = 120)]
+    score_poll_timeout: u64,
+
+    /// Do not wait for the submit to be graded, even if `--expect-score` is used.
+    #[clap(long, default_value_t = false)]
+    no_poll: bool,
+
+    /// Format in which the result of the submit is printed to stdout.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Human)]
+    output_format: OutputFormat,
+
+    /// Check (at most once per day) if a new version of `cargo kelvin` is available.
+    #[clap(long, default_value_t = false, overrides_with = "no_update_check")]
+    check_updates: bool,
+
+    /// Never check if a new version of `cargo kelvin` is available.
+    #[clap(long, default_value_t = false, overrides_with = "check_updates")]
+    no_update_check: bool,
+
+    /// Store the uncommitted state of the workspace in `git stash` while the submit is being
+    /// created, so that it can be recovered if something goes wrong. The submit fails if the
+    /// workspace is modified before its archive is created.
+    #[clap(long, default_value_t = false)]
+    workspace_snapshot: bool,
+
+    /// Keep the workspace snapshot in `git stash` after the submit is finished.
+    #[clap(long, default_value_t = false, requires = "workspace_snapshot")]
+    keep_snapshot: bool,
+
+    /// Remove the given directory prefix from paths of files stored in the archive.
+    #[clap(long)]
+    strip_prefix: Option<PathBuf>,
+
+    /// Put all files in the archive into the given directory.
+    #[clap(long)]
+    add_prefix: Option<PathBuf>,
+
+    /// Store all files at the top level of the archive, without any directories.
+    #[clap(long, default_value_t = false, conflicts_with = "strip_prefix")]
+    flatten: bool,
+
+    /// Maximum number of submits per minute (0 means no limit).
+    /// If the last submit was performed too recently, wait before uploading.
+    #[clap(long, default_value_t = 0)]
+    rate_limit: u32,
+
+    /// Write the progress of the submit to the given file as a JSON object, e.g.
+    /// `{"stage": "compressing", "files_done": 12, "files_total": 45, "percent": 26}`.
+    /// The stages are `compressing`, `compressed`, `uploading`, `uploaded`, `complete`
+    /// and `error`.
+    #[clap(long)]
+    progress_file: Option<PathBuf>,
+
+    /// Read at most the given number of workspace files at once. Higher values speed up
+    /// submits from network filesystems (NFS, SMB).
+    #[clap(long, default_value_t = 4, value_parser = clap::value_parser!(u32).range(1..=64))]
+    max_concurrent_file_reads: u32,
+
+    /// Do not submit if the assignment was already submitted the given number of times
+    /// within `--time-window`, according to `.kelvin-history.json` in the workspace root.
+    /// This is only a local safeguard, the limits of the server still apply.
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "split_by_crate"
+    )]
+    max_submit_attempts: Option<u64>,
+
+    /// Time window of `--max-submit-attempts`, e.g. `12h` or `7days`.
+    #[clap(
+        long,
+        default_value = "24h",
+        value_parser = humantime::parse_duration,
+        requires = "max_submit_attempts"
+    )]
+    time_window: Duration,
+
+    /// Do not include tests in the archive, i.e. files in `tests/` and `benches/` directories
+    /// and source files that contain `#[cfg(test)]` near their beginning.
+    #[clap(long, default_value_t = false, overrides_with = "include_tests")]
+    exclude_tests: bool,
+
+    /// Include tests in the archive (default).
+    #[clap(long, default_value_t = false, overrides_with = "exclude_tests")]
+    include_tests: bool,
+
+    /// Do not include the files of git submodules (listed in `.gitmodules`) in the archive
+    /// (default).
+    #[clap(
+        long,
+        default_value_t = false,
+        overrides_with = "include_git_submodules"
+    )]
+    ignore_git_submodules: bool,
+
+    /// Include the files of git submodules in the archive, e.g. shared libraries that
+    /// the submit depends on.
+    #[clap(
+        long,
+        default_value_t = false,
+        overrides_with = "ignore_git_submodules"
+    )]
+    include_git_submodules: bool,
+
+    /// Only include files in the workspace root and in its direct subdirectories
+    /// (e.g. `src/main.rs`, but not `src/a/b.rs`).
+    #[clap(long, default_value_t = false)]
+    include_workspace_root_only: bool,
+
+    /// Only include files from the `src` directory, together with `Cargo.toml`
+    /// and `Cargo.lock`.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with = "include_workspace_root_only"
+    )]
+    include_src_only: bool,
+
+    /// Include files of all types from the `examples` directory, e.g. input data
+    /// in `examples/data`. Otherwise, only the usual source and text files are included.
+    #[clap(long, default_value_t = false, conflicts_with = "exclude_examples")]
+    include_examples: bool,
+
+    /// Do not include the `examples` directory.
+    #[clap(long, default_value_t = false)]
+    exclude_examples: bool,
+
+    /// Include templates of environment files with placeholder values (`.env.example`,
+    /// `.env.test` and `.env.template`). The `.env` file itself is never included.
+    #[clap(long, default_value_t = false)]
+    include_dotenv: bool,
+
+    /// Include files that are ignored by `.gitignore` or `.ignore` files, e.g. generated
+    /// sources needed by the grader. Each such file is reported with a warning. Files
+    /// can still be excluded by `.kelvinignore` files, which use the `.gitignore` format.
+    #[clap(long, default_value_t = false)]
+    include_ignored: bool,
+
+    /// Abort the submit if a file cannot be read or written to the archive. By default,
+    /// such files are skipped with a warning, which can produce an incomplete archive.
+    #[clap(long, default_value_t = false)]
+    fail_fast_archive: bool,
+
+    /// Abort the submit if a directory of the workspace cannot be walked (e.g. because of
+    /// missing permissions or an invalid ignore file), instead of skipping it with a warning.
+    #[clap(long, default_value_t = false)]
+    fail_fast_walk: bool,
+
+    /// Only include Rust files that are compiled when the workspace is built with the given
+    /// features (comma-separated), so that unfinished code of other features is not
+    /// submitted. The files are found by running `cargo check --all-targets --features`.
+    #[clap(long, value_delimiter = ',', num_args = 1..)]
+    filter_by_cargo_features: Vec<String>,
+
+    /// Only store files that changed since the last submit into the assignment from this
+    /// computer, based on hashes of their content. The other files are listed in
+    /// `manifest.json` and Kelvin takes them from the previous submit. Only used if the
+    /// assignment supports it.
+    #[clap(long, default_value_t = false, conflicts_with_all = ["batch", "split_by_crate"])]
+    compress_only_changed: bool,
+
+    /// Directory in which `--compress-only-changed` stores the hashes of the submitted
+    /// files, `~/.cache/kelvin/content-hashes` by default. Useful e.g. in containers
+    /// where the home directory is read-only.
+    #[clap(long, value_name = "PATH", requires = "compress_only_changed")]
+    content_hash_cache_dir: Option<PathBuf>,
+
+    /// Submit all files with `--compress-only-changed` if the hashes of the previously
+    /// submitted files are older than this, e.g. `7days`.
+    #[clap(
+        long,
+        default_value = CONTENT_HASHES_MAX_AGE,
+        value_parser = humantime::parse_duration,
+        requires = "compress_only_changed"
+    )]
+    content_hash_max_age: Duration,
+
+    /// Always include build scripts (`build.rs`) and the full source of proc-macro crates,
+    /// even with `--include-src-only` or `--include-workspace-root-only`, and warn about
+    /// proc-macro crates, which have to be supported by the grader.
+    #[clap(long, default_value_t = false)]
+    include_proc_macros: bool,
+
+    /// Sign the SHA-256 digest of the archive with the given RSA or Ed25519 private key
+    /// (in PEM format). The signature and the public key are sent along with the submit.
+    #[clap(long)]
+    sign_with_key: Option<PathBuf>,
+
+    /// Create the archive, but do not upload it to Kelvin.
+    #[clap(long, default_value_t = false)]
+    dry_run: bool,
+
+    /// Upload the archive so that Kelvin builds and tests it, but does not store the submit.
+    /// It does not count against the submit limit of the assignment. Use `--dry-run` first
+    /// to check the archive locally.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["dry_run", "batch", "split_by_crate", "two_phase_upload"]
+    )]
+    dry_run_server: bool,
+
+    /// Check that the workspace only depends on crates allowed by the assignment
+    /// before submitting.
+    #[clap(long, default_value_t = false)]
+    check_crates: bool,
+
+    /// Do not include `Cargo.lock` in the archive.
+    #[clap(long, default_value_t = false, conflicts_with = "require_lock")]
+    no_lock: bool,
+
+    /// Do not include `Cargo.toml` and `Cargo.lock` of the workspace root in the archive.
+    /// The submit does not compile without them, so this is only useful if the grader
+    /// of the assignment injects its own manifest. Use `--no-lock` to leave out only
+    /// `Cargo.lock`.
+    #[clap(long, default_value_t = false)]
+    no_manifest: bool,
+
+    /// Fail if the workspace does not contain `Cargo.lock`.
+    #[clap(long, default_value_t = false)]
+    require_lock: bool,
+
+    /// Measure how long it takes to compress the workspace (three runs) and to upload
+    /// the archive.
+    #[clap(long, default_value_t = false)]
+    benchmark: bool,
+
+    /// Include macOS metadata (`__MACOSX` directories, `._` and `.DS_Store` files) in the
+    /// archive, also when they come from `--append-to-archive`.
+    #[clap(long, default_value_t = false)]
+    macos_metadata: bool,
+
+    /// Download all dependencies with `cargo vendor` and include them in the archive
+    /// (in `vendor/`), together with `.cargo/config.toml` that makes Cargo use them.
+    /// Useful for graders without network access.
+    #[clap(long, default_value_t = false, conflicts_with = "flatten")]
+    include_cargo_home_vendored: bool,
+
+    /// Include `.cargo/config.toml` of the workspace in the archive. It is skipped by default,
+    /// because files in hidden directories are not included.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["include_cargo_home_vendored", "flatten"]
+    )]
+    include_cargo_config: bool,
+
+    /// Remove debug symbols from executables included as extra files (configured in
+    /// `.kelvin.toml`). Only the copies stored in the archive are stripped.
+    #[clap(long, default_value_t = false)]
+    strip_debug_symbols: bool,
+
+    /// Content type of the uploaded archive.
+    #[clap(long, default_value = "application/zip")]
+    content_type: String,
+
+    /// Name of the multipart form field that contains the uploaded archive.
+    #[clap(long, default_value = "solution")]
+    multipart_field_name: String,
+
+    /// Compute a checksum of the archive and send it to Kelvin, so that it can verify
+    /// the integrity of the upload.
+    #[clap(long, value_enum)]
+    checksum_algorithm: Option<ChecksumAlgorithm>,
+
+    /// Only include files modified within the given duration (e.g. `30m`, `1h30m` or `2days`).
+    /// `Cargo.toml` and `Cargo.lock` files are always included.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    limit_to_changed_since: Option<Duration>,
+
+    /// Wait until the submit is graded and then show a desktop notification with its score.
+    #[clap(long, default_value_t = false)]
+    notify_desktop: bool,
+
+    /// Show a desktop notification if the submit could not be uploaded.
+    #[clap(long, default_value_t = false)]
+    notify_on_error: bool,
+
+    /// Store versions of the Rust toolchain and the time of the submit in `.kelvin-env.txt`
+    /// in the archive.
+    #[clap(long, default_value_t = false)]
+    record_env: bool,
+
+    /// Store information about the build environment (versions of cargo-kelvin and rustc,
+    /// the operating system, the time of the submit and the current git commit) in
+    /// `build_metadata.json` in the archive.
+    #[clap(long, default_value_t = false)]
+    inject_build_metadata: bool,
+
+    /// Also store the name of this computer in `build_metadata.json`.
+    #[clap(long, default_value_t = false, requires = "inject_build_metadata")]
+    include_hostname: bool,
+
+    /// Store the last N commits (`git log --oneline`) in `git_history.txt` in the archive,
+    /// 5 if no value is given (`--embed-git-log=N`). Skipped outside of a git repository.
+    #[clap(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "5",
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    embed_git_log: Option<u64>,
+
+    /// Store uncommitted changes (`git diff HEAD`) in `uncommitted_changes.diff` in
+    /// the archive. Skipped outside of a git repository.
+    #[clap(long, default_value_t = false)]
+    embed_git_diff: bool,
+
+    /// Store the dependency tree of the workspace (`cargo tree --workspace`) in
+    /// `cargo_tree.txt` in the archive. Skipped if `cargo tree` fails.
+    #[clap(long, default_value_t = false)]
+    with_cargo_tree: bool,
+
+    /// Store the dependency tree of the workspace as JSON in `cargo_tree.json` in
+    /// the archive. Skipped if `cargo tree` fails.
+    #[clap(long, default_value_t = false)]
+    with_cargo_tree_json: bool,
+
+    /// Store the metadata of the workspace packages (`cargo metadata --locked --no-deps`)
+    /// in `cargo_metadata.json` and the output of `cargo verify-project` in
+    /// `cargo_verify.txt` in the archive, so that graders can compare them with their own
+    /// build. The metadata are skipped if `Cargo.lock` is not up to date.
+    #[clap(long, default_value_t = false)]
+    with_lock_provenance: bool,
+
+    /// Include dev-dependencies in the trees stored by `--with-cargo-tree` and
+    /// `--with-cargo-tree-json`.
+    #[clap(long, default_value_t = false)]
+    with_dev_dep_tree: bool,
+
+    /// Run `cargo test` and store its output in the archive, so that it can be seen
+    /// by the teachers.
+    #[clap(long, default_value_t = false)]
+    with_test_report: bool,
+
+    /// Run all tests (including ignored ones) and store the failing cases found by
+    /// property-based tests (`proptest-regressions` files) in `test_vectors/` in the archive,
+    /// so that the graders can reproduce them.
+    #[clap(long, default_value_t = false)]
+    with_test_vectors: bool,
+
+    /// Name of the file with the output of `cargo test` in the archive.
+    #[clap(long, default_value = DEFAULT_TEST_REPORT, requires = "with_test_report")]
+    test_report_name: PathBuf,
+
+    /// Do not submit if the tests run by `--with-test-report` fail.
+    #[clap(long, default_value_t = false, requires = "with_test_report")]
+    abort_on_test_failure: bool,
+
+    /// Measure the test coverage with `cargo llvm-cov` (or `cargo tarpaulin`) and store
+    /// the report in `coverage_report.json` in the archive. Skipped if neither tool is installed.
+    #[clap(long, default_value_t = false)]
+    report_uncovered: bool,
+
+    /// Do not submit if the line coverage measured by `--report-uncovered` is below
+    /// the given percentage.
+    #[clap(long, requires = "report_uncovered")]
+    min_coverage: Option<f64>,
+
+    /// Store the statistics of the `sccache` compilation cache (`sccache --show-stats`)
+    /// in `sccache_stats.json` in the archive, e.g. to show the speedup of the cache.
+    /// Skipped if `sccache` is not installed.
+    #[clap(long, default_value_t = false)]
+    with_sccache_stats: bool,
+
+    /// Profile the binary of the workspace with `cargo flamegraph` and store the result
+    /// in `flamegraph.svg` in the archive. Skipped if `cargo-flamegraph` is not installed
+    /// or if the flamegraph is larger than 5 MiB.
+    #[clap(long, default_value_t = false)]
+    with_flamegraph: bool,
+
+    /// Binary profiled by `--with-flamegraph`, required if the workspace has more than
+    /// one binary.
+    #[clap(long, requires = "with_flamegraph")]
+    flamegraph_bin: Option<String>,
+
+    /// Arguments (separated by spaces) passed to the binary profiled by `--with-flamegraph`.
+    #[clap(long, allow_hyphen_values = true, requires = "with_flamegraph")]
+    flamegraph_args: Option<String>,
+
+    /// File passed to the standard input of the binary profiled by `--with-flamegraph`.
+    #[clap(long, requires = "with_flamegraph")]
+    flamegraph_input_data: Option<PathBuf>,
+
+    /// Run `cargo audit` and store its JSON output in `security_audit.json` in the archive,
+    /// so that the teachers can see whether the dependencies have known vulnerabilities.
+    #[clap(long, default_value_t = false)]
+    with_dependencies_audit: bool,
+
+    /// Do not submit if `--with-dependencies-audit` finds a critical vulnerability
+    /// (with a CVSS score of at least 9.0).
+    #[clap(long, default_value_t = false, requires = "with_dependencies_audit")]
+    abort_on_vulnerability: bool,
+
+    /// Submit the whole workspace or only the package in the current directory.
+    /// If not given and the current directory is inside a package of a larger workspace,
+    /// you will be asked interactively.
+    #[clap(long, value_enum)]
+    scope: Option<SubmitScope>,
+
+    /// Also store all entries of the given ZIP archive in the submit.
+    /// Files from the workspace take precedence over entries with the same path.
+    #[clap(long)]
+    append_to_archive: Option<PathBuf>,
+
+    /// Save the archive to the given path. The path is recorded in `.kelvin-history.json`
+    /// in the workspace root, so that the archive can be uploaded again with
+    /// `--reuse-last-archive`.
+    #[clap(long)]
+    save_archive: Option<PathBuf>,
+
+    /// Upload the archive that was saved most recently by `--save-archive` instead of
+    /// compressing the workspace again. Options that change the content of the archive
+    /// have no effect.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["save_archive", "benchmark", "two_phase_upload", "split_by_crate"]
+    )]
+    reuse_last_archive: bool,
+
+    /// Count lines of code, comments and blank lines in the Rust files of the submit.
+    #[clap(long, default_value_t = false)]
+    measure_lines: bool,
+
+    /// Abort the submit if a Rust file contains `todo!()`, `unimplemented!()`
+    /// or a `// TODO` comment.
+    #[clap(long, default_value_t = false)]
+    abort_on_todo: bool,
+
+    /// Abort the submit if a line of a Rust file matches the given regular expression.
+    /// Can be used multiple times.
+    #[clap(long)]
+    abort_on_pattern: Vec<String>,
+
+    /// Only report the occurrences found by `--abort-on-todo` and `--abort-on-pattern`,
+    /// but submit anyway.
+    #[clap(long, default_value_t = false)]
+    allow_todo: bool,
+
+    /// Abort the submit if a Rust file contains the `unsafe` keyword (outside of comments
+    /// and string literals), e.g. for assignments that forbid `unsafe` code. Can also be
+    /// enabled by `require-no-unsafe = true` in the `[submit]` table of `.kelvin.toml`.
+    #[clap(long, default_value_t = false)]
+    require_no_unsafe: bool,
+
+    /// Allow `unsafe` code in the given file with `--require-no-unsafe`. The path is
+    /// relative to the workspace root and can be a glob pattern (e.g. `src/ffi/*.rs`).
+    /// Can be used multiple times.
+    #[clap(long, value_name = "FILE", value_parser = parse_glob_pattern)]
+    allow_unsafe_in: Vec<glob::Pattern>,
+
+    /// Abort the submit if the Rust files of the workspace contain no `#[test]` functions.
+    #[clap(long, default_value_t = false)]
+    require_tests: bool,
+
+    /// Abort the submit if the Rust files of the workspace contain fewer than the given
+    /// number of `#[test]` functions.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    min_tests: Option<u64>,
+
+    /// Warn about lines of Rust files that look like hardcoded credentials: AWS keys, JWT
+    /// tokens, long token-like strings and comments mentioning passwords, secrets or API keys.
+    #[clap(long, default_value_t = false)]
+    detect_secrets: bool,
+
+    /// Abort the submit if `--detect-secrets` finds a possible credential.
+    #[clap(long, default_value_t = false)]
+    abort_on_secrets: bool,
+
+    /// Ask Kelvin to build the uploaded submit with the toolchain of the server and report
+    /// the compiler output, before the submit is graded.
+    #[clap(long, default_value_t = false)]
+    remote_build_check: bool,
+
+    /// Store the HTTP request headers and the response headers of the upload to the given
+    /// file. The API token, headers passed with `--http-header` and other headers that look
+    /// like credentials are redacted.
+    #[clap(long)]
+    log_request: Option<PathBuf>,
+
+    /// Also store the response body (at most 10 KiB) in the file passed to `--log-request`.
+    #[clap(long, default_value_t = false, requires = "log_request")]
+    log_response_body: bool,
+
+    /// Build the binaries of the workspace with `cargo build --release` and store them
+    /// in `bin/` in the archive. If the build fails, the binaries are skipped.
+    #[clap(long, default_value_t = false)]
+    with_binary: bool,
+
+    /// Cargo profile used to build the binaries of `--with-binary`.
+    #[clap(long, default_value = "release", requires = "with_binary")]
+    with_binary_profile: String,
+
+    /// Do not submit if the binaries of `--with-binary` cannot be built.
+    #[clap(long, default_value_t = false, requires = "with_binary")]
+    require_binary: bool,
+
+    /// Warn about Rust and TOML files that are not valid UTF-8.
+    #[clap(long, default_value_t = false)]
+    verify_utf8: bool,
+
+    /// Do not submit if a Rust or TOML file is not valid UTF-8.
+    #[clap(long, default_value_t = false, requires = "verify_utf8")]
+    abort_on_non_utf8: bool,
+
+    /// Convert Rust and TOML files that are not valid UTF-8 from the given encoding
+    /// (e.g. `windows-1250`) to UTF-8 in the archive. The files in the workspace are not
+    /// modified.
+    #[clap(long, value_parser = parse_encoding)]
+    fix_utf8: Option<&'static encoding_rs::Encoding>,
+
+    /// Compression level of the files in the archive (from 1 to 9, 0 stores the files
+    /// without compression).
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(i64).range(0..=9)
+    )]
+    compression_level: Option<i64>,
+
+    /// Compress files with the given extension with a different level, in the
+    /// `<ext>=<level>` format, e.g. `png=0` for files that are already compressed.
+    /// Can be used multiple times.
+    #[clap(long, value_parser = parse_extension_level)]
+    compress_level_per_ext: Vec<(String, i64)>,
+
+    /// Align the data of each file in the archive to the given number of bytes by padding
+    /// the local headers, for graders that memory-map the entries of the archive. Entries
+    /// copied by `--append-to-archive` are not aligned.
+    #[clap(
+        long,
+        value_name = "BYTES",
+        value_parser = clap::value_parser!(u16).range(2..)
+    )]
+    zip_align: Option<u16>,
+
+    /// Only upload `Cargo.toml` and `Cargo.lock` files and a list of the other files,
+    /// e.g. to let Kelvin check the dependencies before uploading the full submit.
+    #[clap(long, default_value_t = false)]
+    metadata_only: bool,
+
+    /// Send an additional HTTP header with the submit, in the `<name>:<value>` format.
+    /// Can be used multiple times.
+    #[clap(long, value_parser = parse_http_header)]
+    http_header: Vec<(HeaderName, HeaderValue)>,
+
+    /// Allow `--http-header` to override authentication headers, e.g. `Authorization`.
+    #[clap(long, default_value_t = false)]
+    allow_override_auth: bool,
+
+    /// Follow at most 5 redirects of the server, for deployments behind SSO. If the server
+    /// redirects to a login page, the submit fails with an error, because the token
+    /// has most likely expired.
+    #[clap(long, default_value_t = false)]
+    pub accept_response_redirects: bool,
+
+    /// HTTP version used for the upload. HTTP/2 is used with prior knowledge, without
+    /// negotiating it with the server. Useful to find out if problems with a proxy or a load
+    /// balancer are specific to an HTTP version.
+    #[clap(long, value_enum, default_value = "1.1")]
+    pub http_version: HttpVersion,
+
+    /// Use the endpoints of the given version of the Kelvin API (`/api/v<N>/`). Without it,
+    /// the unversioned endpoints (`/api/`) are used, which serve the latest version.
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u32).range(MIN_API_VERSION as i64..=MAX_API_VERSION as i64)
+    )]
+    pub kelvin_api_version: Option<u32>,
+
+    /// Name of the tool that creates the submit, sent in the `User-Agent` header and stored
+    /// in the submit history. Editor extensions or CI systems that use `cargo kelvin` should
+    /// set their own name (e.g. `vscode`).
+    #[clap(long, default_value = DEFAULT_AGENT, value_parser = parse_kelvin_agent)]
+    pub kelvin_agent: String,
+
+    /// Accept only the server certificate with the given SHA-256 fingerprint (in hex, colons
+    /// are allowed), e.g. a self-signed certificate of a Kelvin instance in a university
+    /// network. Other certificates are rejected, even if they are signed by a trusted
+    /// authority.
+    #[clap(long, value_parser = parse_certificate_fingerprint)]
+    pub server_certificate_fingerprint: Option<[u8; 32]>,
+
+    /// ID of a distributed trace (a UUID) sent with all requests in the `X-B3-TraceId` and
+    /// `traceparent` headers, so that the requests can be found in the traces of the server.
+    /// The ID is printed, so that it can be included in bug reports.
+    #[clap(long, value_name = "UUID", value_parser = parse_trace_id)]
+    pub kelvin_trace_id: Option<Uuid>,
+
+    /// Generate a random ID for `--kelvin-trace-id`.
+    #[clap(long, default_value_t = false, conflicts_with = "kelvin_trace_id")]
+    pub auto_trace_id: bool,
+
+    /// Format of log messages. `json` writes one JSON object per line (with `timestamp`,
+    /// `level`, `message` and `module`), e.g. for log aggregation in CI. The format of the
+    /// result of the submit is set by `--output-format`.
+    #[clap(long, value_enum, default_value_t = LogFormat::Text)]
+    pub log_format: LogFormat,
+
+    /// Panic instead of exiting with an error, so that integration tests can check error
+    /// paths with `#[should_panic]`. Only available with the `testing` feature.
+    #[cfg(feature = "testing")]
+    #[clap(long, hide = true)]
+    pub panic_on_early_exit: bool,
+
+    /// Prefix log messages with emoji. They are not used if the terminal does not seem
+    /// to support them or if colors are disabled.
+    #[clap(long, env = "KELVIN_EMOJI", value_parser = clap::builder::BoolishValueParser::new())]
+    pub emoji: bool,
+
+    /// Write detailed logs of the HTTP libraries (connections, redirects, request and
+    /// response headers) into the file given by `--trace-file`, to debug problems with
+    /// proxies or firewalls.
+    #[clap(long, default_value_t = false)]
+    pub trace_http: bool,
+
+    /// File into which `--trace-http` writes the HTTP trace.
+    #[clap(long, default_value = "kelvin-http-trace.log", requires = "trace_http")]
+    pub trace_file: PathBuf,
+
+    /// Limit the upload speed to the given number of bytes per second.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    limit_upload_bandwidth: Option<u64>,
+
+    /// Limit the upload speed to the given number of kilobits per second.
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "limit_upload_bandwidth"
+    )]
+    limit_upload_bandwidth_kbps: Option<u64>,
+
+    /// Maximum time (in seconds) of the whole upload request, from sending the archive
+    /// until the response is received. 30 seconds by default.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    timeout: Option<u64>,
+
+    /// Fail if Kelvin does not respond within the given number of milliseconds after
+    /// the archive was uploaded. The upload itself is only limited by `--timeout`, which
+    /// also still applies to the whole request, so a response timeout longer than
+    /// `--timeout` has no effect.
+    #[clap(long, value_parser = clap::value_parser!(u64).range(1..))]
+    response_timeout: Option<u64>,
+
+    /// Upload the archive in multipart parts of the given number of bytes, if the assignment
+    /// supports it. Otherwise, the archive is uploaded as a single part.
+    #[clap(
+        long,
+        value_parser = clap::value_parser!(u64).range(1024..),
+        conflicts_with_all = ["batch", "split_by_crate"]
+    )]
+    chunk_size: Option<u64>,
+
+    /// Use the given boundary in the multipart form of the upload instead of a random one.
+    /// This is an advanced option for working around proxies or firewalls that only accept
+    /// specific boundaries. It can have at most 70 letters, digits or characters from
+    /// `'()+_,-./:=?`.
+    #[clap(long, value_parser = parse_multipart_boundary)]
+    multipart_boundary: Option<String>,
+
+    /// Also submit the same archive into the given assignments (comma-separated).
+    /// Waiting for grading and opening the browser is not supported in this mode.
+    #[clap(long, value_delimiter = ',')]
+    batch: Vec<u64>,
+
+    /// Write the ID of the created submit to the given file (`-` for stdout). With `--batch`
+    /// or `--split-by-crate`, one `<assignment_id>:<submit_id>` line is written for each
+    /// created submit.
+    #[clap(long, conflicts_with_all = ["dry_run", "dry_run_server"])]
+    submission_id_file: Option<PathBuf>,
+
+    /// How many submits of `--batch` should be uploaded concurrently (at most 8).
+    #[clap(long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..=8))]
+    parallel_archives: u64,
+
+    /// Do not submit if the last submit of the assignment was already graded successfully.
+    #[clap(long, default_value_t = false)]
+    abort_if_already_graded: bool,
+
+    /// With `--abort-if-already-graded`, only abort if the best score of previous submits
+    /// is at least the given percentage.
+    #[clap(
+        long,
+        requires = "abort_if_already_graded",
+        value_parser = clap::value_parser!(u8).range(0..=100)
+    )]
+    min_score_to_abort: Option<u8>,
+
+    /// Show the largest files in the archive and how much of its size they take.
+    #[clap(long, default_value_t = false)]
+    upload_stats: bool,
+
+    /// Show the compressed size of each top-level directory of the archive (files in its
+    /// root are shown as `<root>`), e.g. to find a directory that is unexpectedly large.
+    /// With `--output-format json`, the sizes are included as `size_by_directory`.
+    #[clap(long, default_value_t = false)]
+    size_breakdown: bool,
+
+    /// Normalize the formatting of `Cargo.toml` files (spacing and order of keys) in the
+    /// archive. The files in the workspace are not modified.
+    #[clap(long, default_value_t = false, overrides_with = "no_format_manifest")]
+    format_manifest: bool,
+
+    /// Store `Cargo.toml` files in the archive as they are (default).
+    #[clap(long, default_value_t = false, overrides_with = "format_manifest")]
+    no_format_manifest: bool,
+
+    /// Format all TOML files in the archive with `taplo`, the formatter used by
+    /// `taplo fmt`. The files in the workspace are not modified.
+    #[clap(long, default_value_t = false)]
+    format_toml: bool,
+
+    /// Remove comments (including doc comments) from Rust files in the archive.
+    /// The files in the workspace are not modified.
+    #[clap(long, default_value_t = false)]
+    exclude_comments: bool,
+
+    /// Replace the absolute path of the workspace with `<workspace>` in Rust files in the
+    /// archive, so that it does not reveal e.g. your username. The files in the workspace
+    /// are not modified.
+    #[clap(long, default_value_t = false)]
+    anonymize_paths: bool,
+
+    /// Before creating the archive, list items with `#[allow(dead_code)]`, which might
+    /// be unused code that could be removed.
+    #[clap(long, default_value_t = false)]
+    pre_compress_optimize: bool,
+
+    /// Remove imports that `cargo check` reports as unused from Rust files in the archive.
+    /// Only imports that are unused in all targets (including tests) are removed, so the
+    /// workspace has to compile. The files in the workspace are not modified.
+    #[clap(long, default_value_t = false)]
+    remove_unused_use: bool,
+
+    /// Compute a hash of all files in the archive and send it along with the submit
+    /// (it is also stored in the ZIP comment). You can use it to prove which version
+    /// of your code was submitted.
+    #[clap(long, default_value_t = false)]
+    workspace_hash: bool,
+
+    /// Encrypt the files in the archive with AES-256 using the given password. The password
+    /// is not sent to Kelvin, share it with the teacher in some other way. This is only
+    /// a basic obfuscation for connections that are not encrypted, not a real protection
+    /// of the submit.
+    #[clap(
+        long,
+        env = "KELVIN_ARCHIVE_PASSWORD",
+        hide_env_values = true,
+        conflicts_with = "measure_lines"
+    )]
+    zip_encryption: Option<String>,
+
+    /// Read the password for `--zip-encryption` from the given file.
+    #[clap(long, conflicts_with_all = ["zip_encryption", "measure_lines"])]
+    password_file: Option<PathBuf>,
+
+    /// Ask for the password for `--zip-encryption` without showing it, or read it from
+    /// the first line of stdin if it is not a terminal. Unlike `--zip-encryption`, the
+    /// password does not appear in the shell history or in the list of processes.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["zip_encryption", "password_file", "measure_lines"]
+    )]
+    zip_password_stdin: bool,
+
+    /// Show which files changed since your last submit of the assignment and ask whether
+    /// the submit should be uploaded.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["zip_encryption", "password_file", "zip_password_stdin"]
+    )]
+    compare_with_last: bool,
+
+    /// Compare the submit with the starter code of the assignment in the given ZIP archive.
+    /// A summary of added, removed and modified files is shown and stored in
+    /// `changes_from_starter.txt` in the archive.
+    #[clap(
+        long,
+        conflicts_with_all = [
+            "zip_encryption",
+            "password_file",
+            "zip_password_stdin",
+            "hash_filenames",
+            "reuse_last_archive",
+            "split_by_crate",
+        ]
+    )]
+    diff_from_starter: Option<PathBuf>,
+
+    /// Store a README with your username, the assignment, the submission time, the version
+    /// of `rustc` and the list of submitted files in the archive. If the workspace contains
+    /// `README.md`, the information is appended to a copy of it, otherwise it is stored in
+    /// `SUBMISSION_README.md`. The files in the workspace are not modified.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["reuse_last_archive", "split_by_crate"]
+    )]
+    append_readme: bool,
+
+    /// Write statistics about the archive (number and sizes of files, breakdown by extension,
+    /// the largest file, lines of Rust code and the compression ratio) to the given JSON file.
+    #[clap(long)]
+    workspace_stats_file: Option<PathBuf>,
+
+    /// Store the statistics of `--workspace-stats-file` in `workspace_stats.json`
+    /// in the archive. They describe the archive without this file.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["reuse_last_archive", "split_by_crate"]
+    )]
+    include_stats_in_archive: bool,
+
+    /// Generate a Makefile with `submit`, `dry-run`, `status` and `clean` targets for this
+    /// assignment and Kelvin instance at the given path, instead of submitting.
+    #[clap(long, conflicts_with = "split_by_crate")]
+    emit_makefile: Option<PathBuf>,
+
+    /// Send the given version in the `X-Cargo-Kelvin-Version` header of the upload request
+    /// instead of the real version of `cargo kelvin`, to test how Kelvin handles other
+    /// versions.
+    #[clap(long)]
+    force_version: Option<String>,
+
+    /// Upload the submit in two phases: first only `Cargo.toml` and `Cargo.lock` files, so
+    /// that Kelvin can check the dependencies, and then the full archive. If the server does
+    /// not support it, the full archive is uploaded directly.
+    #[clap(long, default_value_t = false)]
+    two_phase_upload: bool,
+
+    /// Replace your Kelvin username in comments and string literals of Rust files with
+    /// `<REDACTED>` and remove `authors` from Cargo manifests in the archive, for assignments
+    /// that are graded blindly. The files in the workspace are not modified.
+    #[clap(long, default_value_t = false)]
+    obfuscate_author: bool,
+
+    /// If the assignment requires a specific Rust edition, use it in all Cargo manifests
+    /// stored in the archive. The files in the workspace are not modified.
+    #[clap(long, default_value_t = false)]
+    auto_fix_edition: bool,
+
+    /// Fetch the solution template of the assignment, check that the code in `src` defines
+    /// the signatures that it requires and list them in a `// REQUIRED SIGNATURES` comment
+    /// at the beginning of `src/lib.rs` in the archive. The files in the workspace are
+    /// not modified.
+    #[clap(long, default_value_t = false, conflicts_with = "split_by_crate")]
+    inject_solution_template: bool,
+
+    /// Abort the submit if a signature required by the solution template is missing.
+    #[clap(long, default_value_t = false, requires = "inject_solution_template")]
+    strict_template: bool,
+
+    /// Warn if the toolchain pinned by `rust-toolchain.toml` differs from the toolchain
+    /// required by the assignment.
+    #[clap(long, default_value_t = false)]
+    warn_toolchain_mismatch: bool,
+
+    /// Do not submit if the toolchain pinned by `rust-toolchain.toml` differs from
+    /// the toolchain required by the assignment.
+    #[clap(long, default_value_t = false)]
+    abort_toolchain_mismatch: bool,
+
+    /// Do not submit if a package of the workspace has a path dependency on a crate outside
+    /// of the submitted directory, which would be missing from the archive.
+    #[clap(long, default_value_t = false)]
+    strict_workspace: bool,
+
+    /// Do not submit if a package of the workspace does not use the given Rust edition.
+    /// Can also be set by `required-edition = "2021"` in the `[submit]` table of
+    /// `.kelvin.toml`. Unlike `--auto-fix-edition`, the archive is not modified.
+    #[clap(long, value_name = "EDITION", value_parser = RUST_EDITIONS)]
+    enforce_edition: Option<String>,
+
+    /// Truncate paths in the archive that are longer than the given number of bytes,
+    /// 255 if no value is given (`--clip-filenames=N`). The extension is kept and a hash
+    /// of the original path is appended, so that the paths stay unique.
+    #[clap(
+        long,
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "255",
+        value_parser = clap::value_parser!(u64).range(32..)
+    )]
+    clip_filenames: Option<u64>,
+
+    /// Fail if a path in the archive is longer than the limit of `--clip-filenames`
+    /// (255 bytes by default), instead of truncating it.
+    #[clap(long, default_value_t = false)]
+    fail_on_long_filename: bool,
+
+    /// Replace the path of each file in the archive by a hash of the path (keeping its
+    /// extension). The original paths are stored in `filename_manifest.json`.
+    #[clap(long, default_value_t = false)]
+    hash_filenames: bool,
+
+    /// Seed for `--hash-filenames`. The same seed produces the same names, by default a
+    /// random seed is used.
+    #[clap(long, requires = "hash_filenames")]
+    hash_seed: Option<String>,
+
+    /// Submit each member of the workspace separately, into the assignment given by
+    /// `assignment_id` in its `[package.metadata.kelvin]` table. Members without it are
+    /// skipped. Waiting for grading and opening the browser is not supported in this mode.
+    #[clap(
+        long,
+        default_value_t = false,
+        conflicts_with_all = [
+            "assignment_id",
+            "scope",
+            "batch",
+            "two_phase_upload",
+            "checksum_algorithm",
+            "sign_with_key",
+            "benchmark",
+            "measure_lines",
+            "abort_if_already_graded",
+            "auto_fix_edition",
+            "warn_toolchain_mismatch",
+            "abort_toolchain_mismatch",
+        ]
+    )]
+    split_by_crate: bool,
+}
+
+/// Name of the file with the changes from the starter code stored in the archive.
+const STARTER_DIFF_FILE: &str = "changes_from_starter.txt";
+
+/// Headers that cannot be set by `--http-header` without `--allow-override-auth`.
+const AUTH_HEADERS: [HeaderName; 3] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE];
+
+fn parse_http_header(value: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = value
+        .split_once(':')
+        .ok_or_else(|| "the header must be in the `<name>:<value>` format".to_string())?;
+    let name = HeaderName::from_str(name.trim())
+        .map_err(|_| format!("`{}` is not a valid header name", name.trim()))?;
+    let value = HeaderValue::from_str(value.trim())
+        .map_err(|_| format!("`{}` is not a valid header value", value.trim()))?;
+    Ok((name, value))
+}
+
+fn parse_extension_level(value: &str) -> Result<(String, i64), String> {
+    let (extension, level) = value
+        .split_once('=')
+        .ok_or_else(|| "the level must be in the `<ext>=<level>` format".to_string())?;
+    let level = level
+        .trim()
+        .parse::<i64>()
+        .ok()
+        .filter(|level| (0..=9).contains(level))
+        .ok_or_else(|| format!("`{}` is not a compression level from 0 to 9", level.trim()))?;
+    let extension = extension.trim().trim_start_matches('.');
+    if extension.is_empty() {
+        return Err("the extension cannot be empty".to_string());
+    }
+    Ok((extension.to_string(), level))
+}
+
+fn parse_certificate_fingerprint(value: &str) -> Result<[u8; 32], String> {
+    let hex: String = value.chars().filter(|&c| c != ':').collect();
+    if hex.len() != 64 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("the fingerprint must be a SHA-256 hash with 64 hex digits".to_string());
+    }
+    let mut fingerprint = [0; 32];
+    for (byte, index) in fingerprint.iter_mut().zip((0..hex.len()).step_by(2)) {
+        *byte = u8::from_str_radix(&hex[index..index + 2], 16).expect("invalid hex digit");
+    }
+    Ok(fingerprint)
+}
+
+fn parse_glob_pattern(value: &str) -> Result<glob::Pattern, String> {
+    glob::Pattern::new(value).map_err(|error| error.to_string())
+}
+
+fn parse_trace_id(value: &str) -> Result<Uuid, String> {
+    let trace_id = Uuid::parse_str(value).map_err(|error| error.to_string())?;
+    if trace_id.is_nil() {
+        return Err("the trace ID must not be all zeros".to_string());
+    }
+    Ok(trace_id)
+}
+
+fn parse_kelvin_agent(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        return Err("the agent cannot be empty".to_string());
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && !"-_.".contains(c))
+    {
+        return Err(format!("`{c}` cannot be used in the name of an agent"));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_multipart_boundary(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.len() > 70 {
+        return Err("the boundary must have from 1 to 70 characters".to_string());
+    }
+    if let Some(c) = value
+        .chars()
+        .find(|&c| !c.is_ascii_alphanumeric() && !"'()+_,-./:=?".contains(c))
+    {
+        return Err(format!("`{c}` cannot be used in a multipart boundary"));
+    }
+    Ok(value.to_string())
+}
+
+fn parse_encoding(label: &str) -> Result<&'static encoding_rs::Encoding, String> {
+    encoding_rs::Encoding::for_label(label.as_bytes())
+        .ok_or_else(|| format!("`{label}` is not a known encoding"))
+}
+
+#[derive(serde::Serialize, Debug)]
+struct SubmitOutput {
+    submit_id: u64,
+    url: String,
+    task: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expected_score: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    actual_score: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    passed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    benchmark: Option<BenchmarkResult>,
+    #[serde(flatten, skip_serializing_if = "Option::is_none")]
+    lines: Option<LineCounts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    build_passed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    trace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    size_by_directory: Option<BTreeMap<String, u64>>,
+}
+
+pub fn run_submit(args: SubmitArgs, client: &KelvinClient) -> anyhow::Result<ExitCode> {
+    let progress = args
+        .progress_file
+        .clone()
+        .map(|path| Rc::new(ProgressFile::new(path)));
+    let result = submit_workspace(args, client, progress.clone());
+    if let Some(progress) = progress {
+        match result {
+            Ok(_) if progress.stage() == Some(Stage::Error) => {}
+            Ok(_) => progress.set_stage(Stage::Complete),
+            Err(_) => progress.set_stage(Stage::Error),
+        }
+    }
+    result
+}
+
+fn submit_workspace(
+    args: SubmitArgs,
+    client: &KelvinClient,
+    progress: Option<Rc<ProgressFile>>,
+) -> anyhow::Result<ExitCode> {
+    if !args.allow_override_auth {
+        if let Some((name, _)) = args
+            .http_header
+            .iter()
+            .find(|(name, _)| AUTH_HEADERS.contains(name))
+        {
+            anyhow::bail!(
+                "Header `{name}` is used for authentication, pass `--allow-override-auth` to override it"
+            );
+        }
+    }
+    if let (Some(trace_id), OutputFormat::Human) = (args.kelvin_trace_id, args.output_format) {
+        println!("Trace ID: {trace_id}");
+    }
+    if let Some(path) = &args.emit_makefile {
+        let assignment_id = args
+            .assignment_id
+            .expect("Assignment ID is required without `--split-by-crate`");
+        if path.exists() {
+            anyhow::bail!("{path:?} already exists, remove it first");
+        }
+        std::fs::write(
+            path,
+            makefile::generate_makefile(assignment_id, client.kelvin_url()),
+        )
+        .with_context(|| anyhow::anyhow!("Cannot write Makefile to {path:?}"))?;
+        log::info!(
+            "Generated {}, submit with `make -f {} submit`",
+            path.display(),
+            path.display()
+        );
+        return Ok(ExitCode::SUCCESS);
+    }
+    if !args.dry_run {
+        client.check_api_version();
+    }
+    // The assignment ID is only missing with `--split-by-crate`
+    let check_toolchain = args.warn_toolchain_mismatch || args.abort_toolchain_mismatch;
+    let mut required_edition = None;
+    let mut required_toolchain = None;
+    if let Some(assignment_id) = args.assignment_id {
+        if args.auto_fix_edition || check_toolchain {
+            let task = client.get_task_info(assignment_id)?;
+            required_edition = task.edition.filter(|_| args.auto_fix_edition);
+            required_toolchain = task.required_toolchain;
+        }
+        if args.auto_fix_edition && required_edition.is_none() {
+            log::info!("Assignment {assignment_id} does not require a specific Rust edition");
+        }
+        if args.check_crates {
+            check_allowed_crates(client, assignment_id, false)?;
+        }
+        if args.abort_if_already_graded {
+            check_previous_submits(client, assignment_id, args.min_score_to_abort)?;
+        }
+    }
+
+    let scope = match args.split_by_crate {
+        true => Some(SubmitScope::Workspace),
+        false => args.scope,
+    };
+    let manifest = get_manifest_path(scope)?;
+    let root = manifest.parent().expect("Manifest path has no parent");
+    let config = KelvinConfig::load(root)?;
+    if args.strict_workspace {
+        check_path_dependencies(&manifest)?;
+    }
+    if let Some(edition) = args
+        .enforce_edition
+        .as_deref()
+        .or(config.submit.required_edition.as_deref())
+    {
+        check_editions(&manifest, edition)?;
+    }
+    if check_toolchain {
+        check_toolchain_mismatch(
+            root,
+            required_toolchain.as_deref(),
+            args.abort_toolchain_mismatch,
+        )?;
+    }
+    let required_signatures = match (args.inject_solution_template, args.assignment_id) {
+        (true, Some(assignment_id)) => {
+            check_solution_template(client, assignment_id, root, args.strict_template)?
+        }
+        _ => vec![],
+    };
+    let snapshot = if args.workspace_snapshot {
+        Some(WorkspaceSnapshot::create(root, args.keep_snapshot)?)
+    } else {
+        None
+    };
+    if args.abort_on_todo || !args.abort_on_pattern.is_empty() {
+        let count =
+            todo_check::find_forbidden_patterns(root, args.abort_on_todo, &args.abort_on_pattern)?;
+        if count > 0 && !args.allow_todo {
+            anyhow::bail!(
+                "Found {count} unfinished or forbidden line{}, use `--allow-todo` to submit anyway",
+                if count == 1 { "" } else { "s" }
+            );
+        }
+    }
+    if args.require_no_unsafe || config.submit.require_no_unsafe {
+        let count = todo_check::find_unsafe_code(root, &args.allow_unsafe_in).len();
+        if count > 0 {
+            anyhow::bail!(
+                "Found `unsafe` code on {count} line{}, use `--allow-unsafe-in <file>` to allow it in specific files",
+                if count == 1 { "" } else { "s" }
+            );
+        }
+    }
+    if let Some(min_tests) = args.min_tests.or(args.require_tests.then_some(1)) {
+        let tests = todo_check::count_tests(root) as u64;
+        if tests == 0 {
+            anyhow::bail!("No test functions found. Are you sure you've written tests?");
+        }
+        if tests < min_tests {
+            anyhow::bail!(
+                "Found {tests} test function{}, but at least {min_tests} are required",
+                if tests == 1 { "" } else { "s" }
+            );
+        }
+        log::info!(
+            "Found {tests} test function{}",
+            if tests == 1 { "" } else { "s" }
+        );
+    }
+    if args.detect_secrets || args.abort_on_secrets {
+        let secrets = secrets::find_secrets(root);
+        for secret in &secrets {
+            log::warn!(
+                "{}:{}: possible {}",
+                secret.path.display(),
+                secret.line,
+                secret.kind
+            );
+        }
+        if !secrets.is_empty() && args.abort_on_secrets {
+            anyhow::bail!(
+                "Found {} possible secret{}, remove {} before submitting",
+                secrets.len(),
+                if secrets.len() == 1 { "" } else { "s" },
+                if secrets.len() == 1 { "it" } else { "them" },
+            );
+        }
+    }
+    if args.pre_compress_optimize {
+        let lines = todo_check::find_dead_code_allows(root);
+        if !lines.is_empty() {
+            log::info!(
+                "Found {} item{} marked with `#[allow(dead_code)]`, consider removing {} if {} unused:",
+                lines.len(),
+                if lines.len() == 1 { "" } else { "s" },
+                if lines.len() == 1 { "it" } else { "them" },
+                if lines.len() == 1 { "it is" } else { "they are" },
+            );
+            for line in &lines {
+                log::info!("  {}:{}: {}", line.path.display(), line.line, line.content);
+            }
+        }
+    }
+    let lockfile = root.join("Cargo.lock");
+    if args.require_lock && !lockfile.is_file() {
+        anyhow::bail!(
+            "`Cargo.lock` was not found in {root:?}, generate it with `cargo generate-lockfile`"
+        );
+    }
+
+    let encryption_password = match &args.password_file {
+        Some(path) => Some(
+            std::fs::read_to_string(path)
+                .with_context(|| anyhow::anyhow!("Cannot read password from {path:?}"))?
+                .trim_end_matches(['\r', '\n'])
+                .to_string(),
+        ),
+        None if args.zip_password_stdin => Some(read_password("Archive password: ")?),
+        None => args.zip_encryption.clone(),
+    };
+    if encryption_password.as_deref() == Some("") {
+        anyhow::bail!("The password for the archive encryption must not be empty");
+    }
+    if encryption_password
+        .as_deref()
+        .is_some_and(|password| password.contains('\0'))
+    {
+        anyhow::bail!("The password for the archive encryption must not contain NUL bytes");
+    }
+    if encryption_password.is_some() {
+        log::warn!(
+            "The archive is encrypted only as a basic obfuscation, it does not make the submit secure"
+        );
+    }
+
+    let mut options = ArchiveOptions {
+        strip_prefix: args.strip_prefix.clone(),
+        add_prefix: args.add_prefix.clone(),
+        flatten: args.flatten,
+        exclude_tests: args.exclude_tests && !args.include_tests,
+        extra_files: config.submit.extra_files,
+        excluded_paths: vec![
+            PathBuf::from(ENV_FILE),
+            PathBuf::from(BUILD_METADATA_FILE),
+            PathBuf::from(FILE_LIST),
+            PathBuf::from(FILENAME_MANIFEST),
+            PathBuf::from(HISTORY_FILE),
+            PathBuf::from(GIT_LOG_FILE),
+            PathBuf::from(GIT_DIFF_FILE),
+            PathBuf::from(CARGO_TREE_FILE),
+            PathBuf::from(CARGO_TREE_JSON_FILE),
+            PathBuf::from(CARGO_METADATA_FILE),
+            PathBuf::from(CARGO_VERIFY_FILE),
+            PathBuf::from(STARTER_DIFF_FILE),
+            PathBuf::from(SUBMISSION_README_FILE),
+            PathBuf::from(WORKSPACE_STATS_FILE),
+        ],
+        excluded_submodules: vec![],
+        macos_metadata: args.macos_metadata,
+        generated_files: vec![],
+        vendored_files: None,
+        binaries: vec![],
+        changed_since: args
+            .limit_to_changed_since
+            .map(|duration| {
+                SystemTime::now().checked_sub(duration).with_context(|| {
+                    anyhow::anyhow!(
+                        "`--limit-to-changed-since {}` reaches too far into the past",
+                        humantime::format_duration(duration)
+                    )
+                })
+            })
+            .transpose()?,
+        append_to_archive: args.append_to_archive.clone(),
+        metadata_only: args.metadata_only,
+        format_manifests: args.format_manifest && !args.no_format_manifest,
+        format_toml: args.format_toml,
+        strip_comments: args.exclude_comments,
+        verify_utf8: args.verify_utf8,
+        abort_on_non_utf8: args.abort_on_non_utf8,
+        fix_utf8: args.fix_utf8,
+        unused_imports: if args.remove_unused_use {
+            Some(unused_use::find_unused_imports(root)?)
+        } else {
+            None
+        },
+        anonymized_root: args
+            .anonymize_paths
+            .then(|| root.to_string_lossy().into_owned()),
+        strip_debug_symbols: args.strip_debug_symbols,
+        workspace_hash: args.workspace_hash,
+        encryption_password,
+        compression_level: args.compression_level,
+        extension_levels: args.compress_level_per_ext.iter().cloned().collect(),
+        zip_alignment: args.zip_align,
+        redacted_author: if args.obfuscate_author {
+            Some(client.get_user_info()?.username)
+        } else {
+            None
+        },
+        hash_seed: args
+            .hash_filenames
+            .then(|| args.hash_seed.clone().unwrap_or_else(random_seed)),
+        clip_paths: args
+            .clip_filenames
+            .filter(|_| !args.fail_on_long_filename)
+            .map(|length| length as usize),
+        max_path_length: args
+            .fail_on_long_filename
+            .then(|| args.clip_filenames.unwrap_or(255) as usize),
+        root_only: args.include_workspace_root_only,
+        src_only: args.include_src_only,
+        include_examples: args.include_examples,
+        include_dotenv: args.include_dotenv,
+        include_ignored: args.include_ignored,
+        fail_fast_archive: args.fail_fast_archive,
+        fail_fast_walk: args.fail_fast_walk,
+        delta_base: None,
+        feature_sources: if args.filter_by_cargo_features.is_empty() {
+            None
+        } else {
+            Some(features::sources_used_with_features(
+                root,
+                &args.filter_by_cargo_features,
+            )?)
+        },
+        exclude_examples: args.exclude_examples,
+        proc_macro_paths: if args.include_proc_macros {
+            find_proc_macro_paths(&manifest)?
+        } else {
+            vec![]
+        },
+        required_signatures,
+        required_edition,
+        progress,
+        max_concurrent_file_reads: args.max_concurrent_file_reads as usize,
+    };
+    if args.exclude_comments {
+        log::warn!(
+            "`--exclude-comments` also removes doc comments, including `//!` module documentation"
+        );
+    }
+    if args.no_lock {
+        log::info!("`Cargo.lock` will not be included in the archive because of `--no-lock`");
+        options.excluded_paths.push(PathBuf::from("Cargo.lock"));
+    }
+    if args.no_manifest {
+        log::warn!(
+            "`Cargo.toml` and `Cargo.lock` will not be included in the archive because of `--no-manifest`. The submit will not compile unless the grader provides its own manifest."
+        );
+        options
+            .excluded_paths
+            .extend([PathBuf::from("Cargo.toml"), PathBuf::from("Cargo.lock")]);
+    }
+    let submodules = submodules::git_submodules(root)?;
+    for path in &submodules {
+        log::info!("Found git submodule at {path:?}");
+    }
+    if !submodules.is_empty() {
+        let count = submodules.len();
+        let plural = if count == 1 { "" } else { "s" };
+        if args.include_git_submodules && !args.ignore_git_submodules {
+            log::info!(
+                "Including {count} git submodule{plural} because of `--include-git-submodules`"
+            );
+        } else {
+            log::warn!(
+                "Skipping {count} git submodule{plural}, use `--include-git-submodules` to include {}",
+                if count == 1 { "it" } else { "them" }
+            );
+            options.excluded_submodules = submodules;
+        }
+    }
+    if args.include_cargo_config {
+        let path = root.join(".cargo").join("config.toml");
+        if path.is_file() {
+            log::info!("Including `.cargo/config.toml` in the archive");
+            options.extra_files.push(ExtraFile {
+                src: ".cargo/config.toml".to_string(),
+                dest: None,
+                strip_workspace_prefix: false,
+            });
+        } else {
+            log::warn!("Cannot include `.cargo/config.toml`, {path:?} does not exist");
+        }
+    }
+    if args.record_env {
+        options.generated_files.push(GeneratedFile {
+            path: PathBuf::from(ENV_FILE),
+            content: environment::record_environment().into_bytes(),
+        });
+    }
+    if args.compress_only_changed {
+        let assignment_id = args
+            .assignment_id
+            .expect("Assignment ID is required without `--split-by-crate`");
+        if client.get_task_info(assignment_id)?.supports_delta_submit {
+            options.excluded_paths.push(PathBuf::from(DELTA_MANIFEST));
+            let dir = cache::content_hashes_dir(args.content_hash_cache_dir.as_deref())?;
+            options.delta_base = Some(cache::load_content_hashes(
+                &dir,
+                assignment_id,
+                args.content_hash_max_age,
+            ));
+        } else {
+            log::warn!("The assignment does not support delta submits, all files are submitted");
+        }
+    }
+    if args.inject_build_metadata {
+        options.generated_files.push(GeneratedFile {
+            path: PathBuf::from(BUILD_METADATA_FILE),
+            content: environment::build_metadata(root, args.include_hostname).into_bytes(),
+        });
+    }
+    if let Some(count) = args.embed_git_log {
+        if let Some(log) = git_history::recent_commits(root, count as usize) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(GIT_LOG_FILE),
+                content: log.into_bytes(),
+            });
+        }
+    }
+    if args.embed_git_diff {
+        if let Some(diff) = git_history::uncommitted_changes(root) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(GIT_DIFF_FILE),
+                content: diff.into_bytes(),
+            });
+        }
+    }
+    if args.with_cargo_tree {
+        if let Some(tree) = cargo_tree::cargo_tree(root, args.with_dev_dep_tree) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(CARGO_TREE_FILE),
+                content: tree.into_bytes(),
+            });
+        }
+    }
+    if args.with_cargo_tree_json {
+        if let Some(tree) = cargo_tree::cargo_tree_json(root, args.with_dev_dep_tree) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(CARGO_TREE_JSON_FILE),
+                content: tree.into_bytes(),
+            });
+        }
+    }
+    if args.with_lock_provenance {
+        if let Some(metadata) = lock_provenance::locked_metadata(root) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(CARGO_METADATA_FILE),
+                content: metadata.into_bytes(),
+            });
+        }
+        if let Some(verification) = lock_provenance::verify_project(root) {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(CARGO_VERIFY_FILE),
+                content: verification.into_bytes(),
+            });
+        }
+    }
+    if args.with_test_report {
+        let report = test_report::run_tests(root)?;
+        if !report.passed {
+            if args.abort_on_test_failure {
+                anyhow::bail!("Tests have failed, the submit was not uploaded");
+            }
+            log::warn!("Tests have failed, the test report is stored in the archive anyway");
+        }
+        options.excluded_paths.push(args.test_report_name.clone());
+        options.generated_files.push(GeneratedFile {
+            path: args.test_report_name.clone(),
+            content: report.content.into_bytes(),
+        });
+    }
+    if args.with_test_vectors {
+        for (path, content) in test_vectors::collect_test_vectors(root)? {
+            options.generated_files.push(GeneratedFile {
+                path: Path::new(TEST_VECTORS_DIR).join(path),
+                content,
+            });
+        }
+    }
+    if args.with_binary {
+        match binary::build_binaries(root, &args.with_binary_profile) {
+            Ok(binaries) => options.binaries = binaries,
+            Err(error) if args.require_binary => return Err(error),
+            Err(error) => log::warn!("Skipping binaries: {error:?}"),
+        }
+    }
+    if args.report_uncovered {
+        match coverage::measure_coverage(root)? {
+            Some(report) => {
+                let percent = report.percent();
+                log::info!(
+                    "Coverage: {percent:.1}% ({}/{} lines)",
+                    report.covered_lines,
+                    report.total_lines
+                );
+                if let Some(min_coverage) = args.min_coverage.filter(|&min| percent < min) {
+                    anyhow::bail!(
+                        "The coverage {percent:.1}% is below {min_coverage}%, the submit was not uploaded"
+                    );
+                }
+                options
+                    .excluded_paths
+                    .push(PathBuf::from(COVERAGE_REPORT_FILE));
+                options.generated_files.push(GeneratedFile {
+                    path: PathBuf::from(COVERAGE_REPORT_FILE),
+                    content: report.content.into_bytes(),
+                });
+            }
+            None => log::warn!(
+                "Cannot measure coverage, install `cargo-llvm-cov` or `cargo-tarpaulin`. The coverage report is skipped."
+            ),
+        }
+    }
+    let mut sccache_hit_rate = None;
+    if args.with_sccache_stats {
+        match sccache::sccache_stats()? {
+            Some(stats) => {
+                sccache_hit_rate = stats.hit_rate();
+                options
+                    .excluded_paths
+                    .push(PathBuf::from(SCCACHE_STATS_FILE));
+                options.generated_files.push(GeneratedFile {
+                    path: PathBuf::from(SCCACHE_STATS_FILE),
+                    content: stats.content.into_bytes(),
+                });
+            }
+            None => log::info!("`sccache` is not installed, its statistics are not included"),
+        }
+    }
+    if args.with_flamegraph {
+        let run = ProfiledRun {
+            binary: args.flamegraph_bin.as_deref(),
+            args: args
+                .flamegraph_args
+                .as_deref()
+                .map(|args| args.split_whitespace().collect())
+                .unwrap_or_default(),
+            input: args.flamegraph_input_data.as_deref(),
+        };
+        if let Some(flamegraph) = flamegraph::record_flamegraph(root, &run)? {
+            options.excluded_paths.push(PathBuf::from(FLAMEGRAPH_FILE));
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(FLAMEGRAPH_FILE),
+                content: flamegraph,
+            });
+        }
+    }
+    if args.with_dependencies_audit {
+        let audit = audit::run_audit(root)?;
+        for vulnerability in &audit.vulnerabilities {
+            log::warn!(
+                "{} {}: {} ({}{})",
+                vulnerability.package,
+                vulnerability.version,
+                vulnerability.title,
+                vulnerability.id,
+                vulnerability
+                    .score
+                    .map(|score| format!(", CVSS {score}"))
+                    .unwrap_or_default()
+            );
+        }
+        let critical = audit.critical().count();
+        if critical > 0 {
+            if args.abort_on_vulnerability {
+                anyhow::bail!(
+                    "`cargo audit` has found {critical} critical vulnerabilit{}, the submit was not uploaded",
+                    if critical == 1 { "y" } else { "ies" }
+                );
+            }
+            log::warn!(
+                "`cargo audit` has found {critical} critical vulnerabilit{}, the audit is stored in the archive anyway",
+                if critical == 1 { "y" } else { "ies" }
+            );
+        }
+        options
+            .excluded_paths
+            .push(PathBuf::from(SECURITY_AUDIT_FILE));
+        options.generated_files.push(GeneratedFile {
+            path: PathBuf::from(SECURITY_AUDIT_FILE),
+            content: audit.content.into_bytes(),
+        });
+    }
+    let _vendored = if args.include_cargo_home_vendored {
+        let vendored = VendoredDependencies::create(root)?;
+        options.vendored_files = Some(vendored.files()?);
+        options.generated_files.push(GeneratedFile {
+            path: PathBuf::from(".cargo/config.toml"),
+            content: vendor::VENDOR_CARGO_CONFIG.as_bytes().to_vec(),
+        });
+        Some(vendored)
+    } else {
+        None
+    };
+
+    let mut headers = HeaderMap::new();
+    if args.split_by_crate {
+        let upload_options = create_upload_options(&args, headers)?;
+        let rate_limiter = RateLimiter::new(args.rate_limit, cache_dir()?.join("last_submit"));
+        if let Some(limiter) = rate_limiter.as_ref().filter(|_| !args.dry_run) {
+            limiter.wait();
+        }
+        let results = submit_split_by_crate(
+            client,
+            &options,
+            &upload_options,
+            snapshot.as_ref(),
+            args.check_crates,
+            args.dry_run,
+        )?;
+        if args.dry_run {
+            log::info!("Dry run, the submits were not uploaded");
+            return Ok(ExitCode::SUCCESS);
+        }
+        if let Some(limiter) = &rate_limiter {
+            if let Err(error) = limiter.record_submit() {
+                log::warn!("{error:?}");
+            }
+        }
+        record_batch_submits(root, &results, client.agent_name());
+        if let Some(path) = &args.submission_id_file {
+            write_batch_submission_ids(path, &results)?;
+        }
+        return print_batch_results(&results, args.output_format);
+    }
+    let assignment_id = args
+        .assignment_id
+        .expect("Assignment ID is required without `--split-by-crate`");
+
+    let (archive, mut benchmark) = if args.reuse_last_archive {
+        (history::load_last_archive(root)?, None)
+    } else if args.benchmark {
+        let (archive, result) =
+            benchmark::benchmark_compression(|| compress_workspace(manifest.clone(), &options))?;
+        (archive, Some(result))
+    } else {
+        (compress_workspace(manifest.clone(), &options)?, None)
+    };
+    let archive = match &args.diff_from_starter {
+        Some(path) => {
+            let starter = std::fs::read(path)
+                .with_context(|| anyhow::anyhow!("Cannot read starter archive from {path:?}"))?;
+            let diff = archive_diff::diff_archives(&starter, &archive)
+                .context("cannot compare the submit with the starter code")?;
+            let (added, removed, modified) = diff.counts();
+            let summary = format!(
+                "Changes from the starter code: {added} added, {removed} removed, {modified} modified files"
+            );
+            log::info!("{summary}:");
+            diff.log();
+            let mut content = format!("{summary}\n");
+            for line in diff.lines() {
+                content.push_str(&line);
+                content.push('\n');
+            }
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(STARTER_DIFF_FILE),
+                content: content.into_bytes(),
+            });
+            compress_workspace(manifest.clone(), &options)?
+        }
+        None => archive,
+    };
+    let archive = if args.append_readme {
+        let info = SubmissionInfo {
+            username: client.get_user_info()?.username,
+            assignment: client.get_task_info(assignment_id)?.name,
+            rustc_version: toolchain::rustc_version(root),
+        };
+        let metadata = readme::submission_metadata(&info, &archive)?;
+        let (path, content) = readme::submission_readme(root, &metadata)?;
+        log::info!("Storing submission information in `{path}`");
+        options.excluded_paths.push(PathBuf::from(path));
+        options.generated_files.push(GeneratedFile {
+            path: PathBuf::from(path),
+            content: content.into_bytes(),
+        });
+        compress_workspace(manifest.clone(), &options)?
+    } else {
+        archive
+    };
+    let archive = if args.workspace_stats_file.is_some() || args.include_stats_in_archive {
+        let stats = stats::workspace_stats(&archive)?;
+        let content =
+            serde_json::to_string_pretty(&stats).context("cannot serialize workspace stats")?;
+        if let Some(path) = &args.workspace_stats_file {
+            std::fs::write(path, &content)
+                .with_context(|| anyhow::anyhow!("Cannot write workspace stats to {path:?}"))?;
+        }
+        if args.include_stats_in_archive {
+            options.generated_files.push(GeneratedFile {
+                path: PathBuf::from(WORKSPACE_STATS_FILE),
+                content: content.into_bytes(),
+            });
+            compress_workspace(manifest.clone(), &options)?
+        } else {
+            archive
+        }
+    } else {
+        archive
+    };
+    report_stage(&options, Stage::Compressed);
+    if let Some(hit_rate) = sccache_hit_rate {
+        log::info!("sccache hit rate: {hit_rate:.1}%");
+    }
+    if let Some(path) = &args.save_archive {
+        history::save_archive(root, path, &archive)?;
+    }
+    if args.upload_stats {
+        upload_stats::log_upload_stats(&archive)?;
+    }
+    let size_by_directory = if args.size_breakdown {
+        let sizes = upload_stats::size_by_directory(&archive)?;
+        if args.output_format == OutputFormat::Human {
+            upload_stats::print_size_breakdown(&sizes);
+        }
+        Some(sizes)
+    } else {
+        None
+    };
+    let lines = if args.measure_lines {
+        let lines = lines::count_lines(&archive)?;
+        if args.output_format == OutputFormat::Human {
+            lines.log();
+        }
+        Some(lines)
+    } else {
+        None
+    };
+
+    let manifest_archive = if args.two_phase_upload {
+        options.metadata_only = true;
+        let archive = compress_workspace(manifest.clone(), &options)?;
+        options.metadata_only = false;
+        Some(archive)
+    } else {
+        None
+    };
+    // The archive is in memory from now on, so later edits cannot get into the submit
+    if let Some(snapshot) = &snapshot {
+        snapshot.verify_unchanged()?;
+    }
+
+    if let Some(hash) = args
+        .workspace_hash
+        .then(|| read_workspace_hash(&archive))
+        .flatten()
+    {
+        headers.insert(
+            "X-Cargo-Kelvin-Workspace-Hash",
+            HeaderValue::from_str(&hash).expect("Hash is a valid header value"),
+        );
+    }
+    if args.metadata_only {
+        headers.insert(
+            "X-Cargo-Kelvin-Metadata-Only",
+            HeaderValue::from_static("true"),
+        );
+    }
+    if let Some(algorithm) = args.checksum_algorithm {
+        let checksum = format!(
+            "{}:{}",
+            algorithm.name(),
+            checksum::compute_checksum(algorithm, &archive)
+        );
+        log::info!("Archive checksum: {checksum}");
+        headers.insert(
+            "X-Cargo-Kelvin-Checksum",
+            HeaderValue::from_str(&checksum).expect("Checksum is a valid header value"),
+        );
+    }
+    if let Some(key_path) = &args.sign_with_key {
+        let signature = signing::sign_archive(key_path, &archive)?;
+        if args.dry_run {
+            println!("{}", to_hex(&signature.signature));
+        }
+        headers.insert(
+            "X-Cargo-Kelvin-Signature",
+            HeaderValue::from_str(&BASE64_STANDARD.encode(&signature.signature))
+                .expect("Base64 is a valid header value"),
+        );
+        // Header values cannot contain line breaks
+        let public_key = signature
+            .public_key_pem
+            .lines()
+            .collect::<Vec<_>>()
+            .join(" ");
+        headers.insert(
+            "X-Cargo-Kelvin-Public-Key",
+            HeaderValue::from_str(&public_key).context("invalid public key")?,
+        );
+    }
+
+    if args.compare_with_last && !compare_with_last_submit(client, assignment_id, &archive)? {
+        log::info!("The submit was not uploaded");
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if args.dry_run {
+        log::info!("Dry run, the submit was not uploaded");
+        if let Some(benchmark) = &benchmark {
+            match args.output_format {
+                OutputFormat::Human => benchmark.log(),
+                OutputFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string(benchmark).context("serializing benchmark")?
+                ),
+            }
+        }
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut upload_options = create_upload_options(&args, headers)?;
+    if let Some(chunk_size) = args.chunk_size {
+        if client.get_task_info(assignment_id)?.supports_chunked_upload {
+            let chunks = archive.len().div_ceil(chunk_size as usize);
+            log::info!(
+                "Uploading the archive in {chunks} chunk{}",
+                if chunks == 1 { "" } else { "s" }
+            );
+            upload_options.chunk_size = Some(chunk_size as usize);
+        } else {
+            log::warn!(
+                "Assignment {assignment_id} does not support chunked uploads, uploading the archive as a single part"
+            );
+        }
+    }
+    if args.dry_run_server {
+        return validate_on_server(client, assignment_id, &archive, &upload_options, &args);
+    }
+    if let Some(max_attempts) = args.max_submit_attempts {
+        for &assignment_id in std::iter::once(&assignment_id).chain(&args.batch) {
+            check_submit_attempts(root, assignment_id, max_attempts, args.time_window)?;
+        }
+    }
+    let rate_limiter = RateLimiter::new(args.rate_limit, cache_dir()?.join("last_submit"));
+    if let Some(limiter) = &rate_limiter {
+        limiter.wait();
+    }
+    if !args.batch.is_empty() {
+        let mut assignment_ids = vec![assignment_id];
+        assignment_ids.extend(&args.batch);
+        report_stage(&options, Stage::Uploading);
+        let results = submit_batch(
+            client,
+            &assignment_ids,
+            &archive,
+            &upload_options,
+            args.parallel_archives as usize,
+        );
+        report_stage(&options, Stage::Uploaded);
+        if let Some(limiter) = &rate_limiter {
+            if let Err(error) = limiter.record_submit() {
+                log::warn!("{error:?}");
+            }
+        }
+        record_batch_submits(root, &results, client.agent_name());
+        if let Some(path) = &args.submission_id_file {
+            write_batch_submission_ids(path, &results)?;
+        }
+        return print_batch_results(&results, args.output_format);
+    }
+
+    log::info!("{}Uploading the submit to Kelvin", Emoji::Upload.prefix());
+    report_stage(&options, Stage::Uploading);
+    let upload_start = Instant::now();
+    let result = match &manifest_archive {
+        Some(manifest_archive) => submit_two_phase(
+            client,
+            assignment_id,
+            manifest_archive,
+            &archive,
+            &upload_options,
+        ),
+        None => client.submit(assignment_id, &archive, &upload_options),
+    };
+    let response = match result {
+        Ok(response) => response,
+        Err(error) => {
+            if args.notify_on_error {
+                notify_error(&format!("{error:#}"));
+            }
+            return Err(error);
+        }
+    };
+    if let Some(benchmark) = &mut benchmark {
+        benchmark.record_upload(upload_start.elapsed());
+        if args.output_format == OutputFormat::Human {
+            benchmark.log();
+        }
+    }
+    if let Some(limiter) = &rate_limiter {
+        if let Err(error) = limiter.record_submit() {
+            log::warn!("{error:?}");
+        }
+    }
+    let Some(response) = response else {
+        if args.notify_on_error {
+            notify_error("The submit was not accepted by Kelvin");
+        }
+        report_stage(&options, Stage::Error);
+        return Ok(ExitCode::FAILURE);
+    };
+    report_stage(&options, Stage::Uploaded);
+    if options.delta_base.is_some() {
+        let stored = read_delta_manifest(&archive).and_then(|manifest| match manifest {
+            Some(manifest) => {
+                let dir = cache::content_hashes_dir(args.content_hash_cache_dir.as_deref())?;
+                cache::store_content_hashes(&dir, assignment_id, &manifest)
+            }
+            None => Ok(()),
+        });
+        if let Err(error) = stored {
+            log::warn!("Cannot remember the submitted files: {error:?}");
+        }
+    }
+    if let Err(error) = history::record_submits(
+        root,
+        &[(assignment_id, response.submit.id)],
+        client.agent_name(),
+    ) {
+        log::warn!("{error:?}");
+    }
+    log::info!(
+        "{}Created submit #{} for task {}",
+        Emoji::Success.prefix(),
+        response.submit.id,
+        response.task.name
+    );
+    log::info!(
+        "{}You can find the submit at {}",
+        Emoji::Link.prefix(),
+        response.submit.url
+    );
+    if let Some(path) = &args.submission_id_file {
+        write_submission_ids(path, &format!("{}\n", response.submit.id))?;
+    }
+
+    let mut output = SubmitOutput {
+        submit_id: response.submit.id,
+        url: response.submit.url.clone(),
+        task: response.task.name,
+        expected_score: args.expect_score,
+        actual_score: None,
+        passed: None,
+        benchmark,
+        lines,
+        build_passed: None,
+        trace_id: args.kelvin_trace_id.map(|trace_id| trace_id.to_string()),
+        size_by_directory,
+    };
+    let mut exit_code = ExitCode::SUCCESS;
+    if args.remote_build_check {
+        match client.check_build(response.submit.id)? {
+            Some(check) => {
+                if check.success {
+                    log::info!(
+                        "{}The submit was built successfully on the server",
+                        Emoji::Success.prefix()
+                    );
+                } else {
+                    log::error!("The submit cannot be built on the server");
+                    exit_code = ExitCode::FAILURE;
+                }
+                if !check.output.trim().is_empty() {
+                    eprintln!("{}", check.output.trim_end());
+                }
+                output.build_passed = Some(check.success);
+            }
+            None => log::warn!("Kelvin does not support build checks, skipping it"),
+        }
+    }
+    let needs_grading = args.expect_score.is_some() || args.notify_desktop;
+    let status = if needs_grading && args.no_poll {
+        log::warn!("Not waiting for the submit to be graded, because `--no-poll` was used");
+        None
+    } else if needs_grading {
+        Some(wait_for_grading(
+            client,
+            response.submit.id,
+            Duration::from_secs(args.score_poll_interval),
+            Duration::from_secs(args.score_poll_timeout),
+        )?)
+    } else {
+        None
+    };
+    if let (Some(expected_score), Some(status)) = (args.expect_score, &status) {
+        match status.score {
+            Some(score) => {
+                let passed = score >= f64::from(expected_score);
+                if passed {
+                    log::info!(
+                        "{}The submit was graded with score {score}%",
+                        Emoji::Success.prefix()
+                    );
+                } else {
+                    log::error!(
+                        "The submit was graded with score {score}%, which is below the expected score {expected_score}%"
+                    );
+                    exit_code = ExitCode::from(2);
+                }
+                output.actual_score = Some(score);
+                output.passed = Some(passed);
+            }
+            None => {
+                log::error!(
+                    "The submit was graded, but Kelvin did not report its score, so it cannot be compared with the expected score {expected_score}%"
+                );
+                exit_code = ExitCode::FAILURE;
+                output.passed = Some(false);
+            }
+        }
+    }
+    if let (true, Some(status)) = (args.notify_desktop, &status) {
+        if let Err(error) =
+            notification::notify_graded(&output.task, status.score, &response.submit.url)
+        {
+            log::warn!("Cannot show desktop notification: {error:?}");
+        }
+    }
+
+    if args.output_format == OutputFormat::Json {
+        println!(
+            "{}",
+            serde_json::to_string(&output).context("serializing submit output")?
+        );
+    }
+    if !args.no_open {
+        open::that(response.submit.url).context("opening browser")?;
+    }
+    if args.check_updates && !args.no_update_check {
+        update::check_for_updates_periodically();
+    }
+
+    Ok(exit_code)
+}
+
+/// Uploads the manifests first and then the full archive, or only the full archive if the
+/// server does not support two-phase submits.
+fn submit_two_phase(
+    client: &KelvinClient,
+    assignment_id: u64,
+    manifest_archive: &[u8],
+    archive: &[u8],
+    options: &UploadOptions,
+) -> anyhow::Result<Option<Response>> {
+    match client.phase1_submit(assignment_id, manifest_archive, options)? {
+        Some(token) => {
+            log::info!("The dependencies of the submit were approved");
+            client.phase2_submit(assignment_id, archive, &token, options)
+        }
+        None => {
+            log::warn!("Kelvin does not support two-phase submits, uploading the full archive");
+            client.submit(assignment_id, archive, options)
+        }
+    }
+}
+
+/// Finds build scripts and directories of proc-macro crates in the workspace, relative to
+/// its root, and warns about the proc-macro crates.
+fn find_proc_macro_paths(manifest: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    let root = manifest.parent().expect("Manifest path has no parent");
+    let root = root
+        .canonicalize()
+        .with_context(|| anyhow::anyhow!("Cannot resolve {root:?}"))?;
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest)
+        .no_deps()
+        .exec()
+        .context("getting cargo metadata")?;
+    let mut paths = Vec::new();
+    for package in metadata.workspace_packages() {
+        for target in &package.targets {
+            let path = if target.kind.iter().any(|kind| kind == "proc-macro") {
+                log::warn!(
+                    "Package `{}` is a proc-macro crate, make sure that the grader supports proc-macros",
+                    package.name
+                );
+                package.manifest_path.parent().map(|dir| dir.as_std_path())
+            } else if target.is_custom_build() {
+                Some(target.src_path.as_std_path())
+            } else {
+                None
+            };
+            if let Some(path) = path.and_then(|path| path.strip_prefix(&root).ok()) {
+                paths.push(path.to_path_buf());
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Checks that path dependencies of the workspace packages do not point outside of
+/// the directory that is submitted.
+fn check_path_dependencies(manifest: &Path) -> anyhow::Result<()> {
+    let root = manifest.parent().expect("Manifest path has no parent");
+    let root = root
+        .canonicalize()
+        .with_context(|| anyhow::anyhow!("Cannot resolve {root:?}"))?;
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest)
+        .no_deps()
+        .exec()
+        .context("getting cargo metadata")?;
+    let mut outside = 0;
+    for package in metadata.workspace_packages() {
+        for dependency in &package.dependencies {
+            let Some(path) = &dependency.path else {
+                continue;
+            };
+            let path = path.as_std_path();
+            let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+            if !path.starts_with(&root) {
+                log::error!(
+                    "Package `{}` depends on `{}` at {}, which is outside of the workspace and will not be submitted",
+                    package.name,
+                    dependency.name,
+                    path.display()
+                );
+                outside += 1;
+            }
+        }
+    }
+    if outside > 0 {
+        anyhow::bail!(
+            "Found {outside} path dependenc{} outside of the workspace",
+            if outside == 1 { "y" } else { "ies" }
+        );
+    }
+    Ok(())
+}
+
+/// Checks that all packages of the workspace use the given edition.
+fn check_editions(manifest: &Path, edition: &str) -> anyhow::Result<()> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .manifest_path(manifest)
+        .no_deps()
+        .exec()
+        .context("getting cargo metadata")?;
+    let mut mismatched = 0;
+    for package in metadata.workspace_packages() {
+        if package.edition.as_str() != edition {
+            log::error!(
+                "Package `{}` uses edition {} but edition {edition} is required.",
+                package.name,
+                package.edition
+            );
+            mismatched += 1;
+        }
+    }
+    if mismatched > 0 {
+        anyhow::bail!(
+            "Found {mismatched} package{} that do{} not use edition {edition}",
+            if mismatched == 1 { "" } else { "s" },
+            if mismatched == 1 { "es" } else { "" }
+        );
+    }
+    Ok(())
+}
+
+/// Checks that the workspace defines the signatures required by the solution template of
+/// the assignment and returns them, so that they can be listed in the archive.
+fn check_solution_template(
+    client: &KelvinClient,
+    assignment_id: u64,
+    root: &Path,
+    strict: bool,
+) -> anyhow::Result<Vec<String>> {
+    let Some(template) = client.get_solution_template(assignment_id)? else {
+        log::warn!("Assignment {assignment_id} does not have a solution template");
+        return Ok(vec![]);
+    };
+    let required = solution_template::required_signatures(&template);
+    if !root.join("src").join("lib.rs").is_file() {
+        log::warn!("The workspace does not have `src/lib.rs`, the required signatures are not listed in the archive");
+    }
+    let missing = solution_template::missing_signatures(root, &required);
+    if missing.is_empty() {
+        log::info!(
+            "The workspace defines all {} signature{} required by the solution template",
+            required.len(),
+            if required.len() == 1 { "" } else { "s" }
+        );
+        return Ok(required);
+    }
+    for signature in &missing {
+        log::warn!("Missing required signature `{signature}`");
+    }
+    let message = format!(
+        "{} of {} signature{} required by the solution template {} missing",
+        missing.len(),
+        required.len(),
+        if required.len() == 1 { "" } else { "s" },
+        if missing.len() == 1 { "is" } else { "are" }
+    );
+    if strict {
+        anyhow::bail!("{message}");
+    }
+    log::warn!("{message}");
+    Ok(required)
+}
+
+/// Compares the toolchain pinned in the workspace with the toolchain required by the assignment.
+fn check_toolchain_mismatch(
+    root: &Path,
+    required: Option<&str>,
+    abort: bool,
+) -> anyhow::Result<()> {
+    let pinned = toolchain::pinned_toolchain(root)?;
+    log::info!(
+        "Submitting with toolchain {} ({})",
+        pinned.as_deref().unwrap_or("<not pinned>"),
+        toolchain::rustc_version(root).unwrap_or_else(|| "unknown rustc".to_string())
+    );
+    let Some(required) = required else {
+        log::info!("The assignment does not require a specific toolchain");
+        return Ok(());
+    };
+    let message = match pinned.as_deref() {
+        Some(pinned) if pinned == required => return Ok(()),
+        Some(pinned) => format!(
+            "The workspace uses toolchain `{pinned}`, but the assignment requires `{required}`"
+        ),
+        None => format!(
+            "The workspace does not pin a toolchain in `rust-toolchain.toml`, but the assignment requires `{required}`"
+        ),
+    };
+    if abort {
+        anyhow::bail!("{message}");
+    }
+    log::warn!("{message}");
+    Ok(())
+}
+
+/// Shows the differences between the archive and the last submit of the assignment and asks
+/// whether the submit should be uploaded.
+fn compare_with_last_submit(
+    client: &KelvinClient,
+    assignment_id: u64,
+    archive: &[u8],
+) -> anyhow::Result<bool> {
+    let Some(last) = client.get_submits(assignment_id)?.pop() else {
+        log::info!("There is no previous submit of assignment {assignment_id} to compare with");
+        return Ok(true);
+    };
+    let previous = client.get_submit_archive(last.id)?;
+    let diff = archive_diff::diff_archives(&previous, archive)?;
+    log::info!("Changes since submit #{}:", last.id);
+    diff.log();
+    confirm("Proceed with this submission?", true)
+}
+
+/// Fails if a previous submit of the assignment makes a new submit unnecessary.
+fn check_previous_submits(
+    client: &KelvinClient,
+    assignment_id: u64,
+    min_score: Option<u8>,
+) -> anyhow::Result<()> {
+    let submits = client.get_submits(assignment_id)?;
+    match min_score {
+        Some(min_score) => {
+            let best = submits
+                .iter()
+                .filter_map(|submit| submit.score)
+                .max_by(f64::total_cmp);
+            if let Some(best) = best.filter(|&best| best >= f64::from(min_score)) {
+                anyhow::bail!(
+                    "A previous submit was already graded with score {best}%, which is at least {min_score}%. Not submitting again."
+                );
+            }
+        }
+        None => {
+            if let Some(last) = submits.last() {
+                if last
+                    .status
+                    .as_deref()
+                    .is_some_and(|status| matches!(status, "accepted" | "passed" | "finished"))
+                {
+                    anyhow::bail!(
+                        "The last submit #{} was already graded{}. Not submitting again.",
+                        last.id,
+                        last.score
+                            .map(|score| format!(" with score {score}%"))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Uploads the archive with `--dry-run-server` and reports what the server found.
+fn validate_on_server(
+    client: &KelvinClient,
+    assignment_id: u64,
+    archive: &[u8],
+    options: &UploadOptions,
+    args: &SubmitArgs,
+) -> anyhow::Result<ExitCode> {
+    log::info!(
+        "{}Uploading the submit to Kelvin for validation",
+        Emoji::Upload.prefix()
+    );
+    let Some(result) = client.dry_run_submit(assignment_id, archive, options)? else {
+        log::warn!("Kelvin does not support `--dry-run-server`, the submit was not validated");
+        return Ok(ExitCode::FAILURE);
+    };
+    let failed_tests = result.tests.iter().filter(|test| !test.passed).count();
+    match args.output_format {
+        OutputFormat::Human => {
+            if result.compiled {
+                log::info!(
+                    "{}The submit was built successfully on the server",
+                    Emoji::Success.prefix()
+                );
+            } else {
+                log::error!("The submit cannot be built on the server");
+            }
+            if !result.output.trim().is_empty() {
+                eprintln!("{}", result.output.trim_end());
+            }
+            for test in &result.tests {
+                println!(
+                    "{} {}",
+                    if test.passed { "[PASS]" } else { "[FAIL]" },
+                    test.name
+                );
+            }
+            if !result.tests.is_empty() {
+                log::info!(
+                    "Passed {} of {} tests",
+                    result.tests.len() - failed_tests,
+                    result.tests.len()
+                );
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&result).context("serializing dry run result")?
+        ),
+    }
+    log::info!("Dry run, the submit was not stored by Kelvin");
+    Ok(if result.compiled && failed_tests == 0 {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+/// Adds the custom headers and the other options of `args` used for uploading submits.
+fn create_upload_options(
+    args: &SubmitArgs,
+    mut headers: HeaderMap,
+) -> anyhow::Result<UploadOptions> {
+    if let Some(version) = &args.force_version {
+        headers.insert(
+            VERSION_HEADER,
+            HeaderValue::from_str(version).context("invalid version")?,
+        );
+    }
+    for (name, value) in &args.http_header {
+        headers.insert(name.clone(), value.clone());
+    }
+    Ok(UploadOptions {
+        headers,
+        content_type: args.content_type.clone(),
+        field_name: args.multipart_field_name.clone(),
+        request_log: args
+            .log_request
+            .as_deref()
+            .map(|path| {
+                let custom_headers = args.http_header.iter().map(|(name, _)| name.clone());
+                RequestLog::create(path, args.log_response_body, custom_headers.collect())
+            })
+            .transpose()?,
+        bandwidth_limit: args.limit_upload_bandwidth.or(args
+            .limit_upload_bandwidth_kbps
+            .map(|kbps| (kbps * 1000 / 8).max(1))),
+        chunk_size: None,
+        timeout: args.timeout.map(Duration::from_secs),
+        response_timeout: args.response_timeout.map(Duration::from_millis),
+        multipart_boundary: args.multipart_boundary.clone(),
+    })
+}
+
+/// Outcome of uploading the archive into one of the assignments of a batch.
+#[derive(serde::Serialize, Debug)]
+struct BatchResult {
+    /// Package whose archive was submitted, with `--split-by-crate`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    package: Option<String>,
+    assignment_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    submit_id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl BatchResult {
+    fn new(
+        package: Option<String>,
+        assignment_id: u64,
+        result: anyhow::Result<Option<Response>>,
+    ) -> Self {
+        let mut batch_result = BatchResult {
+            package,
+            assignment_id,
+            submit_id: None,
+            url: None,
+            error: None,
+        };
+        match result {
+            Ok(Some(response)) => {
+                batch_result.submit_id = Some(response.submit.id);
+                batch_result.url = Some(response.submit.url);
+            }
+            Ok(None) => {
+                batch_result.error = Some("the submit was not accepted by Kelvin".to_string())
+            }
+            Err(error) => batch_result.error = Some(format!("{error:#}")),
+        }
+        batch_result
+    }
+}
+
+/// Uploads the archive into all given assignments, using up to `parallelism` threads.
+fn submit_batch(
+    client: &KelvinClient,
+    assignment_ids: &[u64],
+    archive: &[u8],
+    options: &UploadOptions,
+    parallelism: usize,
+) -> Vec<BatchResult> {
+    log::info!(
+        "{}Uploading the submit to {} assignments",
+        Emoji::Upload.prefix(),
+        assignment_ids.len()
+    );
+    let mut results = Vec::with_capacity(assignment_ids.len());
+    for chunk in assignment_ids.chunks(parallelism) {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = chunk
+                .iter()
+                .map(|&assignment_id| {
+                    scope.spawn(move || {
+                        (
+                            assignment_id,
+                            client.submit(assignment_id, archive, options),
+                        )
+                    })
+                })
+                .collect();
+            for handle in handles {
+                let (assignment_id, result) = handle.join().expect("Upload thread panicked");
+                results.push(BatchResult::new(None, assignment_id, result));
+            }
+        });
+    }
+    results
+}
+
+/// Creates a separate archive from each workspace member that has `assignment_id` in its
+/// `[package.metadata.kelvin]` table and submits it into that assignment.
+fn submit_split_by_crate(
+    client: &KelvinClient,
+    options: &ArchiveOptions,
+    upload_options: &UploadOptions,
+    snapshot: Option<&WorkspaceSnapshot>,
+    check_crates: bool,
+    dry_run: bool,
+) -> anyhow::Result<Vec<BatchResult>> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .exec()
+        .context("getting cargo metadata")?;
+    let mut packages = Vec::new();
+    for package in metadata.workspace_packages() {
+        match package
+            .metadata
+            .get("kelvin")
+            .and_then(|kelvin| kelvin.get("assignment_id"))
+            .and_then(|id| id.as_u64())
+        {
+            Some(assignment_id) => packages.push((package, assignment_id)),
+            None => log::warn!(
+                "Skipping package `{}`, it has no `assignment_id` in `[package.metadata.kelvin]`",
+                package.name
+            ),
+        }
+    }
+    if packages.is_empty() {
+        anyhow::bail!(
+            "No package of the workspace has `assignment_id` in `[package.metadata.kelvin]`"
+        );
+    }
+
+    let mut results = Vec::with_capacity(packages.len());
+    for (package, assignment_id) in packages {
+        if check_crates {
+            check_allowed_crates(client, assignment_id, false)?;
+        }
+        log::info!(
+            "Creating archive of package `{}` for assignment {assignment_id}",
+            package.name
+        );
+        let archive =
+            compress_workspace(package.manifest_path.clone().into_std_path_buf(), options)?;
+        if let Some(snapshot) = snapshot {
+            snapshot.verify_unchanged()?;
+        }
+        report_stage(options, Stage::Compressed);
+        if dry_run {
+            continue;
+        }
+        log::info!(
+            "{}Uploading package `{}` to Kelvin",
+            Emoji::Upload.prefix(),
+            package.name
+        );
+        report_stage(options, Stage::Uploading);
+        let result = client.submit(assignment_id, &archive, upload_options);
+        report_stage(options, Stage::Uploaded);
+        results.push(BatchResult::new(
+            Some(package.name.clone()),
+            assignment_id,
+            result,
+        ));
+    }
+    Ok(results)
+}
+
+fn print_batch_results(results: &[BatchResult], format: OutputFormat) -> anyhow::Result<ExitCode> {
+    let failed = results
+        .iter()
+        .filter(|result| result.error.is_some())
+        .count();
+    match format {
+        OutputFormat::Human if results.iter().any(|result| result.package.is_some()) => {
+            print_package_table(results);
+            log::info!(
+                "Submitted {} of {} packages",
+                results.len() - failed,
+                results.len()
+            );
+        }
+        OutputFormat::Human => {
+            for result in results {
+                match (&result.submit_id, &result.url, &result.error) {
+                    (Some(id), Some(url), _) => log::info!(
+                        "Assignment {}: created submit #{id} ({url})",
+                        result.assignment_id
+                    ),
+                    (_, _, error) => log::error!(
+                        "Assignment {}: {}",
+                        result.assignment_id,
+                        error.as_deref().unwrap_or("unknown error")
+                    ),
+                }
+            }
+            log::info!(
+                "Submitted into {} of {} assignments",
+                results.len() - failed,
+                results.len()
+            );
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(results).context("serializing batch results")?
+        ),
+    }
+    Ok(if failed > 0 {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// Aborts if the assignment was submitted at least `max_attempts` times in the time window.
+fn check_submit_attempts(
+    root: &Path,
+    assignment_id: u64,
+    max_attempts: u64,
+    window: Duration,
+) -> anyhow::Result<()> {
+    let count = history::count_submits(root, assignment_id, window)? as u64;
+    let window = match window.as_secs() {
+        seconds if seconds % 3600 == 0 => format!("{}h", seconds / 3600),
+        _ => humantime::format_duration(window).to_string(),
+    };
+    let message = format!(
+        "Assignment {assignment_id}: you have submitted {count} of {max_attempts} allowed times in the last {window}"
+    );
+    if count >= max_attempts {
+        anyhow::bail!("{message}, the submit was not uploaded");
+    }
+    log::info!("{message}");
+    Ok(())
+}
+
+fn record_batch_submits(root: &Path, results: &[BatchResult], agent: &str) {
+    let submits: Vec<(u64, u64)> = results
+        .iter()
+        .filter_map(|result| result.submit_id.map(|id| (result.assignment_id, id)))
+        .collect();
+    if let Err(error) = history::record_submits(root, &submits, agent) {
+        log::warn!("{error:?}");
+    }
+}
+
+/// Writes `<assignment_id>:<submit_id>` lines of the created submits for `--submission-id-file`.
+fn write_batch_submission_ids(path: &Path, results: &[BatchResult]) -> anyhow::Result<()> {
+    let content: String = results
+        .iter()
+        .filter_map(|result| {
+            result
+                .submit_id
+                .map(|id| format!("{}:{id}\n", result.assignment_id))
+        })
+        .collect();
+    write_submission_ids(path, &content)
+}
+
+fn write_submission_ids(path: &Path, content: &str) -> anyhow::Result<()> {
+    if path == Path::new("-") {
+        print!("{content}");
+        return Ok(());
+    }
+    std::fs::write(path, content)
+        .with_context(|| anyhow::anyhow!("Cannot write submit ID to {path:?}"))
+}
+
+/// Prints the results of `--split-by-crate` as a table.
+fn print_package_table(results: &[BatchResult]) {
+    let width = results
+        .iter()
+        .filter_map(|result| result.package.as_ref())
+        .map(|name| name.len())
+        .chain(std::iter::once("Crate".len()))
+        .max()
+        .unwrap_or(0);
+    println!("{:<width$}  {:>10}  Result", "Crate", "Assignment");
+    for result in results {
+        let outcome = match (&result.submit_id, &result.url, &result.error) {
+            (Some(id), Some(url), _) => format!("submit #{id} ({url})"),
+            (_, _, error) => format!("failed: {}", error.as_deref().unwrap_or("unknown error")),
+        };
+        println!(
+            "{:<width$}  {:>10}  {outcome}",
+            result.package.as_deref().unwrap_or_default(),
+            result.assignment_id
+        );
+    }
+}
+
+/// Writes the current stage of the submit to the `--progress-file`, if any.
+fn report_stage(options: &ArchiveOptions, stage: Stage) {
+    if let Some(progress) = &options.progress {
+        progress.set_stage(stage);
+    }
+}
+
+/// Creates a seed for `--hash-filenames` that differs between runs.
+fn random_seed() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    format!("{nanos}-{}", std::process::id())
+}
+
+fn notify_error(message: &str) {
+    if let Err(error) = notification::notify_error(message) {
+        log::warn!("Cannot show desktop notification: {error:?}");
+    }
+}
+
+/// Periodically polls the status of the given submit until it is graded.
+fn wait_for_grading(
+    client: &KelvinClient,
+    submit_id: u64,
+    interval: Duration,
+    timeout: Duration,
+) -> anyhow::Result<SubmitStatus> {
+    log::info!("Waiting for the submit to be graded");
+    let start = Instant::now();
+    loop {
+        let status = client.get_submit_status(submit_id)?;
+        if status.is_finished() {
+            return Ok(status);
+        }
+        log::debug!("Submit #{submit_id} is in state `{}`", status.status);
+        if start.elapsed() + interval > timeout {
+            anyhow::bail!("The submit was not graded within {}s", timeout.as_secs());
+        }
+        std::thread::sleep(interval);
+    }
+}
@@ -0,0 +1,88 @@
+use crate::client::KelvinClient;
+use crate::ConnectionArgs;
+use anyhow::Context;
+use std::fmt::Write as _;
+use std::io::{IsTerminal, Write};
+use std::process::{Command, Stdio};
+
+/// Descriptions longer than this (in lines) are shown in a pager.
+const PAGER_THRESHOLD: usize = 40;
+
+#[derive(clap::Parser, Debug)]
+pub struct TaskInfoArgs {
+    /// Assignment ID of the task.
+    assignment_id: u64,
+
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Open the assignment page in the browser.
+    #[clap(long, default_value_t = false)]
+    open: bool,
+}
+
+pub fn run_task_info(args: TaskInfoArgs, client: &KelvinClient) -> anyhow::Result<()> {
+    let task = client.get_task_info(args.assignment_id)?;
+
+    let mut output = String::new();
+    writeln!(output, "Task: {}", task.name)?;
+    if let Some(deadline) = &task.deadline {
+        writeln!(output, "Deadline: {deadline}")?;
+    }
+    if !task.score_breakdown.is_empty() {
+        writeln!(output, "Points:")?;
+        for item in &task.score_breakdown {
+            writeln!(output, "  {}: {}", item.name, item.points)?;
+        }
+    }
+    if let Some(crates) = &task.allowed_crates {
+        if crates.is_empty() {
+            writeln!(output, "Allowed crates: none")?;
+        } else {
+            writeln!(output, "Allowed crates: {}", crates.join(", "))?;
+        }
+    }
+    if !task.file_restrictions.is_empty() {
+        writeln!(output, "File restrictions:")?;
+        for restriction in &task.file_restrictions {
+            writeln!(output, "  {restriction}")?;
+        }
+    }
+    if let Some(description) = &task.description {
+        writeln!(output, "\n{}", description.trim_end())?;
+    }
+    print_paginated(&output)?;
+
+    if args.open {
+        let url = task
+            .url
+            .unwrap_or_else(|| format!("{}/task/{}", client.kelvin_url(), args.assignment_id));
+        open::that(url).context("opening browser")?;
+    }
+    Ok(())
+}
+
+/// Prints the text through `$PAGER` (or `less`) if it is too long to fit on the screen.
+fn print_paginated(text: &str) -> anyhow::Result<()> {
+    let stdout = std::io::stdout();
+    if !stdout.is_terminal() || text.lines().count() <= PAGER_THRESHOLD {
+        print!("{text}");
+        return Ok(());
+    }
+
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less".to_string());
+    match Command::new(&pager).stdin(Stdio::piped()).spawn() {
+        Ok(mut child) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The user can close the pager before reading everything
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait().context("waiting for pager")?;
+        }
+        Err(error) => {
+            log::debug!("Cannot start pager `{pager}`: {error:?}");
+            print!("{text}");
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use crate::client::KelvinClient;
+use crate::commands::courses::{course_url, get_course_tasks, get_courses};
+use crate::{ConnectionArgs, OutputFormat};
+use anyhow::Context;
+
+#[derive(clap::Parser, Debug)]
+pub struct TaskListArgs {
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Only list tasks of the course with the given ID (see `cargo kelvin courses`).
+    #[clap(long)]
+    course: Option<u64>,
+
+    /// Open the page of the course in the browser.
+    #[clap(long, default_value_t = false, requires = "course")]
+    open: bool,
+
+    /// Format of the printed tasks.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+}
+
+pub fn run_task_list(args: TaskListArgs, client: &KelvinClient) -> anyhow::Result<()> {
+    let courses = match args.course {
+        Some(course_id) => vec![get_course_tasks(client, course_id)?],
+        None => get_courses(client)?
+            .into_iter()
+            .map(|course| get_course_tasks(client, course.id))
+            .collect::<anyhow::Result<Vec<_>>>()?,
+    };
+
+    match args.format {
+        OutputFormat::Human => {
+            for course in &courses {
+                println!("{} (course {})", course.name, course.id);
+                if course.tasks.is_empty() {
+                    println!("  no tasks");
+                }
+                for task in &course.tasks {
+                    let id = task.id.map(|id| id.to_string()).unwrap_or_default();
+                    match &task.deadline {
+                        Some(deadline) => {
+                            println!("  {id:>6}  {} (deadline {deadline})", task.name)
+                        }
+                        None => println!("  {id:>6}  {}", task.name),
+                    }
+                }
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&courses).context("serializing tasks")?
+        ),
+    }
+
+    if args.open {
+        if let Some(course) = courses.first() {
+            open::that(course_url(client, course)).context("opening browser")?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use crate::client::KelvinClient;
+use crate::config::GlobalConfig;
+use crate::ConnectionArgs;
+use std::time::Duration;
+
+#[derive(clap::Parser, Debug)]
+pub struct TokenArgs {
+    #[clap(subcommand)]
+    command: TokenCommand,
+}
+
+impl TokenArgs {
+    pub fn connection(&self) -> &ConnectionArgs {
+        match &self.command {
+            TokenCommand::Rotate(args) => &args.connection,
+        }
+    }
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum TokenCommand {
+    /// Generate a new API token and store it in the global config.
+    Rotate(TokenRotateArgs),
+}
+
+#[derive(clap::Parser, Debug)]
+struct TokenRotateArgs {
+    #[clap(flatten)]
+    connection: ConnectionArgs,
+
+    /// How long should the current token remain valid (e.g. `1h` or `2days`).
+    /// If not given, Kelvin decides.
+    #[clap(long, value_parser = humantime::parse_duration)]
+    grace_period: Option<Duration>,
+
+    /// Only print the new token, do not store it in the global config.
+    #[clap(long, default_value_t = false)]
+    no_store: bool,
+}
+
+pub fn run_token(
+    args: TokenArgs,
+    client: &KelvinClient,
+    config: Option<&mut GlobalConfig>,
+) -> anyhow::Result<()> {
+    match args.command {
+        TokenCommand::Rotate(args) => rotate_token(args, client, config),
+    }
+}
+
+fn rotate_token(
+    args: TokenRotateArgs,
+    client: &KelvinClient,
+    config: Option<&mut GlobalConfig>,
+) -> anyhow::Result<()> {
+    let rotation = client.rotate_token(args.grace_period)?;
+    match rotation.grace_period {
+        Some(seconds) => log::info!(
+            "The previous token remains valid for {}",
+            humantime::format_duration(Duration::from_secs(seconds))
+        ),
+        None => log::info!("The previous token is no longer valid"),
+    }
+
+    if args.no_store {
+        println!("{}", rotation.token);
+        return Ok(());
+    }
+    let Some(config) = config else {
+        log::warn!("The new token was not stored, because the global config could not be loaded");
+        log::warn!("Update `KELVIN_API_TOKEN` manually to the new token");
+        println!("{}", rotation.token);
+        return Ok(());
+    };
+
+    let previous = config.token.replace(rotation.token.clone());
+    match config.store() {
+        Ok(()) => {
+            log::info!(
+                "The new token was stored in {}",
+                GlobalConfig::path()?.display()
+            );
+            println!("{}", mask_token(&rotation.token));
+        }
+        Err(error) => {
+            config.token = previous;
+            log::warn!("Cannot store the new token: {error:?}");
+            log::warn!("Update `KELVIN_API_TOKEN` manually to the new token");
+            println!("{}", rotation.token);
+            return Ok(());
+        }
+    }
+    if std::env::var_os("KELVIN_API_TOKEN").is_some() {
+        log::warn!(
+            "`KELVIN_API_TOKEN` takes precedence over the stored token, update it to the new token"
+        );
+    }
+    Ok(())
+}
+
+/// Shows only the beginning and the end of the token.
+fn mask_token(token: &str) -> String {
+    let chars: Vec<char> = token.chars().collect();
+    if chars.len() <= 8 {
+        return "*".repeat(chars.len());
+    }
+    format!(
+        "{}{}{}",
+        chars[..4].iter().collect::<String>(),
+        "*".repeat(chars.len() - 8),
+        chars[chars.len() - 4..].iter().collect::<String>()
+    )
+}
@@ -0,0 +1,41 @@
+use crate::client::KelvinClient;
+use crate::{ConnectionArgs, OutputFormat};
+use anyhow::Context;
+
+#[derive(clap::Parser, Debug)]
+pub struct WhoamiArgs {
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+
+    /// Format of the printed information.
+    #[clap(long, value_enum, default_value_t = OutputFormat::Human)]
+    format: OutputFormat,
+}
+
+pub fn run_whoami(args: WhoamiArgs, client: &KelvinClient) -> anyhow::Result<()> {
+    let user = client.get_user_info()?;
+    match args.format {
+        OutputFormat::Human => {
+            println!("Username: {}", user.username);
+            if let Some(email) = &user.email {
+                println!("Email: {email}");
+            }
+            if !user.courses.is_empty() {
+                println!("Courses: {}", user.courses.join(", "));
+            }
+            if !user.remaining_submits.is_empty() {
+                println!("Remaining submits:");
+                let mut remaining: Vec<_> = user.remaining_submits.iter().collect();
+                remaining.sort();
+                for (assignment_id, count) in remaining {
+                    println!("  assignment {assignment_id}: {count}");
+                }
+            }
+        }
+        OutputFormat::Json => println!(
+            "{}",
+            serde_json::to_string(&user).context("serializing user information")?
+        ),
+    }
+    Ok(())
+}
@@ -0,0 +1,262 @@
+/// Removes line and block comments (including doc comments) from Rust source code.
+///
+/// This is a simple lexer, not a full parser. It skips over string, raw string and character
+/// literals, so that comment markers inside them are kept. Line breaks inside block comments
+/// are kept, so that line numbers in compiler errors do not change.
+pub fn strip_comments(source: &str) -> String {
+    let mut output = String::with_capacity(source.len());
+    for (kind, text) in tokenize(source) {
+        match kind {
+            TokenKind::LineComment => trim_trailing_spaces(&mut output),
+            TokenKind::BlockComment => output.extend(text.chars().filter(|&c| c == '\n')),
+            TokenKind::String | TokenKind::Code => output.push_str(&text),
+        }
+    }
+    output
+}
+
+/// Replaces whole-word occurrences of `word` inside comments and string literals of Rust
+/// source code, the code itself is kept intact. Returns the new source and the number of
+/// replaced occurrences.
+pub fn replace_word_in_comments_and_strings(
+    source: &str,
+    word: &str,
+    replacement: &str,
+) -> (String, usize) {
+    let mut output = String::with_capacity(source.len());
+    let mut count = 0;
+    for (kind, text) in tokenize(source) {
+        match kind {
+            TokenKind::LineComment | TokenKind::BlockComment | TokenKind::String => {
+                let (text, replaced) =
+                    replace_word(&text, word, replacement, kind == TokenKind::String);
+                output.push_str(&text);
+                count += replaced;
+            }
+            TokenKind::Code => output.push_str(&text),
+        }
+    }
+    (output, count)
+}
+
+/// Returns the numbers of lines (starting at 1) of Rust source code that contain
+/// the `unsafe` keyword outside of comments and string literals.
+pub fn unsafe_keyword_lines(source: &str) -> Vec<usize> {
+    let mut lines: Vec<usize> = Vec::new();
+    let mut line = 1;
+    for (kind, text) in tokenize(source) {
+        if kind == TokenKind::Code {
+            for (start, keyword) in text.match_indices("unsafe") {
+                let before = &text[..start];
+                let previous = before.chars().next_back();
+                let next = text[start + keyword.len()..].chars().next();
+                // `r#unsafe` is an identifier
+                if is_identifier_char(previous)
+                    || is_identifier_char(next)
+                    || before.ends_with("r#")
+                {
+                    continue;
+                }
+                let keyword_line = line + before.matches('\n').count();
+                if lines.last() != Some(&keyword_line) {
+                    lines.push(keyword_line);
+                }
+            }
+        }
+        line += text.matches('\n').count();
+    }
+    lines
+}
+
+/// Replaces occurrences of `word` that are not a part of a longer identifier. In string
+/// literals, arguments captured by format strings (`{word}` or `{word:?}`) are kept.
+fn replace_word(text: &str, word: &str, replacement: &str, is_string: bool) -> (String, usize) {
+    if word.is_empty() {
+        return (text.to_string(), 0);
+    }
+    let mut output = String::with_capacity(text.len());
+    let mut count = 0;
+    let mut position = 0;
+    for (start, _) in text.match_indices(word) {
+        let end = start + word.len();
+        let previous = text[..start].chars().next_back();
+        let next = text[end..].chars().next();
+        let is_format_argument =
+            is_string && previous == Some('{') && matches!(next, Some('}' | ':'));
+        if start < position
+            || is_identifier_char(previous)
+            || is_identifier_char(next)
+            || is_format_argument
+        {
+            continue;
+        }
+        output.push_str(&text[position..start]);
+        output.push_str(replacement);
+        position = end;
+        count += 1;
+    }
+    output.push_str(&text[position..]);
+    (output, count)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TokenKind {
+    LineComment,
+    BlockComment,
+    /// String and raw string literals.
+    String,
+    Code,
+}
+
+/// Splits Rust source code into comments, literals and the remaining code.
+fn tokenize(source: &str) -> Vec<(TokenKind, String)> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens: Vec<(TokenKind, String)> = Vec::new();
+    let mut push = |kind: TokenKind, text: &[char]| match tokens.last_mut() {
+        Some((TokenKind::Code, code)) if kind == TokenKind::Code => code.extend(text),
+        _ => tokens.push((kind, text.iter().collect())),
+    };
+    let mut index = 0;
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+        let (kind, end) = match c {
+            '/' if next == Some('/') => {
+                let end = chars[index..]
+                    .iter()
+                    .position(|&c| c == '\n')
+                    .map_or(chars.len(), |position| index + position);
+                (TokenKind::LineComment, end)
+            }
+            '/' if next == Some('*') => (TokenKind::BlockComment, block_comment_end(&chars, index)),
+            '"' => (TokenKind::String, string_end(&chars, index)),
+            'r' if is_raw_string_prefix(&chars, index) => match raw_string_end(&chars, index) {
+                Some(end) => (TokenKind::String, end),
+                None => (TokenKind::Code, index + 1),
+            },
+            // Character literals are skipped so that a quote inside them does not start
+            // a string, otherwise there is a lifetime or a label
+            '\'' => (
+                TokenKind::Code,
+                char_literal_end(&chars, index).unwrap_or(index + 1),
+            ),
+            _ => (TokenKind::Code, index + 1),
+        };
+        push(kind, &chars[index..end]);
+        index = end;
+    }
+    tokens
+}
+
+/// Returns the index after the end of a block comment starting at `start`.
+fn block_comment_end(chars: &[char], start: usize) -> usize {
+    // Block comments can be nested in Rust
+    let mut depth = 0;
+    let mut index = start;
+    while index < chars.len() {
+        if chars[index] == '/' && chars.get(index + 1) == Some(&'*') {
+            depth += 1;
+            index += 2;
+        } else if chars[index] == '*' && chars.get(index + 1) == Some(&'/') {
+            depth -= 1;
+            index += 2;
+            if depth == 0 {
+                break;
+            }
+        } else {
+            index += 1;
+        }
+    }
+    index.min(chars.len())
+}
+
+fn is_identifier_char(c: Option<char>) -> bool {
+    c.is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+/// Checks that the `r` at `index` is not a part of an identifier (`br` and `cr` are prefixes
+/// of raw byte and C strings).
+fn is_raw_string_prefix(chars: &[char], index: usize) -> bool {
+    let previous = |offset: usize| index.checked_sub(offset).map(|i| chars[i]);
+    match previous(1) {
+        Some('b' | 'c') => !is_identifier_char(previous(2)),
+        c => !is_identifier_char(c),
+    }
+}
+
+fn trim_trailing_spaces(output: &mut String) {
+    let trimmed = output.trim_end_matches([' ', '\t']).len();
+    output.truncate(trimmed);
+}
+
+/// Returns the index after the end of a string literal starting at `start`.
+fn string_end(chars: &[char], start: usize) -> usize {
+    let mut index = start + 1;
+    while index < chars.len() {
+        match chars[index] {
+            '\\' => index += 2,
+            '"' => return index + 1,
+            _ => index += 1,
+        }
+    }
+    chars.len()
+}
+
+/// Returns the index after the end of a raw string literal (`r"..."` or `r#"..."#`)
+/// starting at `start`, or `None` if there is no raw string literal at `start`.
+fn raw_string_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut index = start + 1;
+    let mut hashes = 0;
+    while chars.get(index) == Some(&'#') {
+        hashes += 1;
+        index += 1;
+    }
+    if chars.get(index) != Some(&'"') {
+        return None;
+    }
+    index += 1;
+    while index < chars.len() {
+        if chars[index] == '"' && (1..=hashes).all(|offset| chars.get(index + offset) == Some(&'#'))
+        {
+            return Some(index + 1 + hashes);
+        }
+        index += 1;
+    }
+    Some(chars.len())
+}
+
+/// Returns the index after a character literal starting at `start`, or `None` if there
+/// is a lifetime or a label at `start`.
+fn char_literal_end(chars: &[char], start: usize) -> Option<usize> {
+    let end = match (chars.get(start + 1), chars.get(start + 2)) {
+        (Some('\\'), _) => chars[start + 2..]
+            .iter()
+            .skip(1)
+            .position(|&c| c == '\'')
+            .map(|position| start + 3 + position + 1),
+        (Some(_), Some('\'')) => Some(start + 3),
+        _ => None,
+    };
+    end.map(|end| end.min(chars.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::unsafe_keyword_lines;
+
+    #[test]
+    fn find_unsafe_keywords() {
+        let source = r##"// unsafe in a comment
+fn main() {
+    let s = "unsafe in a string";
+    let r = r#"unsafe"#;
+    let not_unsafe_code = 1;
+    unsafe { std::hint::unreachable_unchecked() }
+    /* unsafe
+       unsafe */ let r#unsafe = 2;
+}
+unsafe fn f() {}
+"##;
+        assert_eq!(unsafe_keyword_lines(source), [6, 10]);
+    }
+}
@@ -0,0 +1,218 @@
+use crate::logging::ColorScheme;
+use crate::{config_dir, DEFAULT_KELVIN_URL};
+use anyhow::Context;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Name of the project configuration file, stored in the workspace root.
+pub const PROJECT_CONFIG_FILE: &str = ".kelvin.toml";
+
+/// User-wide configuration, stored in `~/.config/kelvin/config.toml`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct GlobalConfig {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_scheme: Option<ColorScheme>,
+    /// API token stored by `cargo kelvin token rotate`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kelvin_url: Option<String>,
+}
+
+impl GlobalConfig {
+    pub fn path() -> anyhow::Result<PathBuf> {
+        Ok(config_dir()?.join("config.toml"))
+    }
+
+    /// Loads the global configuration, or returns the default one if it does not exist.
+    pub fn load() -> anyhow::Result<Self> {
+        let path = Self::path()?;
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| anyhow::anyhow!("Cannot read global config from {path:?}"))?;
+        toml::from_str(&content)
+            .with_context(|| anyhow::anyhow!("Cannot parse global config at {path:?}"))
+    }
+
+    pub fn store(&self) -> anyhow::Result<()> {
+        let content = toml::to_string(self).context("cannot serialize global config")?;
+        // The config can contain the API token, so only the user should be able to read it
+        write_config_file(&Self::path()?, &content, true)
+    }
+}
+
+/// Writes a configuration file atomically, by writing a temporary file in the same directory
+/// and renaming it. If `private` is set, only the user can read the file.
+pub fn write_config_file(path: &Path, content: &str, private: bool) -> anyhow::Result<()> {
+    let parent = path
+        .parent()
+        .context("Config file has no parent directory")?;
+    std::fs::create_dir_all(parent)
+        .with_context(|| anyhow::anyhow!("Cannot create directory {parent:?}"))?;
+
+    let mut builder = tempfile::Builder::new();
+    builder.prefix(".kelvin-config");
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = match std::fs::metadata(path) {
+            _ if private => 0o600,
+            Ok(metadata) => metadata.permissions().mode() & 0o777,
+            Err(_) => 0o644,
+        };
+        builder.permissions(std::fs::Permissions::from_mode(mode));
+    }
+    let mut file = builder
+        .tempfile_in(parent)
+        .with_context(|| anyhow::anyhow!("Cannot create temporary file in {parent:?}"))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| anyhow::anyhow!("Cannot write config to {:?}", file.path()))?;
+    file.persist(path)
+        .with_context(|| anyhow::anyhow!("Cannot write config to {path:?}"))?;
+    Ok(())
+}
+
+/// Where a resolved configuration value comes from.
+#[derive(Debug)]
+pub enum ConfigSource {
+    Cli,
+    Env(&'static str),
+    ProjectConfig(PathBuf),
+    GlobalConfig(PathBuf),
+    Default,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Cli => write!(f, "CLI"),
+            ConfigSource::Env(name) => write!(f, "env ({name})"),
+            ConfigSource::ProjectConfig(path) => write!(f, "project config ({})", path.display()),
+            ConfigSource::GlobalConfig(path) => write!(f, "global config ({})", path.display()),
+            ConfigSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// Environment variable with the Kelvin URL.
+pub const KELVIN_URL_ENV: &str = "KELVIN_URL";
+
+/// Resolves the Kelvin URL from the CLI, the `KELVIN_URL` environment variable,
+/// the project config of the current workspace and the global config, in this order.
+pub fn resolve_kelvin_url(
+    cli: Option<&str>,
+    global: &GlobalConfig,
+) -> anyhow::Result<(String, ConfigSource)> {
+    if let Some(url) = cli {
+        return Ok((url.to_string(), ConfigSource::Cli));
+    }
+    if let Some(url) = std::env::var(KELVIN_URL_ENV)
+        .ok()
+        .filter(|url| !url.is_empty())
+    {
+        return Ok((url, ConfigSource::Env(KELVIN_URL_ENV)));
+    }
+    if let Some(root) = workspace_root() {
+        if let Some(url) = KelvinConfig::load(&root)?.kelvin_url {
+            return Ok((
+                url,
+                ConfigSource::ProjectConfig(root.join(PROJECT_CONFIG_FILE)),
+            ));
+        }
+    }
+    if let Some(url) = &global.kelvin_url {
+        return Ok((
+            url.clone(),
+            ConfigSource::GlobalConfig(GlobalConfig::path()?),
+        ));
+    }
+    Ok((DEFAULT_KELVIN_URL.to_string(), ConfigSource::Default))
+}
+
+/// Returns the root of the Cargo workspace in the current directory, if there is one.
+pub fn workspace_root() -> Option<PathBuf> {
+    match cargo_metadata::MetadataCommand::new().no_deps().exec() {
+        Ok(metadata) => Some(metadata.workspace_root.into_std_path_buf()),
+        Err(error) => {
+            log::debug!("Cannot load cargo metadata: {error:?}");
+            None
+        }
+    }
+}
+
+/// Project configuration, stored in `.kelvin.toml` in the workspace root.
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct KelvinConfig {
+    /// Kelvin instance used for this project.
+    #[serde(default)]
+    pub kelvin_url: Option<String>,
+    #[serde(default)]
+    pub submit: SubmitConfig,
+}
+
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct SubmitConfig {
+    /// Additional files that should be stored in the archive.
+    #[serde(default)]
+    pub extra_files: Vec<ExtraFile>,
+    /// Abort the submit if a Rust file contains `unsafe` code.
+    #[serde(default)]
+    pub require_no_unsafe: bool,
+    /// Abort the submit if a package of the workspace does not use this Rust edition.
+    #[serde(default, deserialize_with = "deserialize_edition")]
+    pub required_edition: Option<String>,
+}
+
+/// Rust editions that can be required from the packages of a workspace.
+pub const RUST_EDITIONS: [&str; 4] = ["2015", "2018", "2021", "2024"];
+
+fn deserialize_edition<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<String>, D::Error> {
+    let edition: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    match edition {
+        Some(edition) if !RUST_EDITIONS.contains(&edition.as_str()) => {
+            Err(serde::de::Error::custom(format!(
+                "unknown edition `{edition}`, expected one of {}",
+                RUST_EDITIONS.join(", ")
+            )))
+        }
+        edition => Ok(edition),
+    }
+}
+
+/// A file (or a set of files) from outside the normal workspace walk that should be included
+/// in the archive.
+#[derive(serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExtraFile {
+    /// Path relative to the workspace root. Can contain glob patterns, e.g. `reports/*.pdf`.
+    pub src: String,
+    /// Path in the archive. If `src` is a glob pattern, this is a directory into which
+    /// the matched files are stored.
+    #[serde(default)]
+    pub dest: Option<PathBuf>,
+    /// Store the files only under their file name, without the directories leading to them
+    /// from the workspace root.
+    #[serde(default)]
+    pub strip_workspace_prefix: bool,
+}
+
+impl KelvinConfig {
+    /// Loads the project configuration, or returns the default one if it does not exist.
+    pub fn load(workspace_root: &Path) -> anyhow::Result<Self> {
+        let path = workspace_root.join(PROJECT_CONFIG_FILE);
+        if !path.is_file() {
+            return Ok(Self::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| anyhow::anyhow!("Cannot read project config from {path:?}"))?;
+        toml::from_str(&content)
+            .with_context(|| anyhow::anyhow!("Cannot parse project config at {path:?}"))
+    }
+}
@@ -0,0 +1,138 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Name of the file with the coverage report stored in the archive.
+pub const COVERAGE_REPORT_FILE: &str = "coverage_report.json";
+
+pub struct CoverageReport {
+    /// JSON report produced by the coverage tool.
+    pub content: String,
+    pub covered_lines: u64,
+    pub total_lines: u64,
+}
+
+impl CoverageReport {
+    pub fn percent(&self) -> f64 {
+        match self.total_lines {
+            0 => 100.0,
+            total => self.covered_lines as f64 * 100.0 / total as f64,
+        }
+    }
+}
+
+/// Measures the line coverage of the tests of the workspace with `cargo llvm-cov`,
+/// or with `cargo tarpaulin` if it is not installed. Returns `None` if neither tool
+/// is installed.
+pub fn measure_coverage(root: &Path) -> anyhow::Result<Option<CoverageReport>> {
+    if is_installed(root, "llvm-cov") {
+        return run_llvm_cov(root).map(Some);
+    }
+    if is_installed(root, "tarpaulin") {
+        return run_tarpaulin(root).map(Some);
+    }
+    Ok(None)
+}
+
+/// Checks if the given Cargo subcommand is installed.
+pub fn is_installed(root: &Path, subcommand: &str) -> bool {
+    Command::new("cargo")
+        .args([subcommand, "--version"])
+        .current_dir(root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[derive(serde::Deserialize)]
+struct LlvmCovExport {
+    data: Vec<LlvmCovData>,
+}
+
+#[derive(serde::Deserialize)]
+struct LlvmCovData {
+    totals: LlvmCovTotals,
+}
+
+#[derive(serde::Deserialize)]
+struct LlvmCovTotals {
+    lines: LlvmCovSummary,
+}
+
+#[derive(serde::Deserialize)]
+struct LlvmCovSummary {
+    count: u64,
+    covered: u64,
+}
+
+fn run_llvm_cov(root: &Path) -> anyhow::Result<CoverageReport> {
+    log::info!("Running `cargo llvm-cov`");
+    let output = Command::new("cargo")
+        .args(["llvm-cov", "--json"])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo llvm-cov`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo llvm-cov` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let content = String::from_utf8_lossy(&output.stdout).into_owned();
+    let export: LlvmCovExport =
+        serde_json::from_str(&content).context("cannot parse the output of `cargo llvm-cov`")?;
+    let (covered_lines, total_lines) = export.data.iter().fold((0, 0), |(covered, total), data| {
+        (
+            covered + data.totals.lines.covered,
+            total + data.totals.lines.count,
+        )
+    });
+    Ok(CoverageReport {
+        content,
+        covered_lines,
+        total_lines,
+    })
+}
+
+#[derive(serde::Deserialize)]
+struct TarpaulinReport {
+    files: Vec<TarpaulinFile>,
+}
+
+#[derive(serde::Deserialize)]
+struct TarpaulinFile {
+    covered: u64,
+    coverable: u64,
+}
+
+fn run_tarpaulin(root: &Path) -> anyhow::Result<CoverageReport> {
+    log::info!("Running `cargo tarpaulin`");
+    let dir = tempfile::tempdir().context("cannot create temporary directory")?;
+    let output = Command::new("cargo")
+        .args(["tarpaulin", "--out", "Json", "--output-dir"])
+        .arg(dir.path())
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo tarpaulin`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo tarpaulin` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let path = dir.path().join("tarpaulin-report.json");
+    let content = std::fs::read_to_string(&path)
+        .with_context(|| anyhow::anyhow!("Cannot read coverage report from {path:?}"))?;
+    let report: TarpaulinReport =
+        serde_json::from_str(&content).context("cannot parse the report of `cargo tarpaulin`")?;
+    let (covered_lines, total_lines) =
+        report.files.iter().fold((0, 0), |(covered, total), file| {
+            (covered + file.covered, total + file.coverable)
+        });
+    Ok(CoverageReport {
+        content,
+        covered_lines,
+        total_lines,
+    })
+}
@@ -0,0 +1,86 @@
+use crate::snapshot::git;
+use crate::toolchain::rustc_version;
+use std::fmt::Write;
+use std::path::Path;
+use std::process::Command;
+use std::time::SystemTime;
+
+/// Name of the file with information about the toolchain stored in the archive.
+/// A file with this name is never taken from the workspace itself.
+pub const ENV_FILE: &str = ".kelvin-env.txt";
+
+/// Describes the Rust toolchain used to create the submit.
+pub fn record_environment() -> String {
+    let mut output = String::new();
+    for (name, args) in [
+        ("rustc", &["rustc", "--version"][..]),
+        ("cargo", &["cargo", "--version"][..]),
+        ("toolchain", &["rustup", "show", "active-toolchain"][..]),
+    ] {
+        let value = command_output(args).unwrap_or_else(|| "unknown".to_string());
+        writeln!(output, "{name}: {value}").unwrap();
+    }
+    writeln!(
+        output,
+        "submitted at: {}",
+        humantime::format_rfc3339_seconds(SystemTime::now())
+    )
+    .unwrap();
+    output
+}
+
+/// Name of the file with information about the build environment stored in the archive
+/// by `--inject-build-metadata`.
+pub const BUILD_METADATA_FILE: &str = "build_metadata.json";
+
+#[derive(serde::Serialize)]
+struct BuildMetadata {
+    cargo_pkg_version: &'static str,
+    rustc_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    os: &'static str,
+    arch: &'static str,
+    submission_timestamp: String,
+    git_commit: Option<String>,
+}
+
+/// Describes the environment in which the submit of the workspace in `root` is created,
+/// as JSON. The name of the computer is only included with `include_hostname`.
+pub fn build_metadata(root: &Path, include_hostname: bool) -> String {
+    let metadata = BuildMetadata {
+        cargo_pkg_version: env!("CARGO_PKG_VERSION"),
+        rustc_version: rustc_version(root),
+        hostname: include_hostname
+            .then(|| command_output(&["hostname"]))
+            .flatten(),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        submission_timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        git_commit: git(root, &["rev-parse", "HEAD"])
+            .ok()
+            .map(|commit| commit.trim().to_string()),
+    };
+    serde_json::to_string_pretty(&metadata).expect("cannot serialize build metadata")
+}
+
+fn command_output(args: &[&str]) -> Option<String> {
+    let output = Command::new(args[0]).args(&args[1..]).output();
+    match output {
+        Ok(output) if output.status.success() => {
+            Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        Ok(output) => {
+            log::debug!(
+                "`{}` failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(error) => {
+            log::debug!("Cannot run `{}`: {error:?}", args.join(" "));
+            None
+        }
+    }
+}
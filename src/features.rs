@@ -0,0 +1,140 @@
+use anyhow::Context;
+use cargo_metadata::Message;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs `cargo check` on all targets of the workspace with the given features and returns
+/// the canonical paths of the source files used by the compilation, read from the
+/// dep-info files written by rustc.
+pub fn sources_used_with_features(
+    root: &Path,
+    features: &[String],
+) -> anyhow::Result<HashSet<PathBuf>> {
+    let features = features.join(",");
+    log::info!("Running `cargo check --features {features}` to find the used source files");
+    let output = Command::new("cargo")
+        .args([
+            "check",
+            "--workspace",
+            "--all-targets",
+            "--message-format=json",
+            "--features",
+            &features,
+        ])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo check`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo check --features {features}` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    let mut sources = HashSet::new();
+    for message in Message::parse_stream(output.stdout.as_slice()) {
+        let Message::CompilerArtifact(artifact) =
+            message.context("cannot parse `cargo check` output")?
+        else {
+            continue;
+        };
+        for filename in &artifact.filenames {
+            let Some(path) = dep_info_path(filename.as_std_path()) else {
+                continue;
+            };
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(error) => {
+                    log::debug!("Cannot read dep-info file {path:?}: {error:?}");
+                    continue;
+                }
+            };
+            // rustc runs in the workspace root, so paths of local packages are relative to it
+            sources.extend(
+                parse_dep_info(&content)
+                    .into_iter()
+                    .filter_map(|source| std::fs::canonicalize(root.join(source)).ok()),
+            );
+        }
+    }
+    Ok(sources)
+}
+
+/// Returns the path of the dep-info file written next to an artifact, e.g.
+/// `deps/foo-1234.d` for `deps/libfoo-1234.rmeta`.
+fn dep_info_path(artifact: &Path) -> Option<PathBuf> {
+    let stem = artifact.file_stem()?.to_str()?;
+    let name = stem.strip_prefix("lib").unwrap_or(stem);
+    Some(artifact.with_file_name(format!("{name}.d")))
+}
+
+/// Parses a dep-info file, which uses the Makefile format, and returns the paths of the
+/// dependencies of all its rules.
+fn parse_dep_info(content: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    for line in content.lines() {
+        if line.starts_with('#') {
+            continue;
+        }
+        // Windows paths contain `:\`, so only a colon followed by a space ends the target
+        let dependencies = match line.find(": ") {
+            Some(index) => &line[index + 2..],
+            None => continue,
+        };
+        let mut path = String::new();
+        let mut chars = dependencies.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if chars.as_str().starts_with(' ') => {
+                    path.push(' ');
+                    chars.next();
+                }
+                ' ' => {
+                    if !path.is_empty() {
+                        paths.push(PathBuf::from(std::mem::take(&mut path)));
+                    }
+                }
+                c => path.push(c),
+            }
+        }
+        if !path.is_empty() {
+            paths.push(PathBuf::from(path));
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{dep_info_path, parse_dep_info};
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn parse_dep_info_file() {
+        let content = r"/ws/target/debug/deps/foo-1234.d: src/main.rs src/my\ module.rs
+
+/ws/target/debug/deps/libfoo-1234.rmeta: src/main.rs src/my\ module.rs
+
+src/main.rs:
+src/my\ module.rs:
+
+# env-dep:CARGO_PKG_NAME=foo
+";
+        let paths = parse_dep_info(content);
+        assert_eq!(
+            paths,
+            [
+                "src/main.rs",
+                "src/my module.rs",
+                "src/main.rs",
+                "src/my module.rs"
+            ]
+            .map(PathBuf::from)
+        );
+        assert_eq!(
+            dep_info_path(Path::new("/ws/target/debug/deps/libfoo-1234.rmeta")),
+            Some(PathBuf::from("/ws/target/debug/deps/foo-1234.d"))
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use crate::coverage::is_installed;
+use anyhow::Context;
+use std::fs::File;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Name of the flamegraph stored in the archive.
+pub const FLAMEGRAPH_FILE: &str = "flamegraph.svg";
+
+/// Flamegraphs larger than this are not stored in the archive.
+const MAX_FLAMEGRAPH_SIZE: u64 = 5 * 1024 * 1024;
+
+/// How the profiled binary is run.
+pub struct ProfiledRun<'a> {
+    /// Binary target to profile, the only binary of the workspace is used if not given.
+    pub binary: Option<&'a str>,
+    pub args: Vec<&'a str>,
+    /// File passed to the standard input of the binary.
+    pub input: Option<&'a Path>,
+}
+
+/// Profiles a binary of the workspace with `cargo flamegraph` and returns the generated
+/// flamegraph. Returns `None` if `cargo flamegraph` is not installed or the flamegraph
+/// is too large.
+pub fn record_flamegraph(root: &Path, run: &ProfiledRun) -> anyhow::Result<Option<Vec<u8>>> {
+    if !is_installed(root, "flamegraph") {
+        log::warn!(
+            "Cannot profile the binary, install `cargo-flamegraph`. The flamegraph is skipped."
+        );
+        return Ok(None);
+    }
+    let binary = match run.binary {
+        Some(binary) => binary.to_string(),
+        None => only_binary(root)?,
+    };
+    let dir = tempfile::tempdir().context("cannot create temporary directory")?;
+    let path = dir.path().join(FLAMEGRAPH_FILE);
+    let stdin = match run.input {
+        Some(input) => Stdio::from(
+            File::open(input)
+                .with_context(|| anyhow::anyhow!("Cannot open input data {input:?}"))?,
+        ),
+        None => Stdio::null(),
+    };
+    log::info!("Profiling binary `{binary}` with `cargo flamegraph`");
+    let output = Command::new("cargo")
+        .args(["flamegraph", "--bin", &binary, "--output"])
+        .arg(&path)
+        .arg("--")
+        .args(&run.args)
+        .current_dir(root)
+        .stdin(stdin)
+        .output()
+        .context("cannot run `cargo flamegraph`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo flamegraph` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let size = std::fs::metadata(&path)
+        .with_context(|| anyhow::anyhow!("`cargo flamegraph` did not create {path:?}"))?
+        .len();
+    if size > MAX_FLAMEGRAPH_SIZE {
+        log::warn!(
+            "The flamegraph has {size}B, which is more than {MAX_FLAMEGRAPH_SIZE}B. The flamegraph is skipped."
+        );
+        return Ok(None);
+    }
+    std::fs::read(&path)
+        .with_context(|| anyhow::anyhow!("Cannot read flamegraph from {path:?}"))
+        .map(Some)
+}
+
+/// Returns the name of the only binary target of the workspace.
+fn only_binary(root: &Path) -> anyhow::Result<String> {
+    let metadata = cargo_metadata::MetadataCommand::new()
+        .current_dir(root)
+        .no_deps()
+        .exec()
+        .context("getting cargo metadata")?;
+    let binaries: Vec<&str> = metadata
+        .workspace_packages()
+        .into_iter()
+        .flat_map(|package| &package.targets)
+        .filter(|target| target.is_bin())
+        .map(|target| target.name.as_str())
+        .collect();
+    match binaries.as_slice() {
+        [binary] => Ok(binary.to_string()),
+        [] => anyhow::bail!("The workspace has no binary to profile"),
+        _ => anyhow::bail!(
+            "The workspace has multiple binaries ({}), choose one with `--flamegraph-bin`",
+            binaries.join(", ")
+        ),
+    }
+}
@@ -0,0 +1,36 @@
+use crate::snapshot::git;
+use std::path::Path;
+
+/// Name of the file with recent commits stored in the archive by `--embed-git-log`.
+pub const GIT_LOG_FILE: &str = "git_history.txt";
+
+/// Name of the file with uncommitted changes stored in the archive by `--embed-git-diff`.
+pub const GIT_DIFF_FILE: &str = "uncommitted_changes.diff";
+
+/// Returns the last `count` commits of the repository containing `root`, one per line,
+/// or `None` if `root` is not inside a git repository.
+pub fn recent_commits(root: &Path, count: usize) -> Option<String> {
+    let log = run_git(root, &["log", "--oneline", &format!("-{count}")])?;
+    Some(format!(
+        "# Generated by cargo-kelvin: the last {count} commits (`git log --oneline -{count}`)\n{log}"
+    ))
+}
+
+/// Returns the changes of the working tree against `HEAD` of the repository containing `root`,
+/// or `None` if `root` is not inside a git repository.
+pub fn uncommitted_changes(root: &Path) -> Option<String> {
+    let diff = run_git(root, &["diff", "HEAD"])?;
+    Some(format!(
+        "# Generated by cargo-kelvin: uncommitted changes (`git diff HEAD`)\n{diff}"
+    ))
+}
+
+fn run_git(root: &Path, args: &[&str]) -> Option<String> {
+    match git(root, args) {
+        Ok(output) => Some(output),
+        Err(error) => {
+            log::debug!("Skipping git information: {error:?}");
+            None
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::Context;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Name of the file with archives saved by `--save-archive` and created submits, stored in
+/// the workspace root.
+pub const HISTORY_FILE: &str = ".kelvin-history.json";
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default)]
+struct History {
+    #[serde(default)]
+    archives: Vec<SavedArchive>,
+    #[serde(default)]
+    submits: Vec<SubmitRecord>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct SubmitRecord {
+    assignment_id: u64,
+    submit_id: u64,
+    /// UNIX timestamp (in seconds) of the time when the submit was created.
+    submitted_at: u64,
+    /// Tool that created the submit, see `--kelvin-agent`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    agent: Option<String>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+struct SavedArchive {
+    path: PathBuf,
+    /// UNIX timestamp (in seconds) of the time when the archive was saved.
+    saved_at: u64,
+}
+
+fn load_history(path: &Path) -> anyhow::Result<History> {
+    if !path.is_file() {
+        return Ok(History::default());
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| anyhow::anyhow!("Cannot read history from {path:?}"))?;
+    serde_json::from_str(&content)
+        .with_context(|| anyhow::anyhow!("Cannot parse history at {path:?}"))
+}
+
+/// Saves the archive to `path` and records it in the history of the workspace.
+pub fn save_archive(root: &Path, path: &Path, archive: &[u8]) -> anyhow::Result<()> {
+    std::fs::write(path, archive)
+        .with_context(|| anyhow::anyhow!("Cannot save the archive to {path:?}"))?;
+    let path = path
+        .canonicalize()
+        .with_context(|| anyhow::anyhow!("Cannot resolve {path:?}"))?;
+    log::info!("Saved the archive to {}", path.display());
+
+    let history_path = root.join(HISTORY_FILE);
+    let mut history = load_history(&history_path)?;
+    history.archives.push(SavedArchive {
+        path,
+        saved_at: now(),
+    });
+    store_history(&history_path, &history)
+}
+
+fn store_history(path: &Path, history: &History) -> anyhow::Result<()> {
+    let content = serde_json::to_string_pretty(history).context("cannot serialize history")?;
+    std::fs::write(path, content)
+        .with_context(|| anyhow::anyhow!("Cannot write history to {path:?}"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Records submits created in the given assignments by the given agent, as
+/// `(assignment_id, submit_id)` pairs.
+pub fn record_submits(root: &Path, submits: &[(u64, u64)], agent: &str) -> anyhow::Result<()> {
+    let path = root.join(HISTORY_FILE);
+    let mut history = load_history(&path)?;
+    let submitted_at = now();
+    history.submits.extend(
+        submits
+            .iter()
+            .map(|&(assignment_id, submit_id)| SubmitRecord {
+                assignment_id,
+                submit_id,
+                submitted_at,
+                agent: Some(agent.to_string()),
+            }),
+    );
+    store_history(&path, &history)
+}
+
+/// Counts submits recorded for the assignment within the given time window.
+pub fn count_submits(root: &Path, assignment_id: u64, window: Duration) -> anyhow::Result<usize> {
+    let history = load_history(&root.join(HISTORY_FILE))?;
+    let since = now().saturating_sub(window.as_secs());
+    Ok(history
+        .submits
+        .iter()
+        .filter(|submit| submit.assignment_id == assignment_id && submit.submitted_at >= since)
+        .count())
+}
+
+/// Loads the archive that was saved most recently in the workspace and checks that it is
+/// a valid ZIP archive.
+pub fn load_last_archive(root: &Path) -> anyhow::Result<Vec<u8>> {
+    let history = load_history(&root.join(HISTORY_FILE))?;
+    let saved = history
+        .archives
+        .last()
+        .context("No archive was saved in this workspace, use `--save-archive` first")?;
+    let path = &saved.path;
+    let archive = std::fs::read(path)
+        .with_context(|| anyhow::anyhow!("Cannot read the last saved archive {path:?}"))?;
+    zip::ZipArchive::new(Cursor::new(&archive))
+        .with_context(|| anyhow::anyhow!("The last saved archive {path:?} is not a valid ZIP"))?;
+    let saved_at = UNIX_EPOCH + Duration::from_secs(saved.saved_at);
+    log::info!(
+        "Reusing the archive {}, saved at {}",
+        path.display(),
+        humantime::format_rfc3339_seconds(saved_at)
+    );
+    Ok(archive)
+}
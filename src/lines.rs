@@ -0,0 +1,56 @@
+use anyhow::Context;
+use std::io::Read;
+
+/// Number of lines in the Rust source files of a submit.
+#[derive(serde::Serialize, Debug, Default)]
+pub struct LineCounts {
+    pub loc: usize,
+    pub blank_lines: usize,
+    pub comment_lines: usize,
+    pub code_lines: usize,
+}
+
+impl LineCounts {
+    pub fn log(&self) {
+        log::info!(
+            "Lines of Rust code: {} total, {} code, {} comments, {} blank",
+            self.loc,
+            self.code_lines,
+            self.comment_lines,
+            self.blank_lines
+        );
+    }
+}
+
+/// Counts lines of all `.rs` files stored in the given ZIP archive.
+/// Lines are counted as comments if they start with `//`.
+pub fn count_lines(archive: &[u8]) -> anyhow::Result<LineCounts> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut counts = LineCounts::default();
+    for index in 0..zip.len() {
+        let mut file = zip
+            .by_index(index)
+            .context("cannot read file from ZIP archive")?;
+        if !file.is_file() || !file.name().ends_with(".rs") {
+            continue;
+        }
+        let mut content = String::new();
+        if let Err(error) = file.read_to_string(&mut content) {
+            log::warn!("Cannot count lines of {}: {error}", file.name());
+            continue;
+        }
+        for line in content.lines() {
+            let line = line.trim();
+            counts.loc += 1;
+            if line.is_empty() {
+                counts.blank_lines += 1;
+            } else if line.starts_with("//") {
+                counts.comment_lines += 1;
+            } else {
+                counts.code_lines += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
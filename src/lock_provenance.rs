@@ -0,0 +1,58 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+
+/// Name of the file with the output of `cargo metadata --locked` stored in the archive.
+pub const CARGO_METADATA_FILE: &str = "cargo_metadata.json";
+
+/// Name of the file with the output of `cargo verify-project` stored in the archive.
+pub const CARGO_VERIFY_FILE: &str = "cargo_verify.txt";
+
+/// Returns the metadata of the workspace packages (`cargo metadata --locked --no-deps`),
+/// or `None` if it cannot be loaded, e.g. because `Cargo.lock` is not up to date.
+pub fn locked_metadata(root: &Path) -> Option<String> {
+    let args = ["metadata", "--locked", "--no-deps", "--format-version", "1"];
+    let result = run_cargo(root, &args).and_then(|output| {
+        if !output.status.success() {
+            anyhow::bail!(
+                "`cargo metadata --locked` has failed:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    });
+    match result {
+        Ok(metadata) => Some(metadata),
+        Err(error) => {
+            log::warn!("Skipping the locked metadata: {error:?}");
+            None
+        }
+    }
+}
+
+/// Returns the output of `cargo verify-project`, which is stored even if the verification
+/// fails, so that graders can see why. Returns `None` if `cargo` cannot be run.
+pub fn verify_project(root: &Path) -> Option<String> {
+    match run_cargo(root, &["verify-project"]) {
+        Ok(output) => {
+            if !output.status.success() {
+                log::warn!("`cargo verify-project` has found problems in the workspace");
+            }
+            let mut content = String::from_utf8_lossy(&output.stdout).into_owned();
+            content.push_str(&String::from_utf8_lossy(&output.stderr));
+            Some(content)
+        }
+        Err(error) => {
+            log::warn!("Skipping the project verification: {error:?}");
+            None
+        }
+    }
+}
+
+fn run_cargo(root: &Path, args: &[&str]) -> anyhow::Result<std::process::Output> {
+    Command::new("cargo")
+        .args(args)
+        .current_dir(root)
+        .output()
+        .with_context(|| anyhow::anyhow!("Cannot run `cargo {}`", args[0]))
+}
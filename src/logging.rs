@@ -0,0 +1,237 @@
+use anyhow::Context;
+use env_logger::fmt::style::{Ansi256Color, AnsiColor, Effects, Style};
+use log::{Level, LevelFilter};
+use std::io::{IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Whether log messages should be prefixed with emoji.
+static EMOJI_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Kinds of log messages that are prefixed with emoji when `--emoji` is used.
+/// Warnings and errors are prefixed automatically.
+#[derive(Clone, Copy, Debug)]
+pub enum Emoji {
+    Success,
+    Archive,
+    Upload,
+    Link,
+}
+
+impl Emoji {
+    /// Returns the emoji followed by a space, or an empty string if emoji are disabled.
+    pub fn prefix(self) -> &'static str {
+        if !EMOJI_ENABLED.load(Ordering::Relaxed) {
+            return "";
+        }
+        match self {
+            Emoji::Success => "\u{2705} ",
+            Emoji::Archive => "\u{1f4e6} ",
+            Emoji::Upload => "\u{1f680} ",
+            Emoji::Link => "\u{1f517} ",
+        }
+    }
+}
+
+/// Enables emoji in log messages, unless the terminal cannot display them or colors
+/// were disabled (by `NO_COLOR` or the monochrome color scheme).
+pub fn enable_emoji(scheme: ColorScheme) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+        || matches!(scheme, ColorScheme::Monochrome);
+    if no_color || !std::io::stderr().is_terminal() || !is_utf8_locale() {
+        log::debug!("Emoji are not used, because the terminal might not support them");
+        return;
+    }
+    EMOJI_ENABLED.store(true, Ordering::Relaxed);
+}
+
+fn is_utf8_locale() -> bool {
+    if cfg!(windows) {
+        return true;
+    }
+    ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .is_some_and(|value| {
+            let value = value.to_lowercase();
+            value.contains("utf-8") || value.contains("utf8")
+        })
+}
+
+/// Color palette used for log output.
+#[derive(clap::ValueEnum, serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorScheme {
+    #[default]
+    Default,
+    /// Avoids distinguishing levels by red and green.
+    Deuteranopia,
+    /// Avoids red, which is hard to see with protanopia.
+    Protanopia,
+    /// Uses bold and italic text instead of colors.
+    Monochrome,
+}
+
+impl ColorScheme {
+    fn level_style(self, level: Level) -> Style {
+        match self {
+            ColorScheme::Default => match level {
+                Level::Trace => AnsiColor::Cyan.on_default(),
+                Level::Debug => AnsiColor::Blue.on_default(),
+                Level::Info => AnsiColor::Green.on_default(),
+                Level::Warn => AnsiColor::Yellow.on_default(),
+                Level::Error => AnsiColor::Red.on_default().effects(Effects::BOLD),
+            },
+            ColorScheme::Deuteranopia => match level {
+                Level::Trace => AnsiColor::White.on_default(),
+                Level::Debug => AnsiColor::Cyan.on_default(),
+                Level::Info => AnsiColor::Blue.on_default(),
+                Level::Warn => AnsiColor::Yellow.on_default(),
+                Level::Error => Ansi256Color(208).on_default().effects(Effects::BOLD),
+            },
+            ColorScheme::Protanopia => match level {
+                Level::Trace => AnsiColor::White.on_default(),
+                Level::Debug => AnsiColor::Cyan.on_default(),
+                Level::Info => AnsiColor::Blue.on_default(),
+                Level::Warn => Ansi256Color(180).on_default(),
+                Level::Error => AnsiColor::BrightYellow.on_default().effects(Effects::BOLD),
+            },
+            ColorScheme::Monochrome => match level {
+                Level::Trace | Level::Debug => Style::new().effects(Effects::DIMMED),
+                Level::Info => Style::new(),
+                Level::Warn => Style::new().effects(Effects::ITALIC),
+                Level::Error => Style::new().effects(Effects::BOLD),
+            },
+        }
+    }
+}
+
+/// Targets of log messages written by `--trace-http`.
+const HTTP_TARGETS: [&str; 4] = ["reqwest", "hyper", "rustls", "h2"];
+
+fn is_http_target(target: &str) -> bool {
+    HTTP_TARGETS.iter().any(|prefix| {
+        target
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+    })
+}
+
+/// Logger that also writes all messages of the HTTP libraries into a file.
+struct HttpTraceLogger {
+    inner: env_logger::Logger,
+    file: Mutex<std::fs::File>,
+}
+
+impl log::Log for HttpTraceLogger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        self.inner.enabled(metadata) || is_http_target(metadata.target())
+    }
+
+    fn log(&self, record: &log::Record) {
+        if is_http_target(record.target()) {
+            let mut file = self.file.lock().unwrap_or_else(|error| error.into_inner());
+            // The trace is only a debugging aid, it is not worth failing for
+            let _ = writeln!(
+                file,
+                "[{} {:<5} {}] {}",
+                humantime::format_rfc3339_micros(SystemTime::now()),
+                record.level(),
+                record.target(),
+                record.args()
+            );
+        }
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+        let _ = self
+            .file
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+            .flush();
+    }
+}
+
+/// Installs the logger. If `http_trace_file` is given, all messages of the HTTP libraries
+/// are also written into it.
+pub fn init(
+    mut builder: env_logger::Builder,
+    http_trace_file: Option<&Path>,
+) -> anyhow::Result<()> {
+    let Some(path) = http_trace_file else {
+        builder.init();
+        return Ok(());
+    };
+    let file = std::fs::File::create(path)
+        .with_context(|| anyhow::anyhow!("Cannot create HTTP trace file {path:?}"))?;
+    let logger = HttpTraceLogger {
+        inner: builder.build(),
+        file: Mutex::new(file),
+    };
+    log::set_max_level(LevelFilter::Trace);
+    log::set_boxed_logger(Box::new(logger)).context("cannot install logger")?;
+    log::info!("Writing HTTP trace to {path:?}");
+    Ok(())
+}
+
+/// Format of log messages.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq)]
+pub enum LogFormat {
+    /// Human-readable text.
+    #[default]
+    Text,
+    /// One JSON object per line, for log aggregation systems.
+    Json,
+}
+
+#[derive(serde::Serialize)]
+struct JsonRecord<'a> {
+    timestamp: String,
+    level: &'static str,
+    message: String,
+    module: &'a str,
+}
+
+/// Creates a logger that writes each message as a JSON object on a separate line.
+/// The filter can be overridden by `RUST_LOG`.
+pub fn build_json_logger(filter: LevelFilter) -> env_logger::Builder {
+    let mut builder = env_logger::builder();
+    builder.filter_level(filter).parse_default_env();
+    builder.format(|buf, record| {
+        let record = JsonRecord {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            level: record.level().as_str(),
+            message: record.args().to_string(),
+            module: record.module_path().unwrap_or(record.target()),
+        };
+        let line = serde_json::to_string(&record).map_err(std::io::Error::other)?;
+        writeln!(buf, "{line}")
+    });
+    builder
+}
+
+/// Configures the logger to highlight log levels using the given color scheme.
+pub fn apply_scheme(builder: &mut env_logger::Builder, scheme: ColorScheme) {
+    builder.format(move |buf, record| {
+        let style = scheme.level_style(record.level());
+        let emoji = match record.level() {
+            _ if !EMOJI_ENABLED.load(Ordering::Relaxed) => "",
+            Level::Error => "\u{274c} ",
+            Level::Warn => "\u{26a0}\u{fe0f} ",
+            _ => "",
+        };
+        writeln!(
+            buf,
+            "[{} {style}{:<5}{style:#} {}] {emoji}{}",
+            buf.timestamp(),
+            record.level(),
+            record.target(),
+            record.args()
+        )
+    });
+}
@@ -1,12 +1,69 @@
+mod archive;
+mod archive_diff;
+mod audit;
+mod benchmark;
+mod binary;
+mod cache;
+mod cargo_tree;
+mod checksum;
+mod client;
+mod commands;
+mod comments;
+mod config;
+mod coverage;
+mod environment;
+mod features;
+mod flamegraph;
+mod git_history;
+mod history;
+mod lines;
+mod lock_provenance;
+mod logging;
+mod makefile;
+mod notification;
+mod progress;
+mod rate_limit;
+mod readme;
+mod request_log;
+mod sccache;
+mod secrets;
+mod signing;
+mod snapshot;
+mod solution_template;
+mod stats;
+mod submodules;
+mod test_report;
+mod test_vectors;
+mod throttle;
+mod tls;
+mod todo_check;
+mod toolchain;
+mod unused_use;
+mod update;
+mod upload_stats;
+mod vendor;
+
+use crate::client::{validate_kelvin_url, KelvinClient};
+use crate::commands::cache::CacheArgs;
+use crate::commands::check_allowed_crates::CheckAllowedCratesArgs;
+use crate::commands::completion_install::CompletionInstallArgs;
+use crate::commands::config::{ConfigArgs, GlobalOptions};
+use crate::commands::courses::CoursesArgs;
+use crate::commands::grade_trend::GradeTrendArgs;
+use crate::commands::lint::LintArgs;
+use crate::commands::submit::SubmitArgs;
+use crate::commands::task_info::TaskInfoArgs;
+use crate::commands::task_list::TaskListArgs;
+use crate::commands::token::TokenArgs;
+use crate::commands::whoami::WhoamiArgs;
+use crate::config::{resolve_kelvin_url, GlobalConfig};
+use crate::logging::{ColorScheme, LogFormat};
 use anyhow::Context;
 use clap::Parser;
-use ignore::DirEntry;
 use log::LevelFilter;
-use reqwest::StatusCode;
-use std::io::{Seek, Write};
-use std::path::{Path, PathBuf};
-use zip::write::SimpleFileOptions;
-use zip::ZipWriter;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::{Command, ExitCode, Stdio};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
@@ -21,192 +78,280 @@ enum Args {
 struct InnerArgs {
     #[clap(subcommand)]
     subcmd: RootArgs,
+
+    /// Color scheme used for terminal output. The chosen scheme is remembered for future runs.
+    #[clap(long, value_enum, global = true)]
+    color_scheme: Option<ColorScheme>,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    Human,
+    Json,
 }
 
 #[derive(Parser, Debug)]
 enum RootArgs {
     /// Submit the current directory to Kelvin.
-    Submit(SubmitArgs),
+    Submit(Box<SubmitArgs>),
+    /// Install shell completions for your current shell.
+    CompletionInstall(CompletionInstallArgs),
+    /// Check if a new version of `cargo kelvin` is available.
+    UpdateCheck,
+    /// Show information about an assignment.
+    TaskInfo(TaskInfoArgs),
+    /// Check that the workspace only depends on crates allowed by an assignment.
+    CheckAllowedCrates(CheckAllowedCratesArgs),
+    /// Show how the score of your submits evolved over time.
+    GradeTrend(GradeTrendArgs),
+    /// Show information about the user that owns the API token.
+    Whoami(WhoamiArgs),
+    /// Inspect the configuration of `cargo kelvin`.
+    Config(ConfigArgs),
+    /// List courses in which you are enrolled.
+    Courses(CoursesArgs),
+    /// List tasks of your courses.
+    TaskList(TaskListArgs),
+    /// Check that the workspace meets the requirements of an assignment, without submitting it.
+    Lint(LintArgs),
+    /// Manage your API token.
+    Token(TokenArgs),
+    /// Manage the data cached by `cargo kelvin`.
+    Cache(CacheArgs),
 }
 
-#[derive(Parser, Debug)]
-struct SubmitArgs {
-    /// Assignment ID into which your code should be submitted.
-    /// You can find it in the URL of the task, i.e. `https://kelvin.cs.vsb.cz/task/<assignment-id>/<your-login>`.
-    assignment_id: u64,
+const DEFAULT_KELVIN_URL: &str = "https://kelvin.cs.vsb.cz";
 
+/// Arguments needed to communicate with Kelvin.
+#[derive(clap::Args, Debug)]
+struct ConnectionArgs {
     /// API token for submitting things to Kelvin.
     /// You can generate it at `https://kelvin.cs.vsb.cz/api_token`.
     /// You can pass it to `cargo kelvin` through an environment variable `KELVIN_API_TOKEN`.
+    /// If not given, the token stored by `cargo kelvin token rotate` is used.
     #[clap(long, env = "KELVIN_API_TOKEN")]
-    token: String,
-
-    #[clap(long, default_value = "https://kelvin.cs.vsb.cz")]
-    kelvin_url: String,
+    token: Option<String>,
 
-    /// Do not open the browser after uploading the submit.
-    #[clap(long, default_value_t = false)]
-    no_open: bool,
+    /// Address of the Kelvin instance. If not given, it is taken from the `KELVIN_URL`
+    /// environment variable, `kelvin-url` in `.kelvin.toml` or in the global config,
+    /// or `https://kelvin.cs.vsb.cz` is used.
+    #[clap(long)]
+    kelvin_url: Option<String>,
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct SubmitData {
-    id: u64,
-    url: String,
-}
-
-#[derive(serde::Deserialize, Debug)]
-struct TaskData {
-    name: String,
+impl ConnectionArgs {
+    fn create_client(&self, config: &GlobalConfig) -> anyhow::Result<KelvinClient> {
+        let (kelvin_url, source) = resolve_kelvin_url(self.kelvin_url.as_deref(), config)?;
+        log::debug!("Using Kelvin URL {kelvin_url} ({source})");
+        let kelvin_url = validate_kelvin_url(&kelvin_url)?;
+        let token = self.token.clone().or_else(|| config.token.clone()).context(
+            "An API token is required, pass it with `--token` or `KELVIN_API_TOKEN`. You can generate it at `https://kelvin.cs.vsb.cz/api_token`.",
+        )?;
+        Ok(KelvinClient::new(kelvin_url, token))
+    }
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct Response {
-    submit: SubmitData,
-    task: TaskData,
+fn submit(mut args: SubmitArgs, config: &GlobalConfig) -> anyhow::Result<ExitCode> {
+    let mut client = args.connection.create_client(config)?;
+    if args.accept_response_redirects {
+        client = client.accept_response_redirects();
+    }
+    client = client
+        .http_version(args.http_version)
+        .agent(args.kelvin_agent.clone());
+    if let Some(version) = args.kelvin_api_version {
+        client = client.api_version(version);
+    }
+    if let Some(fingerprint) = args.server_certificate_fingerprint {
+        client = client.pin_certificate(fingerprint);
+    }
+    if args.auto_trace_id {
+        args.kelvin_trace_id = Some(uuid::Uuid::new_v4());
+    }
+    if let Some(trace_id) = args.kelvin_trace_id {
+        client = client.trace_id(trace_id);
+    }
+    commands::submit::run_submit(args, &client)
 }
 
-fn main() -> anyhow::Result<()> {
-    env_logger::builder()
-        .filter_level(LevelFilter::Info)
-        .parse_default_env()
-        .init();
-
+fn main() -> anyhow::Result<ExitCode> {
     let Args::Kelvin(InnerArgs {
-        subcmd:
-            RootArgs::Submit(SubmitArgs {
-                assignment_id,
-                token,
-                kelvin_url,
-                no_open,
-            }),
+        subcmd,
+        color_scheme,
     }) = Args::parse();
 
-    let manifest = get_manifest_path()?;
-    let archive = compress_workspace(manifest)?;
-
-    let client = reqwest::blocking::Client::new();
-
-    let file = reqwest::blocking::multipart::Part::bytes(archive).file_name("submit.zip");
-    let form = reqwest::blocking::multipart::Form::new().part("solution", file);
-
-    let res = client
-        .post(format!("{kelvin_url}/api/submits/{assignment_id}"))
-        .header("Authorization", format!("Bearer {token}"))
-        .multipart(form)
-        .send()
-        .context("sending submit to Kelvin")?;
-    if res.status() != StatusCode::OK {
-        log::error!(
-            "The submit was not successful. Status error: {}",
-            res.status(),
-        );
-        log::debug!(
-            "Response content: {}",
-            res.text().context("getting content of HTTP response")?
-        );
-    } else {
-        let response: Response = res.json().context("deserializing response")?;
-        log::info!(
-            "Created submit #{} for task {}",
-            response.submit.id,
-            response.task.name
-        );
-        log::info!("You can find the submit at {}", response.submit.url);
-        if !no_open {
-            open::that(response.submit.url).context("opening browser")?;
+    let (mut config, config_error) = match GlobalConfig::load() {
+        Ok(config) => (config, None),
+        Err(error) => (GlobalConfig::default(), Some(error)),
+    };
+    let scheme = color_scheme.or(config.color_scheme).unwrap_or_default();
+
+    let builder = match &subcmd {
+        RootArgs::Submit(args) if args.log_format == LogFormat::Json => {
+            logging::build_json_logger(LevelFilter::Info)
+        }
+        _ => {
+            let mut builder = env_logger::builder();
+            builder.filter_level(LevelFilter::Info).parse_default_env();
+            logging::apply_scheme(&mut builder, scheme);
+            builder
+        }
+    };
+    let http_trace_file = match &subcmd {
+        RootArgs::Submit(args) if args.trace_http => Some(args.trace_file.as_path()),
+        _ => None,
+    };
+    logging::init(builder, http_trace_file)?;
+    if matches!(&subcmd, RootArgs::Submit(args) if args.emoji) {
+        logging::enable_emoji(scheme);
+    }
+
+    let config_loaded = config_error.is_none();
+    if let Some(error) = config_error {
+        // Do not overwrite a config file that we could not read
+        log::warn!("Cannot load global config: {error:?}");
+    } else if let Some(color_scheme) = color_scheme {
+        config.color_scheme = Some(color_scheme);
+        if let Err(error) = config.store() {
+            log::warn!("Cannot remember chosen color scheme: {error:?}");
+        }
+    }
+
+    match subcmd {
+        RootArgs::Submit(args) => {
+            #[cfg(feature = "testing")]
+            let panic_on_early_exit = args.panic_on_early_exit;
+            let result = submit(*args, &config);
+            #[cfg(feature = "testing")]
+            if panic_on_early_exit {
+                if let Err(error) = &result {
+                    panic!("{error:?}");
+                }
+            }
+            result
+        }
+        RootArgs::CompletionInstall(args) => {
+            commands::completion_install::run_completion_install(args)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::UpdateCheck => {
+            update::check_for_updates()?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::TaskInfo(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::task_info::run_task_info(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::CheckAllowedCrates(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::check_allowed_crates::run_check_allowed_crates(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::GradeTrend(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::grade_trend::run_grade_trend(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::Whoami(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::whoami::run_whoami(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::Courses(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::courses::run_courses(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::TaskList(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::task_list::run_task_list(args, &client)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::Lint(args) => {
+            let client = args.connection.create_client(&config)?;
+            commands::lint::run_lint(args, &client)
+        }
+        RootArgs::Token(args) => {
+            let client = args.connection().create_client(&config)?;
+            // Do not overwrite a config file that we could not read
+            let config = config_loaded.then_some(&mut config);
+            commands::token::run_token(args, &client, config)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::Cache(args) => {
+            commands::cache::run_cache(args)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        RootArgs::Config(args) => {
+            let global = GlobalOptions {
+                color_scheme,
+                config: &config,
+            };
+            commands::config::run_config(args, global)?;
+            Ok(ExitCode::SUCCESS)
         }
     }
+}
 
-    Ok(())
+fn home_dir() -> anyhow::Result<PathBuf> {
+    let home =
+        std::env::var_os("HOME").context("cannot find home directory, `$HOME` is not set")?;
+    Ok(PathBuf::from(home))
 }
 
-fn get_manifest_path() -> anyhow::Result<PathBuf> {
-    let metadata = cargo_metadata::MetadataCommand::new()
-        .exec()
-        .context("getting cargo metadata")?;
-    Ok(metadata
-        .workspace_root
-        .into_std_path_buf()
-        .join("Cargo.toml"))
+/// Directory where `cargo kelvin` stores its global configuration.
+fn config_dir() -> anyhow::Result<PathBuf> {
+    Ok(home_dir()?.join(".config").join("kelvin"))
 }
 
-fn is_valid_path(entry: &DirEntry) -> bool {
-    let path = entry.path();
-    if path.is_dir() {
-        return true;
-    }
-    path.is_file()
-        && path.extension().map_or(false, |ext| {
-            ext == "toml" || ext == "lock" || ext == "rs" || ext == "md" || ext == "txt"
-        })
+/// Directory where `cargo kelvin` stores its cached data.
+fn cache_dir() -> anyhow::Result<PathBuf> {
+    Ok(home_dir()?.join(".cache").join("kelvin"))
 }
 
-fn compress_workspace(manifest_path: PathBuf) -> anyhow::Result<Vec<u8>> {
-    let root_dir = manifest_path.parent().expect("Manifest path has no parent");
-
-    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
-
-    let mut file_count = 0;
-    let iter = ignore::WalkBuilder::new(&root_dir)
-        .max_filesize(Some(1024 * 1024))
-        .same_file_system(true)
-        .filter_entry(is_valid_path)
-        .build();
-    for file in iter {
-        match file {
-            Ok(file) => {
-                if !is_valid_path(&file) {
-                    continue;
-                }
-                if file.path().is_dir() {
-                    continue;
-                }
-                if file.path() == root_dir {
-                    continue;
-                }
-                let Ok(relative_path) = file.path().strip_prefix(&root_dir) else {
-                    continue;
-                };
-                if relative_path.starts_with("target") {
-                    continue;
-                }
-                if let Err(error) = write_file_to_zip(&mut zip, relative_path, file.path()) {
-                    log::warn!(
-                        "Cannot write file {:?} to ZIP archive: {error:?}",
-                        file.path()
-                    );
-                } else {
-                    file_count += 1;
-                }
-            }
-            Err(error) => log::warn!("Cannot include file {error:?}"),
-        }
+/// Asks the user a yes/no question on the terminal. An empty answer means `default`.
+fn confirm(question: &str, default: bool) -> anyhow::Result<bool> {
+    eprint!("{question} {}", if default { "[Y/n] " } else { "[y/N] " });
+    std::io::stderr().flush().context("flushing stderr")?;
+    let mut answer = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut answer)
+        .context("reading answer from stdin")?;
+    Ok(match answer.trim() {
+        "" => default,
+        answer => matches!(answer, "y" | "Y" | "yes"),
+    })
+}
+
+/// Asks the user for a password on the terminal without echoing it. If stdin is not
+/// a terminal, the first line of stdin is used.
+fn read_password(prompt: &str) -> anyhow::Result<String> {
+    let stdin = std::io::stdin();
+    let is_terminal = stdin.is_terminal();
+    if is_terminal {
+        eprint!("{prompt}");
+        std::io::stderr().flush().context("flushing stderr")?;
+        set_terminal_echo(false);
+    }
+    let mut password = String::new();
+    let result = stdin.lock().read_line(&mut password);
+    if is_terminal {
+        set_terminal_echo(true);
+        eprintln!();
     }
-    let data = zip
-        .finish()
-        .context("cannot create ZIP archive")?
-        .into_inner();
-    log::info!(
-        "Compressed {file_count} file{}, total size: {}B",
-        if file_count == 1 { "" } else { "s" },
-        data.len()
-    );
-    Ok(data)
+    result.context("reading password from stdin")?;
+    Ok(password.trim_end_matches(['\r', '\n']).to_string())
 }
 
-fn write_file_to_zip<W: Write + Seek>(
-    zip: &mut ZipWriter<W>,
-    relative_path: &Path,
-    fs_path: &Path,
-) -> anyhow::Result<()> {
-    let options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
-
-    zip.start_file_from_path(relative_path, options)
-        .with_context(|| anyhow::anyhow!("Cannot store {relative_path:?} into ZIP archive"))?;
-    let bytes = std::fs::read(fs_path)
-        .with_context(|| anyhow::anyhow!("Cannot read file at {fs_path:?}"))?;
-    zip.write(&bytes)
-        .context("cannot write bytes into ZIP archive")?;
-    Ok(())
+/// Turns the echo of typed characters in the terminal of stdin on or off with `stty`.
+fn set_terminal_echo(echo: bool) {
+    let status = Command::new("stty")
+        .arg(if echo { "echo" } else { "-echo" })
+        .stdin(Stdio::inherit())
+        .status();
+    if !status.is_ok_and(|status| status.success()) && !echo {
+        log::warn!("Cannot hide the typed password, `stty` is not available");
+    }
 }
@@ -0,0 +1,30 @@
+use crate::history::HISTORY_FILE;
+
+/// Name of the archive saved by the `submit` target of the generated Makefile.
+const MAKEFILE_ARCHIVE: &str = "kelvin-submit.zip";
+
+/// Generates a Makefile with `submit`, `dry-run`, `status` and `clean` targets for the
+/// given assignment.
+pub fn generate_makefile(assignment_id: u64, kelvin_url: &str) -> String {
+    format!(
+        "# Generated by cargo-kelvin\n\
+         ASSIGNMENT ?= {assignment_id}\n\
+         KELVIN_URL ?= {kelvin_url}\n\
+         ARCHIVE ?= {MAKEFILE_ARCHIVE}\n\
+         KELVIN ?= cargo kelvin\n\
+         \n\
+         .PHONY: submit dry-run status clean\n\
+         \n\
+         submit:\n\
+         \t$(KELVIN) submit $(ASSIGNMENT) --kelvin-url $(KELVIN_URL) --save-archive $(ARCHIVE)\n\
+         \n\
+         dry-run:\n\
+         \t$(KELVIN) submit $(ASSIGNMENT) --kelvin-url $(KELVIN_URL) --dry-run\n\
+         \n\
+         status:\n\
+         \t$(KELVIN) grade-trend $(ASSIGNMENT) --kelvin-url $(KELVIN_URL) --format table\n\
+         \n\
+         clean:\n\
+         \trm -f {HISTORY_FILE} $(ARCHIVE)\n"
+    )
+}
@@ -0,0 +1,46 @@
+//! Desktop notifications, only available on platforms supported by `notify-rust`.
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "windows"))]
+mod imp {
+    use anyhow::Context;
+    use notify_rust::{Notification, Timeout};
+
+    pub fn notify_graded(task: &str, score: Option<f64>, url: &str) -> anyhow::Result<()> {
+        let body = match score {
+            Some(score) => format!("Task {task} was graded with score {score}%\n{url}"),
+            None => format!("Task {task} was graded\n{url}"),
+        };
+        let mut notification = Notification::new();
+        notification
+            .summary("Kelvin submit graded")
+            .body(&body)
+            .timeout(Timeout::Milliseconds(30_000));
+
+        // Waiting for a click on an action would block until the notification is dismissed,
+        // so the URL is only shown in the body
+        notification.show().context("cannot show notification")?;
+        Ok(())
+    }
+
+    pub fn notify_error(message: &str) -> anyhow::Result<()> {
+        Notification::new()
+            .summary("Kelvin submit failed")
+            .body(message)
+            .show()
+            .context("cannot show notification")?;
+        Ok(())
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "windows")))]
+mod imp {
+    pub fn notify_graded(_task: &str, _score: Option<f64>, _url: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Desktop notifications are not supported on this platform")
+    }
+
+    pub fn notify_error(_message: &str) -> anyhow::Result<()> {
+        anyhow::bail!("Desktop notifications are not supported on this platform")
+    }
+}
+
+pub use imp::{notify_error, notify_graded};
@@ -0,0 +1,108 @@
+use anyhow::Context;
+use std::cell::Cell;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Stage of a submit reported in the progress file.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    Compressing,
+    Compressed,
+    Uploading,
+    Uploaded,
+    Complete,
+    Error,
+}
+
+#[derive(serde::Serialize)]
+struct Progress {
+    stage: Stage,
+    files_done: usize,
+    files_total: usize,
+    percent: usize,
+}
+
+/// Reports the progress of a submit as a JSON object in a file, for `--progress-file`.
+///
+/// The file is replaced atomically, so that a process polling it never reads a partial write.
+/// Problems with writing the file are not fatal, they are only logged.
+#[derive(Debug)]
+pub struct ProgressFile {
+    path: PathBuf,
+    stage: Cell<Option<Stage>>,
+    files: Cell<(usize, usize)>,
+}
+
+impl ProgressFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            stage: Cell::new(None),
+            files: Cell::new((0, 0)),
+        }
+    }
+
+    pub fn stage(&self) -> Option<Stage> {
+        self.stage.get()
+    }
+
+    /// Reports a new stage, keeping the file counts of the last compression.
+    pub fn set_stage(&self, stage: Stage) {
+        self.stage.set(Some(stage));
+        self.write();
+    }
+
+    /// Reports that `done` of `total` files were compressed. The file is only rewritten
+    /// when the percentage changes.
+    pub fn set_compressed_files(&self, done: usize, total: usize) {
+        let previous = self.files.replace((done, total));
+        if self.stage() == Some(Stage::Compressing)
+            && percent(previous.0, previous.1) == percent(done, total)
+        {
+            return;
+        }
+        self.stage.set(Some(Stage::Compressing));
+        self.write();
+    }
+
+    fn write(&self) {
+        let Some(stage) = self.stage() else {
+            return;
+        };
+        let (files_done, files_total) = self.files.get();
+        let progress = Progress {
+            stage,
+            files_done,
+            files_total,
+            percent: percent(files_done, files_total),
+        };
+        let content = serde_json::to_string(&progress).expect("cannot serialize progress");
+        if let Err(error) = write_atomically(&self.path, &content) {
+            log::warn!("{error:?}");
+        }
+    }
+}
+
+fn percent(done: usize, total: usize) -> usize {
+    match total {
+        0 => 0,
+        total => done * 100 / total,
+    }
+}
+
+fn write_atomically(path: &Path, content: &str) -> anyhow::Result<()> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::Builder::new()
+        .prefix(".kelvin-progress")
+        .tempfile_in(parent)
+        .with_context(|| anyhow::anyhow!("Cannot create temporary file in {parent:?}"))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| anyhow::anyhow!("Cannot write progress to {:?}", file.path()))?;
+    file.persist(path)
+        .with_context(|| anyhow::anyhow!("Cannot write progress to {path:?}"))?;
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use anyhow::Context;
+use reqwest::header::HeaderValue;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait after a 429 response if the server did not say how long to wait.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Enforces a minimum interval between consecutive submits.
+///
+/// The time of the last submit is stored on disk, so that the limit also applies across
+/// separate invocations of `cargo kelvin`.
+pub struct RateLimiter {
+    min_interval: Duration,
+    state_path: PathBuf,
+}
+
+impl RateLimiter {
+    /// Creates a rate limiter allowing at most `submits_per_minute` submits per minute.
+    /// Returns `None` if `submits_per_minute` is zero, i.e. there is no limit.
+    pub fn new(submits_per_minute: u32, state_path: PathBuf) -> Option<Self> {
+        if submits_per_minute == 0 {
+            return None;
+        }
+        Some(Self {
+            min_interval: Duration::from_secs(60) / submits_per_minute,
+            state_path,
+        })
+    }
+
+    /// Blocks until enough time has passed since the last submit.
+    pub fn wait(&self) {
+        let Some(last_submit) = self.last_submit() else {
+            return;
+        };
+        let elapsed = last_submit.elapsed().unwrap_or_default();
+        if let Some(remaining) = self.min_interval.checked_sub(elapsed) {
+            log::warn!(
+                "Rate limited, waiting {}s before submitting.",
+                remaining.as_secs_f64().ceil()
+            );
+            std::thread::sleep(remaining);
+        }
+    }
+
+    /// Remembers that a submit has just been performed.
+    pub fn record_submit(&self) -> anyhow::Result<()> {
+        if let Some(parent) = self.state_path.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| anyhow::anyhow!("Cannot create directory {parent:?}"))?;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        std::fs::write(&self.state_path, now.to_string())
+            .context("cannot store time of the last submit")
+    }
+
+    fn last_submit(&self) -> Option<SystemTime> {
+        let timestamp = std::fs::read_to_string(&self.state_path).ok()?;
+        let secs = timestamp.trim().parse::<u64>().ok()?;
+        Some(UNIX_EPOCH + Duration::from_secs(secs))
+    }
+}
+
+/// Parses the `Retry-After` header of a 429 response, which contains either the number of
+/// seconds to wait or the date after which the request can be retried.
+pub fn parse_retry_after(value: Option<&HeaderValue>) -> Duration {
+    retry_after_at(value, SystemTime::now())
+}
+
+fn retry_after_at(value: Option<&HeaderValue>, now: SystemTime) -> Duration {
+    let Some(value) = value.and_then(|value| value.to_str().ok()) else {
+        return DEFAULT_RETRY_AFTER;
+    };
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Duration::from_secs(seconds);
+    }
+    match parse_http_date(value) {
+        // A date in the past means that the request can be retried right away
+        Some(date) => date.duration_since(now).unwrap_or_default(),
+        None => {
+            log::debug!("Cannot parse Retry-After header `{value}`");
+            DEFAULT_RETRY_AFTER
+        }
+    }
+}
+
+/// Parses an HTTP date in the IMF-fixdate format, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`.
+fn parse_http_date(value: &str) -> Option<SystemTime> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let (_, date) = value.split_once(", ")?;
+    let [day, month, year, time, "GMT"] = date.split(' ').collect::<Vec<_>>()[..] else {
+        return None;
+    };
+    let month = MONTHS.iter().position(|name| *name == month)? + 1;
+    humantime::parse_rfc3339(&format!("{year}-{month:02}-{day}T{time}Z")).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{retry_after_at, DEFAULT_RETRY_AFTER};
+    use reqwest::header::HeaderValue;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn parse_retry_after_header() {
+        // Sun, 06 Nov 1994 08:49:37 GMT
+        let now = UNIX_EPOCH + Duration::from_secs(784111777);
+        let retry_after =
+            |value: &'static str| retry_after_at(Some(&HeaderValue::from_static(value)), now);
+
+        assert_eq!(retry_after("120"), Duration::from_secs(120));
+        assert_eq!(retry_after(" 5 "), Duration::from_secs(5));
+        assert_eq!(
+            retry_after("Sun, 06 Nov 1994 08:50:07 GMT"),
+            Duration::from_secs(30)
+        );
+        assert_eq!(retry_after("Sun, 06 Nov 1994 08:00:00 GMT"), Duration::ZERO);
+        assert_eq!(retry_after_at(None, now), DEFAULT_RETRY_AFTER);
+        assert_eq!(retry_after("soon"), DEFAULT_RETRY_AFTER);
+        assert_eq!(retry_after("-10"), DEFAULT_RETRY_AFTER);
+        assert_eq!(
+            retry_after("Sun, 06 Nov 1994 08:50:07 CET"),
+            DEFAULT_RETRY_AFTER
+        );
+    }
+}
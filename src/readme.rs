@@ -0,0 +1,59 @@
+use anyhow::Context;
+use std::path::Path;
+use std::time::SystemTime;
+
+/// Name of the README generated by `--append-readme` when the workspace has no `README.md`.
+pub const SUBMISSION_README_FILE: &str = "SUBMISSION_README.md";
+
+/// README of the workspace, to which `--append-readme` appends the submission metadata.
+pub const README_FILE: &str = "README.md";
+
+/// Information about the submit stored in the generated README.
+pub struct SubmissionInfo {
+    pub username: String,
+    pub assignment: String,
+    pub rustc_version: Option<String>,
+}
+
+/// Describes the submit and the files of the archive as a Markdown section.
+pub fn submission_metadata(info: &SubmissionInfo, archive: &[u8]) -> anyhow::Result<String> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut content = format!(
+        "## Submission\n\n\
+         - Student: {}\n\
+         - Assignment: {}\n\
+         - Submitted at: {}\n\
+         - Compiler: {}\n\n\
+         | File | Size |\n\
+         |------|------|\n",
+        info.username,
+        info.assignment,
+        humantime::format_rfc3339_seconds(SystemTime::now()),
+        info.rustc_version.as_deref().unwrap_or("unknown"),
+    );
+    for index in 0..zip.len() {
+        let file = zip
+            .by_index_raw(index)
+            .context("cannot read file from ZIP archive")?;
+        if file.is_file() {
+            content.push_str(&format!("| `{}` | {}B |\n", file.name(), file.size()));
+        }
+    }
+    Ok(content)
+}
+
+/// Returns the path and content of the generated README. The submission metadata is
+/// appended to a copy of the README of the workspace, if there is one.
+pub fn submission_readme(root: &Path, metadata: &str) -> anyhow::Result<(&'static str, String)> {
+    let path = root.join(README_FILE);
+    if !path.is_file() {
+        return Ok((
+            SUBMISSION_README_FILE,
+            format!("# Submission README\n\n{metadata}"),
+        ));
+    }
+    let readme = std::fs::read_to_string(&path)
+        .with_context(|| anyhow::anyhow!("Cannot read README from {path:?}"))?;
+    Ok((README_FILE, format!("{}\n\n{metadata}", readme.trim_end())))
+}
@@ -0,0 +1,195 @@
+use anyhow::Context;
+use reqwest::blocking::Request;
+use reqwest::header::{
+    HeaderMap, HeaderName, AUTHORIZATION, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use reqwest::{Method, StatusCode, Version};
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+/// Maximum number of bytes of a response body stored in the log.
+const MAX_LOGGED_BODY: usize = 10 * 1024;
+
+/// Headers that contain credentials, their values are never stored in the log.
+const REDACTED_HEADERS: [HeaderName; 4] = [AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE];
+
+/// Checks if the name of a header suggests that it contains credentials, e.g. `X-Api-Key`,
+/// `X-Auth-User` or `X-Csrf-Token`. Header names are always lowercase.
+fn is_credential_header(name: &HeaderName) -> bool {
+    let name = name.as_str();
+    name.contains("auth") || name.ends_with("-token") || name.ends_with("-key")
+}
+
+/// Parts of a request that are stored in the log.
+/// The request itself cannot be kept, because its body is consumed when it is sent.
+pub struct LoggedRequest {
+    method: Method,
+    url: Url,
+    headers: HeaderMap,
+}
+
+impl LoggedRequest {
+    pub fn new(request: &Request) -> Self {
+        Self {
+            method: request.method().clone(),
+            url: request.url().clone(),
+            headers: request.headers().clone(),
+        }
+    }
+}
+
+/// Stores HTTP requests sent to Kelvin and the received responses in a file,
+/// in the HTTP/1.1 message format. The API token and other credentials are redacted.
+pub struct RequestLog {
+    path: PathBuf,
+    include_response_body: bool,
+    /// Additional headers whose values are not stored, e.g. custom headers passed by the user.
+    redacted_headers: Vec<HeaderName>,
+}
+
+impl RequestLog {
+    /// Creates the log file, overwriting it if it already exists.
+    pub fn create(
+        path: &Path,
+        include_response_body: bool,
+        redacted_headers: Vec<HeaderName>,
+    ) -> anyhow::Result<Self> {
+        File::create(path).with_context(|| anyhow::anyhow!("Cannot create {path:?}"))?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            include_response_body,
+            redacted_headers,
+        })
+    }
+
+    /// Appends a request and its response to the log.
+    /// The request body is never stored.
+    pub fn record(
+        &self,
+        request: &LoggedRequest,
+        version: Version,
+        status: StatusCode,
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> anyhow::Result<()> {
+        let mut file = File::options()
+            .append(true)
+            .open(&self.path)
+            .with_context(|| anyhow::anyhow!("Cannot open {:?}", self.path))?;
+        let mut message = Vec::new();
+
+        let url = &request.url;
+        match url.query() {
+            Some(query) => writeln!(
+                message,
+                "{} {}?{query} HTTP/1.1",
+                request.method,
+                url.path()
+            )?,
+            None => writeln!(message, "{} {} HTTP/1.1", request.method, url.path())?,
+        }
+        if let Some(host) = url.host_str() {
+            match url.port() {
+                Some(port) => writeln!(message, "host: {host}:{port}")?,
+                None => writeln!(message, "host: {host}")?,
+            }
+        }
+        self.write_headers(&mut message, &request.headers)?;
+        writeln!(message, "\n<request body omitted>\n")?;
+
+        writeln!(message, "{version:?} {status}")?;
+        self.write_headers(&mut message, headers)?;
+        writeln!(message)?;
+        if self.include_response_body {
+            message.extend_from_slice(&body[..body.len().min(MAX_LOGGED_BODY)]);
+            if body.len() > MAX_LOGGED_BODY {
+                write!(
+                    message,
+                    "\n<{} more bytes omitted>",
+                    body.len() - MAX_LOGGED_BODY
+                )?;
+            }
+            writeln!(message, "\n")?;
+        }
+
+        file.write_all(&message)
+            .with_context(|| anyhow::anyhow!("Cannot write to {:?}", self.path))
+    }
+
+    fn write_headers(&self, message: &mut Vec<u8>, headers: &HeaderMap) -> std::io::Result<()> {
+        for (name, value) in headers {
+            if REDACTED_HEADERS.contains(name)
+                || self.redacted_headers.contains(name)
+                || is_credential_header(name)
+            {
+                writeln!(message, "{name}: <redacted>")?;
+            } else {
+                writeln!(
+                    message,
+                    "{name}: {}",
+                    String::from_utf8_lossy(value.as_bytes())
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{LoggedRequest, RequestLog};
+    use reqwest::header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION};
+    use reqwest::{Method, StatusCode, Version};
+    use url::Url;
+
+    #[test]
+    fn redact_credential_headers() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("request.log");
+        let log =
+            RequestLog::create(&path, false, vec![HeaderName::from_static("x-course")]).unwrap();
+
+        let mut headers = HeaderMap::new();
+        for (name, value) in [
+            (AUTHORIZATION.as_str(), "Bearer secret-token"),
+            ("x-api-key", "secret-key"),
+            ("x-upstream-auth", "secret-auth"),
+            ("x-session-token", "secret-session"),
+            ("x-course", "secret-course"),
+            ("x-cargo-kelvin-version", "0.1.0"),
+        ] {
+            headers.insert(
+                HeaderName::from_static(name),
+                HeaderValue::from_static(value),
+            );
+        }
+        let request = LoggedRequest {
+            method: Method::POST,
+            url: Url::parse("https://kelvin.cs.vsb.cz/api/submit").unwrap(),
+            headers,
+        };
+        log.record(
+            &request,
+            Version::HTTP_11,
+            StatusCode::OK,
+            &HeaderMap::new(),
+            b"",
+        )
+        .unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(!content.contains("secret"), "{content}");
+        for name in [
+            "authorization",
+            "x-api-key",
+            "x-upstream-auth",
+            "x-session-token",
+            "x-course",
+        ] {
+            assert!(content.contains(&format!("{name}: <redacted>\n")), "{name}");
+        }
+        assert!(content.contains("x-cargo-kelvin-version: 0.1.0\n"));
+    }
+}
@@ -0,0 +1,71 @@
+use anyhow::Context;
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+
+/// Name of the file with the statistics of `sccache` stored in the archive.
+pub const SCCACHE_STATS_FILE: &str = "sccache_stats.json";
+
+pub struct SccacheStats {
+    /// JSON statistics printed by `sccache`.
+    pub content: String,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+}
+
+impl SccacheStats {
+    /// Percentage of compilations served from the cache, `None` if nothing was compiled.
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.cache_hits + self.cache_misses {
+            0 => None,
+            total => Some(self.cache_hits as f64 * 100.0 / total as f64),
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct StatsOutput {
+    stats: Stats,
+}
+
+#[derive(serde::Deserialize)]
+struct Stats {
+    cache_hits: LanguageCounts,
+    cache_misses: LanguageCounts,
+}
+
+#[derive(serde::Deserialize)]
+struct LanguageCounts {
+    /// Counts per language, e.g. `Rust`.
+    counts: HashMap<String, u64>,
+}
+
+/// Returns the statistics of the `sccache` server, or `None` if `sccache` is not installed.
+pub fn sccache_stats() -> anyhow::Result<Option<SccacheStats>> {
+    let installed = Command::new("sccache")
+        .arg("--version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success());
+    if !installed {
+        return Ok(None);
+    }
+    let output = Command::new("sccache")
+        .args(["--show-stats", "--stats-format", "json"])
+        .output()
+        .context("cannot run `sccache --show-stats`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`sccache --show-stats` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let content = String::from_utf8_lossy(&output.stdout).into_owned();
+    let stats: StatsOutput = serde_json::from_str(&content)
+        .context("cannot parse the output of `sccache --show-stats`")?;
+    Ok(Some(SccacheStats {
+        content,
+        cache_hits: stats.stats.cache_hits.counts.values().sum(),
+        cache_misses: stats.stats.cache_misses.counts.values().sum(),
+    }))
+}
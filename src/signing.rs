@@ -0,0 +1,53 @@
+use anyhow::Context;
+use ed25519_dalek::pkcs8::{DecodePrivateKey, EncodePublicKey};
+use rsa::pkcs1::DecodeRsaPrivateKey;
+use rsa::pkcs8::LineEnding;
+use rsa::{Pkcs1v15Sign, RsaPrivateKey, RsaPublicKey};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+
+pub struct ArchiveSignature {
+    pub signature: Vec<u8>,
+    /// Public key corresponding to the signing key, in PEM format.
+    pub public_key_pem: String,
+}
+
+/// Signs the SHA-256 digest of the archive with a RSA or Ed25519 private key stored in PEM
+/// format.
+pub fn sign_archive(key_path: &Path, archive: &[u8]) -> anyhow::Result<ArchiveSignature> {
+    let pem = std::fs::read_to_string(key_path)
+        .with_context(|| anyhow::anyhow!("Cannot read private key from {key_path:?}"))?;
+    let digest = Sha256::digest(archive);
+
+    if let Ok(key) = ed25519_dalek::SigningKey::from_pkcs8_pem(&pem) {
+        use ed25519_dalek::Signer;
+
+        let signature = key.sign(&digest);
+        let public_key_pem = key
+            .verifying_key()
+            .to_public_key_pem(LineEnding::LF)
+            .context("cannot encode Ed25519 public key")?;
+        return Ok(ArchiveSignature {
+            signature: signature.to_bytes().to_vec(),
+            public_key_pem,
+        });
+    }
+
+    let key = RsaPrivateKey::from_pkcs8_pem(&pem)
+        .or_else(|_| RsaPrivateKey::from_pkcs1_pem(&pem))
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "{key_path:?} does not contain a RSA or Ed25519 private key in PEM format"
+            )
+        })?;
+    let signature = key
+        .sign(Pkcs1v15Sign::new::<Sha256>(), &digest)
+        .context("cannot sign archive with RSA key")?;
+    let public_key_pem = RsaPublicKey::from(&key)
+        .to_public_key_pem(LineEnding::LF)
+        .context("cannot encode RSA public key")?;
+    Ok(ArchiveSignature {
+        signature,
+        public_key_pem,
+    })
+}
@@ -0,0 +1,183 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Stores the uncommitted state of a git workspace in the stash, so that the exact state
+/// of the workspace that was submitted can be recovered later.
+///
+/// The working tree is restored immediately after the stash is created, so the archive is
+/// still created from the working tree. [`WorkspaceSnapshot::verify_unchanged`] checks that it
+/// was not modified since the snapshot was created. The stash entry is removed again once the
+/// snapshot is dropped, unless it should be kept.
+pub struct WorkspaceSnapshot {
+    root: PathBuf,
+    /// Message of the stash entry, `None` if there were no uncommitted changes to stash.
+    stash: Option<String>,
+    /// ID of the git tree with all files of the workspace when the snapshot was created.
+    tree: String,
+    keep: bool,
+}
+
+impl WorkspaceSnapshot {
+    pub fn create(root: &Path, keep: bool) -> anyhow::Result<Self> {
+        let inside_repo = Command::new("git")
+            .args(["rev-parse", "--is-inside-work-tree"])
+            .current_dir(root)
+            .output()
+            .context("Cannot run `git`, is it installed?")?;
+        if !inside_repo.status.success() {
+            anyhow::bail!("Workspace at {root:?} is not a git repository");
+        }
+
+        let mut snapshot = Self {
+            root: root.to_path_buf(),
+            stash: None,
+            tree: String::new(),
+            keep,
+        };
+        let status = git(root, &["status", "--porcelain"])?;
+        if status.trim().is_empty() {
+            log::info!("Workspace has no uncommitted changes, nothing is stored in `git stash`");
+        } else {
+            let timestamp = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            let message = format!("cargo-kelvin-snapshot-{timestamp}");
+            git(
+                root,
+                &["stash", "push", "--include-untracked", "-m", &message],
+            )?;
+            snapshot.stash = Some(message);
+            // Never remove the stash entry if the working tree could not be restored from it.
+            snapshot.keep = true;
+            git(root, &["stash", "apply", "--index", &snapshot.stash_ref()?]).context(
+                "Cannot restore workspace from snapshot, your changes are stored in `git stash`",
+            )?;
+            snapshot.keep = keep;
+            log::info!("Created workspace snapshot `{}`", snapshot.name());
+        }
+        snapshot.tree = working_tree_id(root)?;
+        Ok(snapshot)
+    }
+
+    /// Fails if a file of the workspace was modified, added or removed since the snapshot
+    /// was created.
+    pub fn verify_unchanged(&self) -> anyhow::Result<()> {
+        if working_tree_id(&self.root)? != self.tree {
+            anyhow::bail!(
+                "The workspace was modified while the archive was being created, submit again to upload its current state"
+            );
+        }
+        Ok(())
+    }
+
+    fn name(&self) -> &str {
+        self.stash.as_deref().unwrap_or_default()
+    }
+
+    /// Finds the stash entry of this snapshot, in case other entries were added in the meantime.
+    fn stash_ref(&self) -> anyhow::Result<String> {
+        let list = git(&self.root, &["stash", "list", "--format=%gd %s"])?;
+        list.lines()
+            .find(|line| line.ends_with(self.name()))
+            .and_then(|line| line.split_whitespace().next())
+            .map(|reference| reference.to_string())
+            .ok_or_else(|| anyhow::anyhow!("Cannot find stash entry `{}`", self.name()))
+    }
+
+    fn remove(&self) -> anyhow::Result<()> {
+        git(&self.root, &["stash", "drop", &self.stash_ref()?])?;
+        Ok(())
+    }
+}
+
+impl Drop for WorkspaceSnapshot {
+    fn drop(&mut self) {
+        if self.stash.is_none() {
+            return;
+        }
+        if self.keep {
+            log::info!(
+                "Workspace snapshot was kept in `git stash` as `{}`",
+                self.name()
+            );
+        } else if let Err(error) = self.remove() {
+            log::warn!(
+                "Cannot remove workspace snapshot `{}`: {error:?}",
+                self.name()
+            );
+        }
+    }
+}
+
+/// Returns the ID of a git tree with all files of the repository that are not ignored,
+/// including untracked ones. The index of the repository is not modified.
+fn working_tree_id(root: &Path) -> anyhow::Result<String> {
+    let dir = tempfile::tempdir().context("Cannot create a temporary directory")?;
+    let index = dir.path().join("index");
+    run_git(root, &["add", "--all"], Some(&index))?;
+    Ok(run_git(root, &["write-tree"], Some(&index))?
+        .trim()
+        .to_string())
+}
+
+/// Runs a git command in `root` and returns its standard output.
+pub fn git(root: &Path, args: &[&str]) -> anyhow::Result<String> {
+    run_git(root, args, None)
+}
+
+/// Runs a git command, with a different index file if given.
+fn run_git(root: &Path, args: &[&str], index: Option<&Path>) -> anyhow::Result<String> {
+    let mut command = Command::new("git");
+    command.args(args).current_dir(root);
+    if let Some(index) = index {
+        command.env("GIT_INDEX_FILE", index);
+    }
+    let output = command
+        .output()
+        .with_context(|| anyhow::anyhow!("Cannot run `git {}`", args.join(" ")))?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`git {}` failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{git, WorkspaceSnapshot};
+
+    #[test]
+    fn detect_modifications_after_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path();
+        git(root, &["init", "--quiet"]).unwrap();
+        std::fs::write(root.join("main.rs"), "fn main() {}").unwrap();
+        // `git stash` also needs an identity
+        git(root, &["config", "user.name", "Test"]).unwrap();
+        git(root, &["config", "user.email", "test@example.com"]).unwrap();
+        git(root, &["add", "main.rs"]).unwrap();
+        git(root, &["commit", "--quiet", "-m", "Initial"]).unwrap();
+        std::fs::write(root.join("main.rs"), "fn main() { todo!() }").unwrap();
+        std::fs::write(root.join("lib.rs"), "").unwrap();
+
+        let snapshot = WorkspaceSnapshot::create(root, false).unwrap();
+        // The uncommitted changes are still in the working tree
+        assert_eq!(
+            std::fs::read_to_string(root.join("main.rs")).unwrap(),
+            "fn main() { todo!() }"
+        );
+        assert!(root.join("lib.rs").is_file());
+        snapshot.verify_unchanged().unwrap();
+
+        std::fs::write(root.join("lib.rs"), "pub fn solve() {}").unwrap();
+        assert!(snapshot.verify_unchanged().is_err());
+        drop(snapshot);
+        assert_eq!(git(root, &["stash", "list"]).unwrap(), "");
+    }
+}
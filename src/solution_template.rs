@@ -0,0 +1,137 @@
+use crate::comments::strip_comments;
+use regex::Regex;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// First line of the comment with the required signatures added to `src/lib.rs`.
+const REQUIRED_SIGNATURES_HEADER: &str = "// REQUIRED SIGNATURES";
+
+/// Matches the signatures of functions and the headers of types and traits, up to their
+/// body or the end of the item.
+const SIGNATURE_PATTERN: &str = r"(?m)^[ \t]*(?:pub(?:\([^)]*\))?\s+)?(?:(?:const|async|unsafe|extern\s+\x22[^\x22]*\x22)\s+)*(?:fn|struct|enum|trait|type)\s+\w+[^{;]*";
+
+/// Returns the item signatures defined by a solution template, each on a single line.
+pub fn required_signatures(template: &str) -> Vec<String> {
+    let mut seen = HashSet::new();
+    find_signatures(template)
+        .into_iter()
+        .filter(|signature| seen.insert(normalize_signature(signature)))
+        .collect()
+}
+
+/// Returns the required signatures that are not defined in any Rust file in the `src`
+/// directory of the workspace.
+pub fn missing_signatures(root: &Path, required: &[String]) -> Vec<String> {
+    let mut defined = HashSet::new();
+    for entry in ignore::WalkBuilder::new(root.join("src")).build().flatten() {
+        let path = entry.path();
+        if !path.is_file() || path.extension().is_none_or(|ext| ext != "rs") {
+            continue;
+        }
+        match std::fs::read_to_string(path) {
+            Ok(content) => defined.extend(
+                find_signatures(&content)
+                    .iter()
+                    .map(|signature| normalize_signature(signature)),
+            ),
+            Err(error) => log::warn!("Cannot read {path:?}: {error:?}"),
+        }
+    }
+    required
+        .iter()
+        .filter(|signature| !defined.contains(&normalize_signature(signature)))
+        .cloned()
+        .collect()
+}
+
+/// Adds the required signatures as a comment at the beginning of `content`.
+pub fn inject_signatures(content: &str, required: &[String]) -> String {
+    let mut injected = format!("{REQUIRED_SIGNATURES_HEADER}\n");
+    for signature in required {
+        injected.push_str(&format!("// {signature}\n"));
+    }
+    injected.push('\n');
+    injected.push_str(content);
+    injected
+}
+
+fn find_signatures(source: &str) -> Vec<String> {
+    let regex = Regex::new(SIGNATURE_PATTERN).expect("Invalid signature pattern");
+    regex
+        .find_iter(&strip_comments(source))
+        .map(|signature| {
+            signature
+                .as_str()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .replace("( ", "(")
+                .replace(", )", ")")
+                .replace(" )", ")")
+        })
+        .collect()
+}
+
+/// Collapses whitespace, removes it around punctuation and removes trailing commas, so that
+/// differently formatted signatures can be compared.
+fn normalize_signature(signature: &str) -> String {
+    let joins = |c: char| !(c.is_alphanumeric() || c == '_' || c == '\'' || c == '"');
+    let mut normalized = String::new();
+    for token in signature.split_whitespace() {
+        if normalized.ends_with(joins) || token.starts_with(joins) {
+            normalized.push_str(token);
+        } else {
+            if !normalized.is_empty() {
+                normalized.push(' ');
+            }
+            normalized.push_str(token);
+        }
+    }
+    normalized
+        .replace(",)", ")")
+        .replace(",>", ">")
+        .replace(",}", "}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inject_signatures, normalize_signature, required_signatures};
+
+    #[test]
+    fn normalize_template_signatures() {
+        let template = r#"
+/// Sorts the numbers.
+pub fn sort(
+    numbers: &mut [u32],
+) {
+    todo!()
+}
+
+// fn commented_out() {}
+pub struct Graph<T> {
+    nodes: Vec<T>,
+}
+
+pub trait Solver: Send {
+    fn solve(&self, input: &str) -> Option<u64>;
+}
+"#;
+        let signatures = required_signatures(template);
+        assert_eq!(
+            signatures,
+            [
+                "pub fn sort(numbers: &mut [u32])",
+                "pub struct Graph<T>",
+                "pub trait Solver: Send",
+                "fn solve(&self, input: &str) -> Option<u64>",
+            ]
+        );
+        // Formatting of the student's code does not matter
+        assert_eq!(
+            normalize_signature("pub fn sort( numbers :&mut[ u32 ],)"),
+            normalize_signature(&signatures[0])
+        );
+        assert!(inject_signatures("fn main() {}\n", &signatures[..1])
+            .starts_with("// REQUIRED SIGNATURES\n// pub fn sort(numbers: &mut [u32])\n\n"));
+    }
+}
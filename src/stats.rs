@@ -0,0 +1,81 @@
+use crate::lines::count_lines;
+use anyhow::Context;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Name of the file with workspace statistics stored in the archive.
+pub const WORKSPACE_STATS_FILE: &str = "workspace_stats.json";
+
+/// Statistics about the files of a submit, for `--workspace-stats-file`.
+#[derive(serde::Serialize, Debug)]
+pub struct WorkspaceStats {
+    total_files: usize,
+    compressed_size: u64,
+    uncompressed_size: u64,
+    /// Number and uncompressed size of files per extension (empty for files without one).
+    extensions: BTreeMap<String, ExtensionStats>,
+    largest_file: Option<LargestFile>,
+    rust_files: usize,
+    rust_lines: usize,
+    /// Uncompressed size divided by the size of the archive.
+    compression_ratio: f64,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+struct ExtensionStats {
+    count: usize,
+    size: u64,
+}
+
+#[derive(serde::Serialize, Debug)]
+struct LargestFile {
+    path: String,
+    size: u64,
+}
+
+/// Computes statistics about the files stored in the ZIP archive.
+pub fn workspace_stats(archive: &[u8]) -> anyhow::Result<WorkspaceStats> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut total_files = 0;
+    let mut uncompressed_size = 0;
+    let mut extensions: BTreeMap<String, ExtensionStats> = BTreeMap::new();
+    let mut largest_file: Option<LargestFile> = None;
+    for index in 0..zip.len() {
+        let file = zip
+            .by_index_raw(index)
+            .context("cannot read file from ZIP archive")?;
+        if !file.is_file() {
+            continue;
+        }
+        total_files += 1;
+        uncompressed_size += file.size();
+        let extension = Path::new(file.name())
+            .extension()
+            .map(|ext| ext.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let stats = extensions.entry(extension).or_default();
+        stats.count += 1;
+        stats.size += file.size();
+        if largest_file
+            .as_ref()
+            .is_none_or(|largest| file.size() > largest.size)
+        {
+            largest_file = Some(LargestFile {
+                path: file.name().to_string(),
+                size: file.size(),
+            });
+        }
+    }
+    let compressed_size = archive.len() as u64;
+    Ok(WorkspaceStats {
+        total_files,
+        compressed_size,
+        uncompressed_size,
+        rust_files: extensions.get("rs").map_or(0, |stats| stats.count),
+        extensions,
+        largest_file,
+        rust_lines: count_lines(archive)?.loc,
+        compression_ratio: uncompressed_size as f64 / compressed_size.max(1) as f64,
+    })
+}
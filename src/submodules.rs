@@ -0,0 +1,91 @@
+use crate::snapshot::git;
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+
+/// Name of the file in which git stores the submodules of a repository.
+const GITMODULES_FILE: &str = ".gitmodules";
+
+/// Returns the paths of the git submodules of the repository containing `root` that are
+/// inside `root`, relative to it.
+pub fn git_submodules(root: &Path) -> anyhow::Result<Vec<PathBuf>> {
+    // The paths in `.gitmodules` are relative to the root of the repository, which can be
+    // above the workspace
+    let repository = match git(root, &["rev-parse", "--show-toplevel"]) {
+        Ok(output) => PathBuf::from(output.trim()),
+        Err(error) => {
+            log::debug!("Looking for submodules in the workspace root: {error:?}");
+            root.to_path_buf()
+        }
+    };
+    let path = repository.join(GITMODULES_FILE);
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+    let content =
+        std::fs::read_to_string(&path).with_context(|| anyhow::anyhow!("Cannot read {path:?}"))?;
+    let root = std::fs::canonicalize(root).unwrap_or_else(|_| root.to_path_buf());
+    let repository = std::fs::canonicalize(&repository).unwrap_or(repository);
+    Ok(parse_gitmodules(&content)
+        .into_iter()
+        .filter_map(|path| {
+            repository
+                .join(path)
+                .strip_prefix(&root)
+                .ok()
+                .map(Path::to_path_buf)
+        })
+        .collect())
+}
+
+/// Parses the `path` keys of the `[submodule "<name>"]` sections of a `.gitmodules` file.
+fn parse_gitmodules(content: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut in_submodule = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') {
+            in_submodule = line.starts_with("[submodule");
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        if in_submodule && key.trim() == "path" {
+            let value = value.trim();
+            let value = value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .unwrap_or(value);
+            paths.push(PathBuf::from(value));
+        }
+    }
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_gitmodules;
+    use std::path::PathBuf;
+
+    #[test]
+    fn parse_gitmodules_file() {
+        let content = r#"
+[submodule "algorithms"]
+	path = libs/algorithms
+	url = https://example.com/algorithms.git
+# A comment
+[submodule "data"]
+	url = https://example.com/data.git
+	path = "test data"
+[core]
+	path = not-a-submodule
+"#;
+        assert_eq!(
+            parse_gitmodules(content),
+            ["libs/algorithms", "test data"].map(PathBuf::from)
+        );
+    }
+}
@@ -0,0 +1,32 @@
+use crate::environment;
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+
+/// Default name of the file with the output of `cargo test` stored in the archive.
+pub const DEFAULT_TEST_REPORT: &str = "test_report.txt";
+
+pub struct TestReport {
+    pub content: String,
+    pub passed: bool,
+}
+
+/// Runs `cargo test` in the given directory and records its output, prefixed with
+/// the versions of the toolchain and the current time.
+pub fn run_tests(root: &Path) -> anyhow::Result<TestReport> {
+    log::info!("Running `cargo test`");
+    let output = Command::new("cargo")
+        .arg("test")
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo test`")?;
+
+    let mut content = environment::record_environment();
+    content.push('\n');
+    content.push_str(&String::from_utf8_lossy(&output.stderr));
+    content.push_str(&String::from_utf8_lossy(&output.stdout));
+    Ok(TestReport {
+        content,
+        passed: output.status.success(),
+    })
+}
@@ -0,0 +1,72 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Name of the directory in the archive with the failing cases found by property-based tests.
+pub const TEST_VECTORS_DIR: &str = "test_vectors";
+
+/// Name of the directories in which `proptest` stores the failing cases that it found.
+const PROPTEST_REGRESSIONS_DIR: &str = "proptest-regressions";
+
+/// Runs all tests of the workspace (including ignored ones), so that property-based tests
+/// can store the failing cases that they find, and returns the contents of the regression
+/// files, with paths relative to `root`.
+pub fn collect_test_vectors(root: &Path) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+    let output = Command::new("cargo")
+        .args(["test", "--workspace", "--", "--list"])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo test -- --list`")?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "`cargo test -- --list` has failed:\n{}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    let count = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter(|line| line.ends_with(": test"))
+        .count();
+    log::info!(
+        "Running {count} test{} to find test vectors",
+        if count == 1 { "" } else { "s" }
+    );
+    let status = Command::new("cargo")
+        .args(["test", "--workspace", "--", "--include-ignored"])
+        .current_dir(root)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .context("cannot run `cargo test`")?;
+    if !status.success() {
+        log::debug!("Some tests have failed while looking for test vectors");
+    }
+
+    let mut walkers = vec![ignore::WalkBuilder::new(root)];
+    // The build directory is ignored by git, but some setups store the regressions there
+    let target_dir = root.join("target").join(PROPTEST_REGRESSIONS_DIR);
+    if target_dir.is_dir() {
+        let mut walker = ignore::WalkBuilder::new(target_dir);
+        walker.standard_filters(false);
+        walkers.push(walker);
+    }
+    let mut files = Vec::new();
+    for walker in walkers {
+        for entry in walker.build().flatten() {
+            let path = entry.path();
+            let Ok(relative_path) = path.strip_prefix(root) else {
+                continue;
+            };
+            let in_regressions_dir = relative_path
+                .parent()
+                .is_some_and(|dir| dir.iter().any(|c| c == PROPTEST_REGRESSIONS_DIR));
+            if !in_regressions_dir || !path.is_file() {
+                continue;
+            }
+            let content = std::fs::read(path)
+                .with_context(|| anyhow::anyhow!("Cannot read test vectors from {path:?}"))?;
+            files.push((relative_path.to_path_buf(), content));
+        }
+    }
+    Ok(files)
+}
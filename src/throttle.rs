@@ -0,0 +1,52 @@
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Reader that limits how fast data can be read from the inner reader, using the token
+/// bucket algorithm. The bucket holds at most one second worth of data.
+pub struct ThrottledReader<R: Read> {
+    inner: R,
+    bytes_per_second: u64,
+    /// Number of bytes that can be read right now.
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl<R: Read> ThrottledReader<R> {
+    pub fn new(inner: R, bytes_per_second: u64) -> Self {
+        assert!(bytes_per_second > 0, "Bandwidth limit must not be zero");
+        Self {
+            inner,
+            bytes_per_second,
+            tokens: 0.0,
+            last_refill: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        let rate = self.bytes_per_second as f64;
+        self.tokens = (self.tokens + elapsed * rate).min(rate);
+        self.last_refill = now;
+    }
+}
+
+impl<R: Read> Read for ThrottledReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        self.refill();
+        if self.tokens < 1.0 {
+            let missing = 1.0 - self.tokens;
+            std::thread::sleep(Duration::from_secs_f64(
+                missing / self.bytes_per_second as f64,
+            ));
+            self.refill();
+        }
+        let allowed = (self.tokens as usize).clamp(1, buf.len());
+        let read = self.inner.read(&mut buf[..allowed])?;
+        self.tokens -= read as f64;
+        Ok(read)
+    }
+}
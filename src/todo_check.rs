@@ -0,0 +1,134 @@
+use crate::comments;
+use anyhow::Context;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+/// Matches unfinished code: `todo!()`, `unimplemented!()` and `// TODO` comments.
+const TODO_PATTERN: &str = r"(?i)\btodo!\s*\(|\bunimplemented!\s*\(|//\s*todo\b";
+
+/// Matches test attributes, e.g. `#[test]` or `#[tokio::test]`.
+const TEST_PATTERN: &str = r"#\[(?:\w+::)*test\b";
+
+/// Matches attributes that silence warnings about unused code.
+const DEAD_CODE_PATTERN: &str = r"#!?\[allow\([^)]*\bdead_code\b";
+
+/// Checks that Rust files of the workspace do not contain unfinished code or other
+/// forbidden patterns. Returns the number of found occurrences, each of them is reported.
+pub fn find_forbidden_patterns(
+    root: &Path,
+    check_todo: bool,
+    patterns: &[String],
+) -> anyhow::Result<usize> {
+    let mut regexes = Vec::new();
+    if check_todo {
+        regexes.push(Regex::new(TODO_PATTERN).expect("Invalid TODO pattern"));
+    }
+    for pattern in patterns {
+        regexes.push(
+            Regex::new(pattern).with_context(|| anyhow::anyhow!("Invalid pattern `{pattern}`"))?,
+        );
+    }
+
+    let lines = find_matching_lines(root, &regexes);
+    for line in &lines {
+        log::warn!("{}:{}: {}", line.path.display(), line.line, line.content);
+    }
+    Ok(lines.len())
+}
+
+/// Finds items marked with `#[allow(dead_code)]`, which might be unused code that could
+/// be removed.
+pub fn find_dead_code_allows(root: &Path) -> Vec<MatchedLine> {
+    let regex = Regex::new(DEAD_CODE_PATTERN).expect("Invalid dead code pattern");
+    find_matching_lines(root, &[regex])
+}
+
+/// Counts test functions in Rust files of the workspace, by searching for test attributes.
+pub fn count_tests(root: &Path) -> usize {
+    let regex = Regex::new(TEST_PATTERN).expect("Invalid test pattern");
+    find_matching_lines(root, &[regex]).len()
+}
+
+/// Line of a Rust file that matches one of the searched patterns.
+pub struct MatchedLine {
+    /// Path of the file, relative to the workspace root.
+    pub path: PathBuf,
+    pub line: usize,
+    pub content: String,
+}
+
+/// Finds lines of Rust files in the workspace that match any of the regexes.
+pub fn find_matching_lines(root: &Path, regexes: &[Regex]) -> Vec<MatchedLine> {
+    let mut lines = Vec::new();
+    for_each_rust_file(root, |relative_path, content| {
+        for (index, line) in content.lines().enumerate() {
+            if regexes.iter().any(|regex| regex.is_match(line)) {
+                lines.push(MatchedLine {
+                    path: relative_path.to_path_buf(),
+                    line: index + 1,
+                    content: line.trim().to_string(),
+                });
+            }
+        }
+    });
+    lines
+}
+
+/// Finds lines of Rust files in the workspace that contain the `unsafe` keyword (outside of
+/// comments and string literals). Files whose path relative to the workspace root matches
+/// one of the `allowed` patterns are skipped. Each occurrence is reported.
+pub fn find_unsafe_code(root: &Path, allowed: &[glob::Pattern]) -> Vec<MatchedLine> {
+    let mut lines = Vec::new();
+    for_each_rust_file(root, |relative_path, content| {
+        if allowed
+            .iter()
+            .any(|pattern| pattern.matches_path(relative_path))
+        {
+            log::debug!("Allowing `unsafe` code in {relative_path:?}");
+            return;
+        }
+        let content_lines: Vec<&str> = content.lines().collect();
+        for line in comments::unsafe_keyword_lines(content) {
+            let content = content_lines.get(line - 1).map_or("", |line| line.trim());
+            log::warn!("{}:{line}: {content}", relative_path.display());
+            lines.push(MatchedLine {
+                path: relative_path.to_path_buf(),
+                line,
+                content: content.to_string(),
+            });
+        }
+    });
+    lines
+}
+
+/// Calls `f` with the path (relative to `root`) and the content of each Rust file
+/// of the workspace.
+fn for_each_rust_file(root: &Path, mut f: impl FnMut(&Path, &str)) {
+    for entry in ignore::WalkBuilder::new(root)
+        .same_file_system(true)
+        .build()
+    {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(error) => {
+                log::warn!("Cannot check file {error:?}");
+                continue;
+            }
+        };
+        let path = entry.path();
+        let Ok(relative_path) = path.strip_prefix(root) else {
+            continue;
+        };
+        if relative_path.starts_with("target")
+            || !path.is_file()
+            || path.extension().is_none_or(|ext| ext != "rs")
+        {
+            continue;
+        }
+        let Ok(content) = std::fs::read_to_string(path) else {
+            log::warn!("Cannot read file {path:?}");
+            continue;
+        };
+        f(relative_path, &content);
+    }
+}
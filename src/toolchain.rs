@@ -0,0 +1,51 @@
+use anyhow::Context;
+use std::path::Path;
+use std::process::Command;
+
+/// Files that pin the Rust toolchain of a workspace, in the order in which rustup reads them.
+const TOOLCHAIN_FILES: [&str; 2] = ["rust-toolchain", "rust-toolchain.toml"];
+
+/// Returns the toolchain channel pinned by `rust-toolchain.toml` (or the legacy
+/// `rust-toolchain` file) in the workspace root, if there is one.
+pub fn pinned_toolchain(root: &Path) -> anyhow::Result<Option<String>> {
+    #[derive(serde::Deserialize)]
+    struct ToolchainFile {
+        toolchain: Toolchain,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct Toolchain {
+        channel: Option<String>,
+    }
+
+    for name in TOOLCHAIN_FILES {
+        let path = root.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| anyhow::anyhow!("Cannot read {path:?}"))?;
+        // The legacy file can also contain only the name of the channel
+        if name == "rust-toolchain" && !content.contains('[') {
+            return Ok(Some(content.trim().to_string()));
+        }
+        let file: ToolchainFile =
+            toml::from_str(&content).with_context(|| anyhow::anyhow!("Cannot parse {path:?}"))?;
+        return Ok(file.toolchain.channel);
+    }
+    Ok(None)
+}
+
+/// Returns the output of `rustc --version` in the workspace root, which uses the pinned
+/// toolchain.
+pub fn rustc_version(root: &Path) -> Option<String> {
+    let output = Command::new("rustc")
+        .arg("--version")
+        .current_dir(root)
+        .output()
+        .ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
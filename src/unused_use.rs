@@ -0,0 +1,157 @@
+use anyhow::Context;
+use cargo_metadata::diagnostic::{Applicability, Diagnostic};
+use cargo_metadata::{Message, PackageId, Target};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Parts of Rust files that rustc reported as unused imports and suggested to remove.
+#[derive(Debug, Default)]
+pub struct UnusedImports {
+    removals: HashMap<PathBuf, Vec<Range<usize>>>,
+}
+
+impl UnusedImports {
+    /// Removes the unused imports from the content of the file at `fs_path`.
+    /// Returns the new content and the original lines that were changed.
+    pub fn remove(&self, fs_path: &Path, content: &str) -> (String, Vec<String>) {
+        let Some(ranges) = normalize_path(fs_path).and_then(|path| self.removals.get(&path)) else {
+            return (content.to_string(), Vec::new());
+        };
+        let mut output = String::with_capacity(content.len());
+        let mut changed_lines: Vec<String> = Vec::new();
+        let mut position = 0;
+        for range in ranges {
+            if content.get(range.clone()).is_none() {
+                continue;
+            }
+            output.push_str(&content[position..range.start]);
+            position = range.end;
+
+            let line_start = content[..range.start]
+                .rfind('\n')
+                .map_or(0, |index| index + 1);
+            // Removals of whole lines also contain the line break
+            let end = range.start + content[range.clone()].trim_end().len();
+            let line_end = content[end..]
+                .find('\n')
+                .map_or(content.len(), |index| end + index);
+            let line = content[line_start..line_end].trim().to_string();
+            if changed_lines.last() != Some(&line) {
+                changed_lines.push(line);
+            }
+        }
+        output.push_str(&content[position..]);
+        (output, changed_lines)
+    }
+}
+
+/// Runs `cargo check` on all targets of the workspace and collects the imports that rustc
+/// reports as unused. An import is only removed if it is unused in every compilation of
+/// its target (e.g. also in the test build), so that the archive still compiles.
+pub fn find_unused_imports(root: &Path) -> anyhow::Result<UnusedImports> {
+    log::info!("Running `cargo check` to find unused imports");
+    let output = Command::new("cargo")
+        .args([
+            "check",
+            "--workspace",
+            "--all-targets",
+            "--message-format=json",
+        ])
+        .current_dir(root)
+        .output()
+        .context("cannot run `cargo check`")?;
+    if !output.status.success() {
+        log::debug!("{}", String::from_utf8_lossy(&output.stderr));
+        anyhow::bail!(
+            "`cargo check` failed, unused imports can only be removed from a workspace that compiles"
+        );
+    }
+
+    // How many times was each target compiled, and how many times was each removal suggested
+    let mut compilations: HashMap<String, usize> = HashMap::new();
+    let mut suggestions: HashMap<(String, PathBuf, usize, usize), usize> = HashMap::new();
+    for message in Message::parse_stream(output.stdout.as_slice()) {
+        match message.context("cannot parse `cargo check` output")? {
+            Message::CompilerArtifact(artifact) => {
+                *compilations
+                    .entry(target_key(&artifact.package_id, &artifact.target))
+                    .or_default() += 1;
+            }
+            Message::CompilerMessage(message) => {
+                let diagnostic = &message.message;
+                if diagnostic
+                    .code
+                    .as_ref()
+                    .is_none_or(|code| code.code != "unused_imports")
+                {
+                    continue;
+                }
+                let target = target_key(&message.package_id, &message.target);
+                for (file, range) in removal_suggestions(diagnostic) {
+                    let Some(path) = normalize_path(&root.join(file)) else {
+                        continue;
+                    };
+                    *suggestions
+                        .entry((target.clone(), path, range.start, range.end))
+                        .or_default() += 1;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut removals: HashMap<PathBuf, Vec<Range<usize>>> = HashMap::new();
+    for ((target, path, start, end), count) in suggestions {
+        if compilations.get(&target) == Some(&count) {
+            removals.entry(path).or_default().push(start..end);
+        }
+    }
+    for ranges in removals.values_mut() {
+        ranges.sort_by_key(|range| (range.start, range.end));
+        ranges.dedup();
+        // Overlapping suggestions could come from different targets, keep the first one
+        let mut end = 0;
+        ranges.retain(|range| {
+            let keep = range.start >= end;
+            if keep {
+                end = range.end;
+            }
+            keep
+        });
+    }
+    Ok(UnusedImports { removals })
+}
+
+/// Returns the file and its byte range for each machine-applicable suggestion of the
+/// diagnostic that removes code.
+fn removal_suggestions(diagnostic: &Diagnostic) -> Vec<(&str, Range<usize>)> {
+    diagnostic
+        .children
+        .iter()
+        .flat_map(|child| &child.spans)
+        .filter(|span| {
+            span.suggested_replacement.as_deref() == Some("")
+                && matches!(
+                    span.suggestion_applicability,
+                    Some(Applicability::MachineApplicable)
+                )
+        })
+        .map(|span| {
+            (
+                span.file_name.as_str(),
+                span.byte_start as usize..span.byte_end as usize,
+            )
+        })
+        .collect()
+}
+
+/// Identifies a target, a library and a binary of a package can have the same name.
+fn target_key(package_id: &PackageId, target: &Target) -> String {
+    format!("{} {:?} {}", package_id, target.kind, target.name)
+}
+
+fn normalize_path(path: &Path) -> Option<PathBuf> {
+    std::fs::canonicalize(path).ok()
+}
@@ -0,0 +1,84 @@
+use crate::cache_dir;
+use anyhow::Context;
+use semver::Version;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const RELEASES_URL: &str = "https://api.github.com/repos/kobzol/cargo-kelvin/releases/latest";
+
+/// How often should the update check be performed automatically during `submit`.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+#[derive(serde::Deserialize, Debug)]
+struct Release {
+    tag_name: String,
+}
+
+/// Checks if a newer version of `cargo kelvin` was released and notifies the user about it.
+pub fn check_for_updates() -> anyhow::Result<()> {
+    let current = Version::parse(env!("CARGO_PKG_VERSION")).expect("Invalid package version");
+    let latest = fetch_latest_version()?;
+    if let Err(error) = store_last_check() {
+        log::debug!("Cannot store update check timestamp: {error:?}");
+    }
+
+    if latest > current {
+        log::warn!(
+            "Update available: {latest} (you have {current}). Run `cargo install --force --git https://github.com/kobzol/cargo-kelvin` to upgrade."
+        );
+    } else {
+        log::info!("You are using the latest version ({current})");
+    }
+    Ok(())
+}
+
+/// Performs the update check, but at most once per day.
+/// Failures are not fatal, because the check is not essential.
+pub fn check_for_updates_periodically() {
+    match last_check() {
+        Some(time) if time.elapsed().unwrap_or_default() < CHECK_INTERVAL => {
+            log::debug!("Skipping update check, it was performed recently");
+        }
+        _ => {
+            if let Err(error) = check_for_updates() {
+                log::debug!("Cannot check for updates: {error:?}");
+            }
+        }
+    }
+}
+
+fn fetch_latest_version() -> anyhow::Result<Version> {
+    let release: Release = reqwest::blocking::Client::new()
+        .get(RELEASES_URL)
+        .header(
+            "User-Agent",
+            concat!("cargo-kelvin/", env!("CARGO_PKG_VERSION")),
+        )
+        .send()
+        .context("fetching latest release")?
+        .error_for_status()
+        .context("fetching latest release")?
+        .json()
+        .context("deserializing latest release")?;
+    let version = release.tag_name.trim_start_matches('v');
+    Version::parse(version)
+        .with_context(|| anyhow::anyhow!("Cannot parse release version `{version}`"))
+}
+
+fn last_check() -> Option<SystemTime> {
+    let path = cache_dir().ok()?.join("last_update_check");
+    let timestamp = std::fs::read_to_string(path).ok()?;
+    let secs = timestamp.trim().parse::<u64>().ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+fn store_last_check() -> anyhow::Result<()> {
+    let dir = cache_dir()?;
+    std::fs::create_dir_all(&dir)
+        .with_context(|| anyhow::anyhow!("Cannot create directory {dir:?}"))?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    std::fs::write(dir.join("last_update_check"), now.to_string())
+        .context("cannot write update check timestamp")
+}
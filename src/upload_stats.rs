@@ -0,0 +1,121 @@
+use anyhow::Context;
+use std::collections::BTreeMap;
+
+/// How many of the largest files are shown.
+const SHOWN_FILES: usize = 10;
+
+/// Shows which files take up the most space in the ZIP archive.
+pub fn log_upload_stats(archive: &[u8]) -> anyhow::Result<()> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut files = Vec::with_capacity(zip.len());
+    for index in 0..zip.len() {
+        let file = zip
+            .by_index_raw(index)
+            .context("cannot read file from ZIP archive")?;
+        if file.is_file() {
+            files.push((file.name().to_string(), file.compressed_size()));
+        }
+    }
+    files.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let total = archive.len().max(1) as f64;
+    let width = files
+        .iter()
+        .take(SHOWN_FILES)
+        .map(|(name, _)| name.len())
+        .max()
+        .unwrap_or(0);
+    log::info!(
+        "Largest files in the archive ({} of {}):",
+        files.len().min(SHOWN_FILES),
+        files.len()
+    );
+    for (name, size) in files.iter().take(SHOWN_FILES) {
+        log::info!(
+            "  {name:<width$}  {size:>8}B  {:>5.1}%{}",
+            *size as f64 / total * 100.0,
+            if is_required(name) {
+                ""
+            } else {
+                "  (could be excluded)"
+            }
+        );
+    }
+    Ok(())
+}
+
+/// Files that the grader needs to build the submit.
+fn is_required(name: &str) -> bool {
+    name.ends_with(".rs") || name.ends_with("Cargo.toml") || name.ends_with("Cargo.lock")
+}
+
+/// Group of the files in the root of the archive in the size breakdown.
+const ROOT_GROUP: &str = "<root>";
+
+/// Width of the size breakdown chart, in columns.
+const CHART_WIDTH: usize = 80;
+
+/// Longest shown directory name in the size breakdown chart, longer names are truncated.
+const MAX_NAME_WIDTH: usize = 24;
+
+/// Computes the compressed size of the files in each top-level directory of the archive
+/// (e.g. `src/`). Files in the root of the archive are grouped under `<root>`.
+pub fn size_by_directory(archive: &[u8]) -> anyhow::Result<BTreeMap<String, u64>> {
+    let mut zip =
+        zip::ZipArchive::new(std::io::Cursor::new(archive)).context("cannot read ZIP archive")?;
+    let mut sizes = BTreeMap::new();
+    for index in 0..zip.len() {
+        let file = zip
+            .by_index_raw(index)
+            .context("cannot read file from ZIP archive")?;
+        if !file.is_file() {
+            continue;
+        }
+        let group = match file.name().split_once('/') {
+            Some((dir, _)) => format!("{dir}/"),
+            None => ROOT_GROUP.to_string(),
+        };
+        *sizes.entry(group).or_insert(0) += file.compressed_size();
+    }
+    Ok(sizes)
+}
+
+/// Prints the sizes of directories as a bar chart that fits into 80 columns, from the
+/// largest directory.
+pub fn print_size_breakdown(sizes: &BTreeMap<String, u64>) {
+    let mut sizes: Vec<(&str, u64)> = sizes
+        .iter()
+        .map(|(dir, &size)| (dir.as_str(), size))
+        .collect();
+    sizes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    let total = sizes.iter().map(|(_, size)| size).sum::<u64>().max(1) as f64;
+    let largest = sizes.first().map_or(1, |(_, size)| *size).max(1) as f64;
+    let name_width = sizes
+        .iter()
+        .map(|(dir, _)| dir.chars().count())
+        .max()
+        .unwrap_or(0)
+        .min(MAX_NAME_WIDTH);
+    // Name, bar, size (`12345678B`) and percentage (`100.0%`), separated by two spaces
+    let bar_width = CHART_WIDTH - name_width - 2 - 2 - 9 - 2 - 6;
+    println!("Compressed size by directory:");
+    for (dir, size) in sizes {
+        let name: String = if dir.chars().count() > name_width {
+            let mut name: String = dir.chars().take(name_width - 1).collect();
+            name.push('…');
+            name
+        } else {
+            dir.to_string()
+        };
+        let bar = match (size as f64 / largest * bar_width as f64).round() as usize {
+            0 if size > 0 => 1,
+            length => length,
+        };
+        println!(
+            "{name:<name_width$}  {:<bar_width$}  {size:>8}B  {:>5.1}%",
+            "█".repeat(bar),
+            size as f64 / total * 100.0
+        );
+    }
+}
@@ -0,0 +1,74 @@
+use anyhow::Context;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Maximum total (uncompressed) size of vendored dependencies stored in the archive.
+const MAX_VENDOR_SIZE: u64 = 100 * 1024 * 1024;
+
+/// Configuration that makes Cargo use the vendored dependencies stored in the archive.
+pub const VENDOR_CARGO_CONFIG: &str = r#"[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "vendor"
+"#;
+
+/// Dependencies of the workspace downloaded by `cargo vendor` into a temporary directory,
+/// which is removed when this struct is dropped.
+pub struct VendoredDependencies {
+    dir: tempfile::TempDir,
+}
+
+impl VendoredDependencies {
+    pub fn create(workspace_root: &Path) -> anyhow::Result<Self> {
+        let dir = tempfile::tempdir().context("cannot create temporary directory")?;
+        log::info!("Vendoring dependencies");
+        let output = Command::new("cargo")
+            .arg("vendor")
+            .arg("--quiet")
+            .arg(dir.path().join("vendor"))
+            .current_dir(workspace_root)
+            .output()
+            .context("cannot run `cargo vendor`")?;
+        if !output.status.success() {
+            anyhow::bail!(
+                "`cargo vendor` failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(Self { dir })
+    }
+
+    /// Returns all vendored files, along with their paths in the archive (`vendor/...`).
+    pub fn files(&self) -> anyhow::Result<Vec<(PathBuf, PathBuf)>> {
+        let root = self.dir.path();
+        let mut files = Vec::new();
+        let mut total_size = 0;
+        // Vendored crates contain hidden `.cargo-checksum.json` files, so all filters are disabled
+        for entry in ignore::WalkBuilder::new(root.join("vendor"))
+            .standard_filters(false)
+            .build()
+        {
+            let entry = entry.context("cannot read vendored file")?;
+            if !entry.path().is_file() {
+                continue;
+            }
+            total_size += entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            let relative_path = entry
+                .path()
+                .strip_prefix(root)
+                .expect("Vendored file is outside of the vendor directory");
+            files.push((entry.path().to_path_buf(), relative_path.to_path_buf()));
+        }
+        if total_size > MAX_VENDOR_SIZE {
+            anyhow::bail!(
+                "Vendored dependencies are too large ({total_size}B, the limit is {MAX_VENDOR_SIZE}B)"
+            );
+        }
+        log::info!(
+            "Vendored {} files of dependencies, total size: {total_size}B",
+            files.len()
+        );
+        Ok(files)
+    }
+}